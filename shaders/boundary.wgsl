@@ -0,0 +1,79 @@
+//Must match Boundary::code in boundary.rs
+let BOUNDARY_TORUS: u32 = 0u;
+let BOUNDARY_DEAD: u32 = 1u;
+let BOUNDARY_ALIVE: u32 = 2u;
+let BOUNDARY_MIRROR: u32 = 3u;
+let BOUNDARY_KLEIN: u32 = 4u;
+let BOUNDARY_CYLINDER: u32 = 5u;
+
+fn wrap_axis(v: i32, size: i32) -> i32 {
+    return ((v % size) + size) % size;
+}
+
+//Reflects about the edge, repeating the edge cell. Neighborhoods are smaller than the grid, so one reflection is enough.
+fn mirror_axis(v: i32, size: i32) -> i32 {
+    if (v < 0) {
+        return -v - 1;
+    }
+    if (v >= size) {
+        return 2 * size - v - 1;
+    }
+    return v;
+}
+
+//Maps a position that may be outside the grid to the cell it reads from.
+//Returns -1 in x for positions outside a fixed boundary.
+fn boundary_coords(pos: vec2<i32>, dims: vec2<i32>, mode: u32) -> vec2<i32> {
+    var p = pos;
+    if (mode == BOUNDARY_TORUS) {
+        p = vec2<i32>(wrap_axis(p.x, dims.x), wrap_axis(p.y, dims.y));
+    } else if (mode == BOUNDARY_MIRROR) {
+        p = vec2<i32>(mirror_axis(p.x, dims.x), mirror_axis(p.y, dims.y));
+    } else if (mode == BOUNDARY_KLEIN) {
+        //Crossing the top or bottom edge flips the grid left to right.
+        if (p.y < 0 || p.y >= dims.y) {
+            p.x = dims.x - 1 - p.x;
+        }
+        p = vec2<i32>(wrap_axis(p.x, dims.x), wrap_axis(p.y, dims.y));
+    } else if (mode == BOUNDARY_CYLINDER) {
+        p.x = wrap_axis(p.x, dims.x);
+    }
+    if (p.x < 0 || p.y < 0 || p.x >= dims.x || p.y >= dims.y) {
+        return vec2<i32>(-1, -1);
+    }
+    return p;
+}
+
+//Reads a cell through the boundary. Cells outside a fixed boundary are all 0, or all 1 for BOUNDARY_ALIVE.
+fn get_pixel_boundary(pos: vec2<i32>, dims: vec2<i32>, tex: texture_2d<f32>, mode: u32) -> vec4<f32> {
+    let p = boundary_coords(pos, dims, mode);
+    if (p.x < 0) {
+        return vec4<f32>(select(0.0, 1.0, mode == BOUNDARY_ALIVE));
+    }
+    return textureLoad(tex, p, 0);
+}
+
+//Texture coordinates for the renderers, which sample with a repeating sampler. Klein bottle copies above and below the grid are flipped.
+fn boundary_tex_coords(uv: vec2<f32>, mode: u32) -> vec2<f32> {
+    if (mode == BOUNDARY_KLEIN && i32(floor(uv.y)) % 2 != 0) {
+        return vec2<f32>(1.0 - uv.x, uv.y);
+    }
+    return uv;
+}
+
+//Whether the renderers draw anything at these texture coordinates. Copies of the grid are only shown along axes that wrap.
+fn boundary_shows(uv: vec2<f32>, mode: u32) -> bool {
+    let inside = uv >= vec2<f32>(0.0) && uv < vec2<f32>(1.0);
+    if (mode == BOUNDARY_TORUS || mode == BOUNDARY_KLEIN) {
+        return true;
+    }
+    if (mode == BOUNDARY_CYLINDER) {
+        return inside.y;
+    }
+    return inside.x && inside.y;
+}
+
+//Drawn beyond the edges of grids that don't wrap.
+fn boundary_outside_colour() -> vec4<f32> {
+    return vec4<f32>(0.12, 0.12, 0.14, 1.0);
+}
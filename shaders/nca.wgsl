@@ -0,0 +1,166 @@
+#include("update_scheme.wgsl");
+
+//Growing neural cellular automata. The 16 channels of a cell are split over 4 rgba32float textures.
+//A step takes three passes:
+//alive: marks cells that have a living cell in their 3x3 neighborhood before the update.
+//update: runs the perception and dense network, and adds the result to cells picked by the update scheme.
+//mask: kills cells that weren't alive before the update or aren't alive after it.
+//Every entry point uses its own bindings in group 0, so each pipeline only binds the textures it needs.
+
+struct NcaParams {
+    hidden: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+};
+
+let CHANNELS: u32 = 16u;
+let PERCEPTION: u32 = 48u;
+//Cells with alpha above this are alive.
+let ALIVE_THRESHOLD: f32 = 0.1;
+
+@group(0) @binding(0) var state0 : texture_2d<f32>;
+@group(0) @binding(1) var state1 : texture_2d<f32>;
+@group(0) @binding(2) var state2 : texture_2d<f32>;
+@group(0) @binding(3) var state3 : texture_2d<f32>;
+@group(0) @binding(4) var updated_out0 : texture_storage_2d<rgba32float, write>;
+@group(0) @binding(5) var updated_out1 : texture_storage_2d<rgba32float, write>;
+@group(0) @binding(6) var updated_out2 : texture_storage_2d<rgba32float, write>;
+@group(0) @binding(7) var updated_out3 : texture_storage_2d<rgba32float, write>;
+@group(0) @binding(8) var alive_out : texture_storage_2d<r32float, write>;
+@group(0) @binding(9) var updated0 : texture_2d<f32>;
+@group(0) @binding(10) var updated1 : texture_2d<f32>;
+@group(0) @binding(11) var updated2 : texture_2d<f32>;
+@group(0) @binding(12) var updated3 : texture_2d<f32>;
+@group(0) @binding(13) var alive_in : texture_2d<f32>;
+@group(0) @binding(14) var state_out0 : texture_storage_2d<rgba32float, write>;
+@group(0) @binding(15) var state_out1 : texture_storage_2d<rgba32float, write>;
+@group(0) @binding(16) var state_out2 : texture_storage_2d<rgba32float, write>;
+@group(0) @binding(17) var state_out3 : texture_storage_2d<rgba32float, write>;
+
+//w0, b0, w1, b1 packed one after the other. See NcaModel.
+struct Weights {
+    w: array<f32>,
+};
+
+@group(1) @binding(0) var<storage, read> weights : Weights;
+@group(1) @binding(1) var<uniform> params : NcaParams;
+@group(2) @binding(0) var<uniform> update_params : UpdateParams;
+
+fn wrap(pos: vec2<i32>, dims: vec2<i32>) -> vec2<i32> {
+    return (pos + dims) % dims;
+}
+
+//Largest alpha in the 3x3 neighborhood of a texture holding channels 0 to 3.
+fn max_alpha(tex: texture_2d<f32>, coords: vec2<i32>, dims: vec2<i32>) -> f32 {
+    var m = 0.0;
+    for (var dy: i32 = -1; dy <= 1; dy = dy + 1) {
+        for (var dx: i32 = -1; dx <= 1; dx = dx + 1) {
+            m = max(m, textureLoad(tex, wrap(coords + vec2<i32>(dx, dy), dims), 0).a);
+        }
+    }
+    return m;
+}
+
+@compute @workgroup_size(16,16)
+fn alive(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(state0);
+    let coords = vec2<i32>(global_id.xy);
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+    let is_alive = max_alpha(state0, coords, dimensions) > ALIVE_THRESHOLD;
+    textureStore(alive_out, coords, vec4<f32>(select(0.0, 1.0, is_alive)));
+}
+
+fn load_state(pos: vec2<i32>) -> array<vec4<f32>, 4> {
+    return array<vec4<f32>, 4>(
+        textureLoad(state0, pos, 0),
+        textureLoad(state1, pos, 0),
+        textureLoad(state2, pos, 0),
+        textureLoad(state3, pos, 0)
+    );
+}
+
+@compute @workgroup_size(16,16)
+fn update(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(state0);
+    let coords = vec2<i32>(global_id.xy);
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+    var cell = load_state(coords);
+
+    if (should_update(coords, update_params)) {
+        //Perception is identity, Sobel x and Sobel y for every channel, ordered [channel * 3 + filter].
+        //Sobel x is outer([1, 2, 1], [-1, 0, 1]) / 8 with rows going down the texture. Sobel y is its transpose.
+        var perception: array<f32, 48>;
+        var sobel_x = array<f32, 16>(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        var sobel_y = array<f32, 16>(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        let blur = vec3<f32>(1.0, 2.0, 1.0);
+        for (var dy: i32 = -1; dy <= 1; dy = dy + 1) {
+            for (var dx: i32 = -1; dx <= 1; dx = dx + 1) {
+                let wx = blur[dy + 1] * f32(dx) / 8.0;
+                let wy = f32(dy) * blur[dx + 1] / 8.0;
+                var n = load_state(wrap(coords + vec2<i32>(dx, dy), dimensions));
+                for (var c: u32 = 0u; c < CHANNELS; c = c + 1u) {
+                    let v = n[c / 4u][c % 4u];
+                    sobel_x[c] = sobel_x[c] + wx * v;
+                    sobel_y[c] = sobel_y[c] + wy * v;
+                }
+            }
+        }
+        for (var c: u32 = 0u; c < CHANNELS; c = c + 1u) {
+            perception[c * 3u] = cell[c / 4u][c % 4u];
+            perception[c * 3u + 1u] = sobel_x[c];
+            perception[c * 3u + 2u] = sobel_y[c];
+        }
+
+        //Dense layers. The hidden activations are folded straight into the output so they don't need storing.
+        let hidden = params.hidden;
+        let b0 = PERCEPTION * hidden;
+        let w1 = b0 + hidden;
+        let b1 = w1 + hidden * CHANNELS;
+        var dx = array<f32, 16>(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        for (var h: u32 = 0u; h < hidden; h = h + 1u) {
+            var sum = weights.w[b0 + h];
+            for (var i: u32 = 0u; i < PERCEPTION; i = i + 1u) {
+                sum = sum + perception[i] * weights.w[i * hidden + h];
+            }
+            let activated = max(sum, 0.0);
+            for (var c: u32 = 0u; c < CHANNELS; c = c + 1u) {
+                dx[c] = dx[c] + activated * weights.w[w1 + h * CHANNELS + c];
+            }
+        }
+        for (var c: u32 = 0u; c < CHANNELS; c = c + 1u) {
+            cell[c / 4u][c % 4u] = cell[c / 4u][c % 4u] + dx[c] + weights.w[b1 + c];
+        }
+    }
+
+    textureStore(updated_out0, coords, cell[0]);
+    textureStore(updated_out1, coords, cell[1]);
+    textureStore(updated_out2, coords, cell[2]);
+    textureStore(updated_out3, coords, cell[3]);
+}
+
+@compute @workgroup_size(16,16)
+fn mask(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(updated0);
+    let coords = vec2<i32>(global_id.xy);
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+    let was_alive = textureLoad(alive_in, coords, 0).r > 0.5;
+    let is_alive = max_alpha(updated0, coords, dimensions) > ALIVE_THRESHOLD;
+    let life = select(0.0, 1.0, was_alive && is_alive);
+    textureStore(state_out0, coords, textureLoad(updated0, coords, 0) * life);
+    textureStore(state_out1, coords, textureLoad(updated1, coords, 0) * life);
+    textureStore(state_out2, coords, textureLoad(updated2, coords, 0) * life);
+    textureStore(state_out3, coords, textureLoad(updated3, coords, 0) * life);
+}
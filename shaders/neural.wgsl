@@ -1,9 +1,5 @@
-fn get_pixel_wrap(pos: vec2<i32>, dims: vec2<i32>, tex: texture_2d<f32>) -> vec4<f32> {
-    let x = (pos.x + dims.x) %dims.x;
-    let y = (pos.y +  dims.y) %  dims.y;
-    let col: vec4<f32> = textureLoad(tex, vec2<i32>(x,y), 0);
-    return col;
- }
+#include("update_scheme.wgsl");
+#include("boundary.wgsl");
 
 fn close(a: f32, b: i32) -> bool {
     return abs(a - f32(b)) < 0.2;
@@ -13,88 +9,172 @@ fn inverse_gaussian(x: f32) -> f32 {
   return -1.0/pow(2., (0.6*pow(x, 2.)))+1.;
 }
 
-fn activation(x: f32) -> f32 {
-  return inverse_gaussian(x);
+struct Activation {
+    kind: u32,
+    params: vec4<f32>,
+};
+
+fn gaussian(x: f32, scale: f32) -> f32 {
+  return 1.0/pow(2., (scale*pow(x, 2.)));
+}
+
+//Case values must match Activation::kind in neural_parameters.rs
+fn activation(x: f32, a: Activation) -> f32 {
+  switch (a.kind) {
+    case 0u: { return x; }
+    case 1u: { return 1.0 / (1.0 + exp(-x)); }
+    case 2u: { return tanh(x); }
+    case 3u: { return max(x, 0.0); }
+    case 4u: { return sin(a.params.x * x); }
+    case 5u: { return abs(x); }
+    case 6u: { return gaussian(x, a.params.x); }
+    case 7u: { return 1.0 - gaussian(x, a.params.x); }
+    case 8u: { return a.params.x + x * (a.params.y + x * (a.params.z + x * a.params.w)); }
+    default: { return inverse_gaussian(x); }
+  }
 }
  
- struct Filter {
-     w0: f32,
-     w1: f32,
-     w2: f32,
-     w3: f32,
-     w4: f32,
-     w5: f32,
-     w6: f32,
-     w7: f32,
-     w8: f32
- };
+//Weights from every input channel to every output channel, indexed [(output * 4 + input) * kernel_size^2 + tap].
+//Taps are in row order, starting at the top left.
+struct Filter {
+    weights: array<f32>,
+};
+
+struct Channels {
+    count: u32,
+    kernel_size: u32,
+    residual: u32,
+    wrap: u32,
+    time_step: f32,
+    fire_rate: f32,
+    boundary: u32,
+    pad0: u32,
+    bias: vec4<f32>,
+    activations: array<Activation, 4>,
+};
+
+//See MemoryChannel in neural_parameters.rs
+struct MemoryChannel {
+    enabled: u32,
+    source: u32,
+    threshold: f32,
+    rearm: f32,
+    decay: f32,
+    fade_below: f32,
+    fade_decay: f32,
+    pad0: f32,
+};
+
+struct Memory {
+    channels: array<MemoryChannel, 3>,
+};
+
+//Largest kernel radius. Must match MAX_KERNEL_SIZE in neural_parameters.rs.
+let MAX_RADIUS: i32 = 7;
+let WORKGROUP_SIZE: i32 = 16;
+//Edge length of the shared tile, the workgroup plus a halo of MAX_RADIUS on each side.
+let TILE_SIZE: i32 = 30;
+
+//Every invocation in a workgroup reads its whole neighborhood from here instead of the texture.
+var<workgroup> tile: array<vec4<f32>, 900>;
+
+//User code from the neural window is spliced in here. The defaults are in NeuralState.
+fn user_activation(x: f32, a: Activation) -> f32 {
+    return #snippet("activation");
+}
+
+fn user_update(next: vec4<f32>, me: vec4<f32>, conv: vec4<f32>) -> vec4<f32> {
+    return #snippet("update");
+}
 
 @group(0) @binding(0) var input_texture : texture_2d<f32>;
 @group(0) @binding(1) var output_texture : texture_storage_2d<rgba8unorm, write>;
-@group(1) @binding(0) var<uniform> c_filter : Filter;
+@group(1) @binding(0) var<storage, read> c_filter : Filter;
+@group(1) @binding(1) var<uniform> c_channels : Channels;
+@group(1) @binding(2) var<uniform> c_memory : Memory;
+@group(2) @binding(0) var<uniform> update_params : UpdateParams;
 @compute @workgroup_size(16,16)
 fn main(
   @builtin(global_invocation_id) global_id : vec3<u32>,
+  @builtin(local_invocation_id) local_id : vec3<u32>,
+  @builtin(local_invocation_index) local_index : u32,
+  @builtin(workgroup_id) workgroup_id : vec3<u32>,
 ) {
     let dimensions = textureDimensions(input_texture);
     let coords = vec2<i32>(global_id.xy);
+    let radius = min(i32(c_channels.kernel_size) / 2, MAX_RADIUS);
+    let kernel_size = radius * 2 + 1;
+
+    //Load this workgroup's tile, only as much of the halo as the kernel needs.
+    let tile_edge = WORKGROUP_SIZE + radius * 2;
+    let tile_origin = vec2<i32>(workgroup_id.xy) * WORKGROUP_SIZE - vec2<i32>(radius);
+    for (var t: i32 = i32(local_index); t < tile_edge * tile_edge; t = t + WORKGROUP_SIZE * WORKGROUP_SIZE) {
+        let p = vec2<i32>(t % tile_edge, t / tile_edge);
+        tile[p.y * TILE_SIZE + p.x] = get_pixel_boundary(tile_origin + p, dimensions, input_texture, c_channels.boundary);
+    }
+    workgroupBarrier();
 
     if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
         return;
     }
-    let x: i32 = coords.x;
-    let y: i32 = coords.y;
-    
-    let c_left = vec2<i32>(x - 1,y);
-    let c_up = vec2<i32>(x,y + 1);
-    let c_right = vec2<i32>(x + 1,y);
-    let c_down = vec2<i32>(x,y - 1);
-
-    let c_left_up = vec2<i32>(x - 1,y + 1);
-    let c_right_up = vec2<i32>(x + 1,y + 1);
-    let c_left_down = vec2<i32>(x - 1,y - 1);
-    let c_right_down = vec2<i32>(x + 1,y - 1);
-
-    let me      =  get_pixel_wrap(coords,dimensions,input_texture);
-    let left    =  get_pixel_wrap(c_left,dimensions,input_texture).r;    
-    let right   =  get_pixel_wrap(c_right,dimensions,input_texture).r;    
-    let up      =  get_pixel_wrap(c_up,dimensions,input_texture).r;    
-    let down    =  get_pixel_wrap(c_down,dimensions,input_texture).r;    
-
-    let l_up    =  get_pixel_wrap(c_left_up,dimensions,input_texture).r;    
-    let r_up    =  get_pixel_wrap(c_right_up,dimensions,input_texture).r;    
-    let l_down  =  get_pixel_wrap(c_left_down,dimensions,input_texture).r;    
-    let r_down  =  get_pixel_wrap(c_right_down,dimensions,input_texture).r;    
-    let me_r = me.r;
-
-    let conv_filter : array<f32, 9> = array<f32, 9>
-    (-0.72, 0.90, -0.68, 
-    0.92, 0.68, 0.91, 
-    -0.68, 0.9, -0.72);
-
-    let conv = c_filter.w0 * l_up + c_filter.w1 * up + c_filter.w2 * r_up + c_filter.w3 * left + c_filter.w4 * me_r + c_filter.w5 * right + c_filter.w6 * l_down + c_filter.w7 * down + c_filter.w8 * r_down;
-    let val = clamp(activation(conv),0.0,1.0);
-    var g = me.g;
-    var b = me.b;
-    if (val > 0.8 && g < 0.5) {
-        g = 1.0;
-    } else {
-        g = g * .99;
+    //The fire rate mask is independent of the update scheme's random mask, so the seed is salted.
+    let fired = random_unit(coords, pcg_hash(update_params.step_seed ^ 0x5bd1e995u)) < c_channels.fire_rate;
+    if (!should_update(coords, update_params) || !fired) {
+        textureStore(output_texture, coords.xy, textureLoad(input_texture, coords, 0));
+        return;
     }
+    let local = vec2<i32>(local_id.xy) + vec2<i32>(radius);
+    let me = tile[local.y * TILE_SIZE + local.x];
+    let count = min(c_channels.count, 4u);
+    let taps = u32(kernel_size * kernel_size);
 
-    if (g < 0.2) {
-        g = g * .5;
+    var conv = vec4<f32>(0.0);
+    for (var row: i32 = 0; row < kernel_size; row = row + 1) {
+        for (var col: i32 = 0; col < kernel_size; col = col + 1) {
+            let tap = u32(row * kernel_size + col);
+            //The top row of the kernel is at y + radius.
+            let p = local + vec2<i32>(col - radius, radius - row);
+            let n = tile[p.y * TILE_SIZE + p.x];
+            for (var o: u32 = 0u; o < count; o = o + 1u) {
+                for (var i: u32 = 0u; i < count; i = i + 1u) {
+                    conv[o] = conv[o] + c_filter.weights[(o * 4u + i) * taps + tap] * n[i];
+                }
+            }
+        }
+    }
+    var next = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    for (var o: u32 = 0u; o < count; o = o + 1u) {
+        conv[o] = conv[o] + c_channels.bias[o];
+        var value = user_activation(conv[o], c_channels.activations[o]);
+        if (c_channels.residual != 0u) {
+            value = me[o] + c_channels.time_step * value;
+        }
+        if (c_channels.wrap != 0u) {
+            next[o] = fract(value);
+        } else {
+            next[o] = clamp(value, 0.0, 1.0);
+        }
     }
 
- 
-
-    let cell = vec4<f32>(val,g,0.0,1.0);
-     
+    //Colour channels the filter doesn't compute can remember when another channel fired.
+    for (var m: u32 = count; m < 3u; m = m + 1u) {
+        let memory = c_memory.channels[m];
+        if (memory.enabled == 0u) {
+            continue;
+        }
+        var g = me[m];
+        if (next[min(memory.source, 3u)] > memory.threshold && g < memory.rearm) {
+            g = 1.0;
+        } else {
+            g = g * memory.decay;
+        }
+        if (g < memory.fade_below) {
+            g = g * memory.fade_decay;
+        }
+        next[m] = g;
+    }
 
-    
-    
-    
-    
+    let cell = user_update(next, me, conv);
 
     textureStore(output_texture, coords.xy, cell);
-}
\ No newline at end of file
+}
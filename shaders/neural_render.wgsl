@@ -1,3 +1,4 @@
+#include("boundary.wgsl");
 // Vertex shader
 
 struct VertexInput {
@@ -18,6 +19,13 @@ struct Camera {
 struct RenderParams {
     window_size: vec2<i32>,
     sim_size: vec2<i32>,
+    species: u32,
+    channels: u32,
+    //See Boundary in boundary.rs.
+    boundary: u32,
+    pad2: u32,
+    //Display colour of each state channel. Only read here, see ChannelColours.
+    colours: array<vec4<f32>, 3>,
 };
 
 fn close(a: f32, b: i32) -> bool {
@@ -61,8 +69,14 @@ fn cam_to_tex_coords(cam: Camera, p: vec2<f32>) -> vec2<f32> {
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let dimensions = render_params.sim_size;
-    let cam2tex = cam_to_tex_coords(cam,in.tex_coords.xy);
+    let cam2tex = boundary_tex_coords(cam_to_tex_coords(cam,in.tex_coords.xy), render_params.boundary);
     let cell = textureSample(t_diffuse, s_diffuse, cam2tex);
-    let c = cell.rrra;
-    return c;
+    if (!boundary_shows(cam2tex, render_params.boundary)) {
+        return boundary_outside_colour();
+    }
+    var rgb = vec3<f32>(0.0);
+    for (var c: i32 = 0; c < 3; c = c + 1) {
+        rgb = rgb + cell[c] * render_params.colours[c].rgb;
+    }
+    return vec4<f32>(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,144 @@
+#include("update_scheme.wgsl");
+#include("boundary.wgsl");
+
+//Two state totalistic rules on bit packed cells. Each u32 holds 32 cells of a row, bit i being the cell at x = 32 * word + i.
+//Every invocation updates a whole word at once: the eight neighbours are shifted into place as bit planes and summed
+//with an adder network, so each bit of the count planes holds one bit of one cell's neighbour count.
+
+struct PackedParams {
+    //Bit n is set if a dead cell with n live neighbours is born.
+    born: u32,
+    //Bit n is set if a live cell with n live neighbours stays alive.
+    stay_alive: u32,
+    boundary: u32,
+    width: u32,
+    height: u32,
+    words_per_row: u32,
+    pad0: u32,
+    pad1: u32,
+};
+
+@group(0) @binding(0) var<storage, read> cells_in : array<u32>;
+@group(0) @binding(1) var<storage, read_write> cells_out : array<u32>;
+@group(1) @binding(0) var<uniform> params : PackedParams;
+@group(2) @binding(0) var<uniform> update_params : UpdateParams;
+
+//A single cell read through the boundary, as 0 or 1.
+fn cell_at(pos: vec2<i32>) -> u32 {
+    let dims = vec2<i32>(i32(params.width), i32(params.height));
+    let p = boundary_coords(pos, dims, params.boundary);
+    if (p.x < 0) {
+        return select(0u, 1u, params.boundary == BOUNDARY_ALIVE);
+    }
+    let word = cells_in[u32(p.y) * params.words_per_row + u32(p.x) / 32u];
+    return (word >> (u32(p.x) % 32u)) & 1u;
+}
+
+//The cells of word wx in row y, and the same cells shifted so each bit holds its west or east neighbour.
+//Returned as (west, centre, east). Rows outside the grid are read cell by cell through the boundary.
+fn row_planes(wx: u32, y: i32) -> vec3<u32> {
+    let x0 = i32(wx * 32u);
+    var centre = 0u;
+    var west_bit = 0u;
+    var east_bit = 0u;
+    if (y >= 0 && y < i32(params.height)) {
+        let row = u32(y) * params.words_per_row;
+        centre = cells_in[row + wx];
+        if (wx > 0u) {
+            west_bit = cells_in[row + wx - 1u] >> 31u;
+        } else {
+            west_bit = cell_at(vec2<i32>(x0 - 1, y));
+        }
+        if (wx + 1u < params.words_per_row) {
+            east_bit = cells_in[row + wx + 1u] & 1u;
+        } else {
+            east_bit = cell_at(vec2<i32>(x0 + 32, y));
+        }
+    } else {
+        for (var i = 0u; i < 32u; i = i + 1u) {
+            centre = centre | (cell_at(vec2<i32>(x0 + i32(i), y)) << i);
+        }
+        west_bit = cell_at(vec2<i32>(x0 - 1, y));
+        east_bit = cell_at(vec2<i32>(x0 + 32, y));
+    }
+    return vec3<u32>((centre << 1u) | west_bit, centre, (centre >> 1u) | (east_bit << 31u));
+}
+
+//Sum and carry of three bit planes.
+fn full_add(a: u32, b: u32, c: u32) -> vec2<u32> {
+    let s = a ^ b;
+    return vec2<u32>(s ^ c, (a & b) | (s & c));
+}
+
+fn half_add(a: u32, b: u32) -> vec2<u32> {
+    return vec2<u32>(a ^ b, a & b);
+}
+
+//Neighbour counts of all 32 cells, as bit planes of weight 1, 2, 4 and 8.
+fn count_neighbours(up: vec3<u32>, middle: vec3<u32>, down: vec3<u32>) -> vec4<u32> {
+    let u = full_add(up.x, up.y, up.z);
+    let d = full_add(down.x, down.y, down.z);
+    let m = half_add(middle.x, middle.z);
+    let ones = full_add(u.x, d.x, m.x);
+    let twos = full_add(u.y, d.y, m.y);
+    let twos_carry = half_add(twos.x, ones.y);
+    let fours = half_add(twos.y, twos_carry.y);
+    return vec4<u32>(ones.x, twos_carry.x, fours.x, fours.y);
+}
+
+//Bits of the cells whose count is n.
+fn count_is(counts: vec4<u32>, n: u32) -> u32 {
+    var planes = counts;
+    var matches = 0xffffffffu;
+    for (var k = 0u; k < 4u; k = k + 1u) {
+        if (((n >> k) & 1u) == 1u) {
+            matches = matches & planes[k];
+        } else {
+            matches = matches & ~planes[k];
+        }
+    }
+    return matches;
+}
+
+//Bits of the cells that should_update picks this generation.
+fn update_mask(wx: u32, y: i32) -> u32 {
+    if (update_params.mode == UPDATE_SYNCHRONOUS) {
+        return 0xffffffffu;
+    }
+    var mask = 0u;
+    for (var i = 0u; i < 32u; i = i + 1u) {
+        if (should_update(vec2<i32>(i32(wx * 32u + i), y), update_params)) {
+            mask = mask | (1u << i);
+        }
+    }
+    return mask;
+}
+
+@compute @workgroup_size(16, 16)
+fn main(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let wx = global_id.x;
+    let y = i32(global_id.y);
+    if (wx >= params.words_per_row || global_id.y >= params.height) {
+        return;
+    }
+
+    let middle = row_planes(wx, y);
+    let counts = count_neighbours(row_planes(wx, y - 1), middle, row_planes(wx, y + 1));
+    var born = 0u;
+    var stay_alive = 0u;
+    for (var n = 1u; n <= 8u; n = n + 1u) {
+        let matches = count_is(counts, n);
+        if (((params.born >> n) & 1u) == 1u) {
+            born = born | matches;
+        }
+        if (((params.stay_alive >> n) & 1u) == 1u) {
+            stay_alive = stay_alive | matches;
+        }
+    }
+    let alive = middle.y;
+    let next = (born & ~alive) | (stay_alive & alive);
+    let mask = update_mask(wx, y);
+    cells_out[global_id.y * params.words_per_row + wx] = (next & mask) | (alive & ~mask);
+}
@@ -0,0 +1,83 @@
+#include("boundary.wgsl");
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+struct Camera {
+    position: vec3<f32>,
+    zoom: f32,
+};
+
+struct RenderParams {
+    window_size: vec2<i32>,
+    sim_size: vec2<i32>,
+    species: u32,
+    channels: u32,
+    //See Boundary in boundary.rs.
+    boundary: u32,
+    pad2: u32,
+};
+
+//Transforms camera coordinates to texture coordinates.
+fn cam_to_tex_coords(cam: Camera, p: vec2<f32>,r_params: RenderParams) -> vec2<f32> {
+    let ssize = r_params.sim_size;
+    let rsize = r_params.window_size;
+    let ar = f32(rsize.y) / f32(rsize.x);
+    let r = 1.0/cam.zoom;
+    let cam_rect_size = vec2<f32>(r,r*ar);
+    let cx = (cam.position.x + 1.0) / 2.0;
+    let cy = 1.0 - ((cam.position.y + 1.0) / 2.0);
+    let x = cx - (cam_rect_size.x/2.0) + (p.x*cam_rect_size.x);
+    let y = cy - (cam_rect_size.y/2.0) + (p.y*cam_rect_size.y);
+    return vec2<f32>(x,y);
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+//Bit packed cells, see packed.wgsl. Rows are sim_size.x / 32 words long.
+@group(0) @binding(0)
+var<storage, read> cells: array<u32>;
+@group(1) @binding(0)
+var<uniform> cam: Camera;
+@group(1) @binding(1)
+var<uniform> render_params: RenderParams;
+
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let alive = vec4<f32>(0.09,0.47,0.0,1.0);
+    let dead = vec4<f32>(0.0,0.0,0.0,1.0);
+    let dimensions = render_params.sim_size;
+
+    let cam2tex = boundary_tex_coords(cam_to_tex_coords(cam,in.tex_coords.xy,render_params), render_params.boundary);
+    if (!boundary_shows(cam2tex, render_params.boundary)) {
+        return boundary_outside_colour();
+    }
+
+    //Repeats the grid like the sampler of the other renderers.
+    let p = min(vec2<i32>(fract(cam2tex) * vec2<f32>(dimensions)), dimensions - vec2<i32>(1));
+    let words_per_row = u32(dimensions.x) / 32u;
+    let word = cells[u32(p.y) * words_per_row + u32(p.x) / 32u];
+    if (((word >> (u32(p.x) % 32u)) & 1u) == 1u) {
+        return alive;
+    }
+    return dead;
+}
@@ -0,0 +1,67 @@
+let MAX_SOURCES: u32 = 8u;
+let PI: f32 = 3.14159265;
+
+struct PointSource {
+    position: vec2<f32>,
+    amplitude: f32,
+    frequency: f32,
+};
+
+struct PdeParams {
+    wave_speed: f32,
+    damping: f32,
+    diffusivity: f32,
+    time: f32,
+    source_count: u32,
+    boundary: u32,
+    pad1: u32,
+    pad2: u32,
+    sources: array<PointSource, 8>,
+};
+
+fn get_float_pixel_wrap(pos: vec2<i32>, dims: vec2<i32>, tex: texture_2d<f32>) -> vec4<f32> {
+    let x = (pos.x + dims.x) % dims.x;
+    let y = (pos.y + dims.y) % dims.y;
+    return textureLoad(tex, vec2<i32>(x,y), 0);
+}
+
+//Returns the value of the neighbor, or the value of the center cell if the neighbor is a wall. This gives walls a zero gradient.
+//Outside a fixed boundary the field is held at 0, or 1 for BOUNDARY_ALIVE.
+fn neighbor_value(center: vec4<f32>, pos: vec2<i32>, dims: vec2<i32>, tex: texture_2d<f32>, mode: u32) -> f32 {
+    let p = boundary_coords(pos, dims, mode);
+    if (p.x < 0) {
+        return select(0.0, 1.0, mode == BOUNDARY_ALIVE);
+    }
+    let n = textureLoad(tex, p, 0);
+    if (n.b > 0.5) {
+        return center.r;
+    }
+    return n.r;
+}
+
+//Five point discrete laplacian of the red channel.
+fn laplacian(coords: vec2<i32>, center: vec4<f32>, dims: vec2<i32>, tex: texture_2d<f32>, mode: u32) -> f32 {
+    let left = neighbor_value(center, coords + vec2<i32>(-1, 0), dims, tex, mode);
+    let right = neighbor_value(center, coords + vec2<i32>(1, 0), dims, tex, mode);
+    let up = neighbor_value(center, coords + vec2<i32>(0, 1), dims, tex, mode);
+    let down = neighbor_value(center, coords + vec2<i32>(0, -1), dims, tex, mode);
+    return left + right + up + down - 4.0 * center.r;
+}
+
+//Returns the index of the source located at coords, or -1 if there is none.
+fn source_at(coords: vec2<i32>, params: PdeParams) -> i32 {
+    var sources = params.sources;
+    for (var i: u32 = 0u; i < min(params.source_count, MAX_SOURCES); i = i + 1u) {
+        let p = vec2<i32>(sources[i].position);
+        if (p.x == coords.x && p.y == coords.y) {
+            return i32(i);
+        }
+    }
+    return -1;
+}
+
+fn source_value(index: i32, params: PdeParams) -> f32 {
+    var sources = params.sources;
+    let source = sources[index];
+    return source.amplitude * sin(2.0 * PI * source.frequency * params.time);
+}
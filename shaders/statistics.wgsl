@@ -0,0 +1,193 @@
+#include("update_scheme.wgsl");
+
+//Population statistics of a 2D simulation, summed with a parallel reduction in two passes.
+//reduce_cells sums each 16x16 tile into a partial, then reduce_partials sums the partials into one result.
+//hash_cells and reduce_hashes do the same for a hash of the whole state, used to spot repeating states.
+
+struct Partial {
+    sum: vec4<f32>,
+    sum_sq: vec4<f32>,
+    live: u32,
+    births: u32,
+    deaths: u32,
+    cells: u32,
+};
+
+//Two independent 32 bit sums of per cell hashes. Sums don't depend on the order cells are added in.
+struct HashPartial {
+    a: u32,
+    b: u32,
+    live: u32,
+    pad0: u32,
+};
+
+struct StatsParams {
+    //Channels that can make a cell count as live. A cell is live if any of them is above one half.
+    channels: u32,
+    //Number of partials written by reduce_cells.
+    partial_count: u32,
+    //Workgroups along x in reduce_cells, to find each tile's partial.
+    workgroups_x: u32,
+    pad0: u32,
+};
+
+@group(0) @binding(0) var current_texture : texture_2d<f32>;
+@group(0) @binding(1) var previous_texture : texture_2d<f32>;
+@group(0) @binding(2) var<storage, read_write> partials : array<Partial>;
+@group(0) @binding(3) var<uniform> params : StatsParams;
+@group(0) @binding(4) var<storage, read_write> result : Partial;
+@group(0) @binding(5) var<storage, read_write> hash_partials : array<HashPartial>;
+@group(0) @binding(6) var<storage, read_write> hash_result : HashPartial;
+
+var<workgroup> shared_partials : array<Partial, 256>;
+var<workgroup> shared_hashes : array<HashPartial, 256>;
+
+fn empty_partial() -> Partial {
+    return Partial(vec4<f32>(0.0), vec4<f32>(0.0), 0u, 0u, 0u, 0u);
+}
+
+fn add_partials(a: Partial, b: Partial) -> Partial {
+    return Partial(
+        a.sum + b.sum,
+        a.sum_sq + b.sum_sq,
+        a.live + b.live,
+        a.births + b.births,
+        a.deaths + b.deaths,
+        a.cells + b.cells,
+    );
+}
+
+fn is_live(cell: vec4<f32>) -> bool {
+    var live = false;
+    for (var c = 0u; c < min(params.channels, 4u); c = c + 1u) {
+        live = live || cell[c] > 0.5;
+    }
+    return live;
+}
+
+//Tree reduction of shared_partials into shared_partials[0]. Every invocation of the workgroup has to call it.
+fn reduce_shared(index: u32) {
+    var stride = 128u;
+    loop {
+        if (stride == 0u) {
+            break;
+        }
+        workgroupBarrier();
+        if (index < stride) {
+            shared_partials[index] = add_partials(shared_partials[index], shared_partials[index + stride]);
+        }
+        stride = stride / 2u;
+    }
+    workgroupBarrier();
+}
+
+@compute @workgroup_size(16, 16)
+fn reduce_cells(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+  @builtin(workgroup_id) workgroup_id : vec3<u32>,
+  @builtin(local_invocation_index) index : u32,
+) {
+    let dimensions = textureDimensions(current_texture);
+    let coords = vec2<i32>(global_id.xy);
+
+    var p = empty_partial();
+    if (coords.x < dimensions.x && coords.y < dimensions.y) {
+        let cell = textureLoad(current_texture, coords, 0);
+        let live = is_live(cell);
+        let was_live = is_live(textureLoad(previous_texture, coords, 0));
+        p.sum = cell;
+        p.sum_sq = cell * cell;
+        p.live = u32(live);
+        p.births = u32(live && !was_live);
+        p.deaths = u32(!live && was_live);
+        p.cells = 1u;
+    }
+    shared_partials[index] = p;
+    reduce_shared(index);
+    if (index == 0u) {
+        partials[workgroup_id.y * params.workgroups_x + workgroup_id.x] = shared_partials[0];
+    }
+}
+
+@compute @workgroup_size(256)
+fn reduce_partials(
+  @builtin(local_invocation_index) index : u32,
+) {
+    var p = empty_partial();
+    for (var i = index; i < params.partial_count; i = i + 256u) {
+        p = add_partials(p, partials[i]);
+    }
+    shared_partials[index] = p;
+    reduce_shared(index);
+    if (index == 0u) {
+        result = shared_partials[0];
+    }
+}
+
+fn add_hashes(a: HashPartial, b: HashPartial) -> HashPartial {
+    return HashPartial(a.a + b.a, a.b + b.b, a.live + b.live, 0u);
+}
+
+//Same as reduce_shared, for shared_hashes.
+fn reduce_shared_hashes(index: u32) {
+    var stride = 128u;
+    loop {
+        if (stride == 0u) {
+            break;
+        }
+        workgroupBarrier();
+        if (index < stride) {
+            shared_hashes[index] = add_hashes(shared_hashes[index], shared_hashes[index + stride]);
+        }
+        stride = stride / 2u;
+    }
+    workgroupBarrier();
+}
+
+//Mixes the position in, so moving a cell changes the hash even though the sums ignore order.
+fn cell_hash(coords: vec2<i32>, width: i32, cell: vec4<f32>) -> u32 {
+    var h = pcg_hash(u32(coords.y * width + coords.x));
+    h = pcg_hash(h ^ bitcast<u32>(cell.r));
+    h = pcg_hash(h ^ bitcast<u32>(cell.g));
+    h = pcg_hash(h ^ bitcast<u32>(cell.b));
+    return pcg_hash(h ^ bitcast<u32>(cell.a));
+}
+
+@compute @workgroup_size(16, 16)
+fn hash_cells(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+  @builtin(workgroup_id) workgroup_id : vec3<u32>,
+  @builtin(local_invocation_index) index : u32,
+) {
+    let dimensions = textureDimensions(current_texture);
+    let coords = vec2<i32>(global_id.xy);
+
+    var p = HashPartial(0u, 0u, 0u, 0u);
+    if (coords.x < dimensions.x && coords.y < dimensions.y) {
+        let cell = textureLoad(current_texture, coords, 0);
+        let h = cell_hash(coords, dimensions.x, cell);
+        p.a = h;
+        p.b = pcg_hash(h ^ 0x9e3779b9u);
+        p.live = u32(is_live(cell));
+    }
+    shared_hashes[index] = p;
+    reduce_shared_hashes(index);
+    if (index == 0u) {
+        hash_partials[workgroup_id.y * params.workgroups_x + workgroup_id.x] = shared_hashes[0];
+    }
+}
+
+@compute @workgroup_size(256)
+fn reduce_hashes(
+  @builtin(local_invocation_index) index : u32,
+) {
+    var p = HashPartial(0u, 0u, 0u, 0u);
+    for (var i = index; i < params.partial_count; i = i + 256u) {
+        p = add_hashes(p, hash_partials[i]);
+    }
+    shared_hashes[index] = p;
+    reduce_shared_hashes(index);
+    if (index == 0u) {
+        hash_result = shared_hashes[0];
+    }
+}
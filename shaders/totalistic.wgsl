@@ -1,4 +1,6 @@
 #include("shader_tools.wgsl");
+#include("update_scheme.wgsl");
+#include("boundary.wgsl");
 
  
 
@@ -22,6 +24,14 @@
     
  };
 
+ struct TotalisticParams {
+    rules: array<Rules, 4>,
+    species: u32,
+    use_rule_mask: u32,
+    region_rule_count: u32,
+    boundary: u32,
+ };
+
 fn compute_cell(val: f32, sum: f32, rules: Rules) -> f32 {
    
     var born = ( ((rules.b0 == 1u) && close(sum,1)) || ((rules.b1 == 1u) && close(sum,2)) || ((rules.b2  == 1u) && close(sum,3)) || ((rules.b3== 1u) && close(sum,4)) || ((rules.b4== 1u) && close(sum,5)) || ((rules.b5== 1u) && close(sum,6))  || ((rules.b6== 1u) && close(sum,7)) || ((rules.b7== 1u) && close(sum,8))   );
@@ -39,9 +49,112 @@ fn compute_cell(val: f32, sum: f32, rules: Rules) -> f32 {
     return 0.0;
  }
 
+fn rule_born(rules: Rules, n: i32) -> bool {
+    var born = array<u32, 8>(rules.b0, rules.b1, rules.b2, rules.b3, rules.b4, rules.b5, rules.b6, rules.b7);
+    return n > 0 && n <= 8 && born[n - 1] == 1u;
+}
+
+fn rule_stay_alive(rules: Rules, n: i32) -> bool {
+    var stay = array<u32, 8>(rules.s0, rules.s1, rules.s2, rules.s3, rules.s4, rules.s5, rules.s6, rules.s7);
+    return n > 0 && n <= 8 && stay[n - 1] == 1u;
+}
+
+//Species are stored one hot in the RGBA channels. Returns -1 for a dead cell.
+fn species_of(cell: vec4<f32>, species: u32) -> i32 {
+    var channels = array<f32, 4>(cell.r, cell.g, cell.b, cell.a);
+    for (var i: u32 = 0u; i < species; i = i + 1u) {
+        if (channels[i] > 0.5) {
+            return i32(i);
+        }
+    }
+    return -1;
+}
+
+fn species_color(s: i32) -> vec4<f32> {
+    var colors = array<vec4<f32>, 4>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0)
+    );
+    if (s < 0) {
+        return vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    }
+    return colors[s];
+}
+
+//Picks the species of a newborn cell from the counts of each species among its neighbors. The majority species wins. In QuadLife, three parents of different species produce the missing fourth species.
+fn newborn_species(counts: vec4<i32>, species: u32) -> i32 {
+    var c = array<i32, 4>(counts.x, counts.y, counts.z, counts.w);
+    var best = 0;
+    var present = 0;
+    var missing = -1;
+    for (var i: i32 = 0; i < i32(species); i = i + 1) {
+        if (c[i] > c[best]) {
+            best = i;
+        }
+        if (c[i] > 0) {
+            present = present + 1;
+        } else {
+            missing = i;
+        }
+    }
+    var ties = 0;
+    for (var i: i32 = 0; i < i32(species); i = i + 1) {
+        if (c[i] == c[best]) {
+            ties = ties + 1;
+        }
+    }
+    if (ties > 1 && species == 4u && present == 3) {
+        return missing;
+    }
+    return best;
+}
+
 @group(0) @binding(0) var input_texture : texture_2d<f32>;
 @group(0) @binding(1) var output_texture : texture_storage_2d<rgba8unorm, write>;
-@group(1) @binding(0) var<uniform> rules : Rules;
+@group(1) @binding(0) var<uniform> params : TotalisticParams;
+@group(2) @binding(0) var<uniform> update_params : UpdateParams;
+@group(1) @binding(1) var<storage, read> region_rules : array<Rules, 16>;
+@group(1) @binding(2) var rule_mask : texture_2d<u32>;
+
+//Picks the rule for a cell. When the rule mask is enabled, the mask decides the rule for every species.
+fn cell_rules(coords: vec2<i32>, species: i32) -> Rules {
+    if (params.use_rule_mask == 1u) {
+        let index = min(textureLoad(rule_mask, coords, 0).r, params.region_rule_count - 1u);
+        return region_rules[index];
+    }
+    var rules = params.rules;
+    return rules[species];
+}
+
+fn compute_species_cell(coords: vec2<i32>, dimensions: vec2<i32>) -> vec4<f32> {
+    var counts = vec4<i32>(0, 0, 0, 0);
+    for (var dy: i32 = -1; dy <= 1; dy = dy + 1) {
+        for (var dx: i32 = -1; dx <= 1; dx = dx + 1) {
+            if (dx != 0 || dy != 0) {
+                let n = get_pixel_boundary(coords + vec2<i32>(dx, dy), dimensions, input_texture, params.boundary);
+                let s = species_of(n, params.species);
+                if (s >= 0) {
+                    counts = counts + vec4<i32>(species_color(s));
+                }
+            }
+        }
+    }
+    let total = counts.x + counts.y + counts.z + counts.w;
+    let me = species_of(get_pixel_boundary(coords, dimensions, input_texture, params.boundary), params.species);
+    if (me >= 0) {
+        if (rule_stay_alive(cell_rules(coords, me), total)) {
+            return species_color(me);
+        }
+        return species_color(-1);
+    }
+    let child = newborn_species(counts, params.species);
+    if (rule_born(cell_rules(coords, child), total)) {
+        return species_color(child);
+    }
+    return species_color(-1);
+}
 
 @compute @workgroup_size(16, 16)
 fn main(
@@ -53,6 +166,16 @@ fn main(
     if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
         return;
     }
+    if (!should_update(coords, update_params)) {
+        textureStore(output_texture, coords.xy, textureLoad(input_texture, coords, 0));
+        return;
+    }
+
+    if (params.species > 1u) {
+        textureStore(output_texture, coords.xy, compute_species_cell(coords, dimensions));
+        return;
+    }
+
     let x: i32 = coords.x;
     let y: i32 = coords.y;
     
@@ -66,20 +189,20 @@ fn main(
     let c_left_down = vec2<i32>(x - 1,y - 1);
     let c_right_down = vec2<i32>(x + 1,y - 1);
 
-    let me      =  get_pixel_wrap(coords,dimensions,input_texture).r;
-    let left    =  get_pixel_wrap(c_left,dimensions,input_texture).r;    
-    let right   =  get_pixel_wrap(c_right,dimensions,input_texture).r;    
-    let up      =  get_pixel_wrap(c_up,dimensions,input_texture).r;    
-    let down    =  get_pixel_wrap(c_down,dimensions,input_texture).r;    
+    let me      =  get_pixel_boundary(coords, dimensions, input_texture, params.boundary).r;
+    let left    =  get_pixel_boundary(c_left, dimensions, input_texture, params.boundary).r;    
+    let right   =  get_pixel_boundary(c_right, dimensions, input_texture, params.boundary).r;    
+    let up      =  get_pixel_boundary(c_up, dimensions, input_texture, params.boundary).r;    
+    let down    =  get_pixel_boundary(c_down, dimensions, input_texture, params.boundary).r;    
 
-    let l_up    =  get_pixel_wrap(c_left_up,dimensions,input_texture).r;    
-    let r_up    =  get_pixel_wrap(c_right_up,dimensions,input_texture).r;    
-    let l_down  =  get_pixel_wrap(c_left_down,dimensions,input_texture).r;    
-    let r_down  =  get_pixel_wrap(c_right_down,dimensions,input_texture).r;    
+    let l_up    =  get_pixel_boundary(c_left_up, dimensions, input_texture, params.boundary).r;    
+    let r_up    =  get_pixel_boundary(c_right_up, dimensions, input_texture, params.boundary).r;    
+    let l_down  =  get_pixel_boundary(c_left_down, dimensions, input_texture, params.boundary).r;    
+    let r_down  =  get_pixel_boundary(c_right_down, dimensions, input_texture, params.boundary).r;    
 
     let sum = left + right + up + down + l_up + r_up + l_down + r_down;
-    let cell =  compute_cell(me, sum, rules);
+    let cell =  compute_cell(me, sum, cell_rules(coords, 0));
     let new_color = vec4<f32>(cell,cell,cell,1.0);
 
     textureStore(output_texture, coords.xy, new_color);
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
+#include("boundary.wgsl");
 // Vertex shader
 
 struct VertexInput {
@@ -18,6 +19,11 @@ struct Camera {
 struct RenderParams {
     window_size: vec2<i32>,
     sim_size: vec2<i32>,
+    species: u32,
+    channels: u32,
+    //See Boundary in boundary.rs.
+    boundary: u32,
+    pad2: u32,
 };
 
 fn close(a: f32, b: i32) -> bool {
@@ -69,13 +75,32 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let grid = vec4<f32>(0.15,0.15,0.15,1.0);
     let dimensions = render_params.sim_size;
     
-    let cam2tex = cam_to_tex_coords(cam,in.tex_coords.xy,render_params);
+    let cam2tex = boundary_tex_coords(cam_to_tex_coords(cam,in.tex_coords.xy,render_params), render_params.boundary);
 
     let cell = textureSample(t_diffuse, s_diffuse, cam2tex.xy);
+    if (!boundary_shows(cam2tex, render_params.boundary)) {
+        return boundary_outside_colour();
+    }
   
     
    
     var c = dead;
+    if (render_params.species > 1u) {
+        //One species per channel.
+        var colors = array<vec4<f32>, 4>(
+            vec4<f32>(0.9,0.2,0.2,1.0),
+            vec4<f32>(0.2,0.8,0.2,1.0),
+            vec4<f32>(0.25,0.4,1.0,1.0),
+            vec4<f32>(0.95,0.85,0.2,1.0)
+        );
+        var channels = array<f32, 4>(cell.r, cell.g, cell.b, cell.a);
+        for (var i: u32 = 0u; i < min(render_params.species, 4u); i = i + 1u) {
+            if (channels[i] > 0.5) {
+                c = colors[i];
+            }
+        }
+        return c;
+    }
     if (close(cell.r,1)) {
         c = alive;
     }
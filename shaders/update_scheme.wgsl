@@ -0,0 +1,59 @@
+let UPDATE_SYNCHRONOUS: u32 = 0u;
+let UPDATE_RANDOM_SEQUENTIAL: u32 = 1u;
+let UPDATE_CHECKERBOARD: u32 = 2u;
+let UPDATE_BLOCK_SEQUENTIAL: u32 = 3u;
+
+struct UpdateParams {
+    mode: u32,
+    alpha: f32,
+    block_size: u32,
+    step_seed: u32,
+    generation: u32,
+    pad0: u32,
+    pad1: u32,
+    pad2: u32,
+};
+
+//PCG hash. Gives well distributed random numbers from the cell position and the per step seed.
+fn pcg_hash(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random_unit(coords: vec2<i32>, seed: u32) -> f32 {
+    let h = pcg_hash(u32(coords.x) ^ pcg_hash(u32(coords.y) ^ pcg_hash(seed)));
+    return f32(h) / 4294967295.0;
+}
+
+//Returns true if the cell at coords should be updated this generation. Cells that aren't updated keep their old value.
+fn should_update(coords: vec2<i32>, u: UpdateParams) -> bool {
+    if (u.mode == UPDATE_RANDOM_SEQUENTIAL) {
+        return random_unit(coords, u.step_seed) < u.alpha;
+    }
+    if (u.mode == UPDATE_CHECKERBOARD) {
+        return u32(coords.x + coords.y) % 2u == u.generation % 2u;
+    }
+    if (u.mode == UPDATE_BLOCK_SEQUENTIAL) {
+        let b = max(u.block_size, 1u);
+        let phase = u32(coords.x) % b + (u32(coords.y) % b) * b;
+        return phase == u.generation % (b * b);
+    }
+    return true;
+}
+
+//Same as should_update, for volumetric simulations.
+fn should_update_3d(coords: vec3<i32>, u: UpdateParams) -> bool {
+    if (u.mode == UPDATE_RANDOM_SEQUENTIAL) {
+        return random_unit(coords.xy, pcg_hash(u32(coords.z) ^ u.step_seed)) < u.alpha;
+    }
+    if (u.mode == UPDATE_CHECKERBOARD) {
+        return u32(coords.x + coords.y + coords.z) % 2u == u.generation % 2u;
+    }
+    if (u.mode == UPDATE_BLOCK_SEQUENTIAL) {
+        let b = max(u.block_size, 1u);
+        let phase = u32(coords.x) % b + (u32(coords.y) % b) * b + (u32(coords.z) % b) * b * b;
+        return phase == u.generation % (b * b * b);
+    }
+    return true;
+}
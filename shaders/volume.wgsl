@@ -0,0 +1,85 @@
+#include("update_scheme.wgsl");
+
+//Volumetric totalistic automata. State 0 is dead, 1 is alive, and 2..states-1 are decaying cells that can't be born into or counted as neighbors.
+struct Rule3D {
+    survive: u32,
+    born: u32,
+    states: u32,
+    neighborhood: u32,
+};
+
+@group(0) @binding(0) var input_texture : texture_3d<u32>;
+@group(0) @binding(1) var output_texture : texture_storage_3d<r32uint, write>;
+@group(1) @binding(0) var<uniform> rule : Rule3D;
+@group(2) @binding(0) var<uniform> update_params : UpdateParams;
+
+fn get_cell_wrap(pos: vec3<i32>, dims: vec3<i32>) -> u32 {
+    let p = (pos + dims) % dims;
+    return textureLoad(input_texture, p, 0).r;
+}
+
+fn is_alive(pos: vec3<i32>, dims: vec3<i32>) -> u32 {
+    return select(0u, 1u, get_cell_wrap(pos, dims) == 1u);
+}
+
+fn count_neighbors(coords: vec3<i32>, dims: vec3<i32>) -> u32 {
+    var count = 0u;
+    if (rule.neighborhood == 1u) {
+        count = count + is_alive(coords + vec3<i32>(1, 0, 0), dims);
+        count = count + is_alive(coords + vec3<i32>(-1, 0, 0), dims);
+        count = count + is_alive(coords + vec3<i32>(0, 1, 0), dims);
+        count = count + is_alive(coords + vec3<i32>(0, -1, 0), dims);
+        count = count + is_alive(coords + vec3<i32>(0, 0, 1), dims);
+        count = count + is_alive(coords + vec3<i32>(0, 0, -1), dims);
+        return count;
+    }
+    for (var dz: i32 = -1; dz <= 1; dz = dz + 1) {
+        for (var dy: i32 = -1; dy <= 1; dy = dy + 1) {
+            for (var dx: i32 = -1; dx <= 1; dx = dx + 1) {
+                if (dx != 0 || dy != 0 || dz != 0) {
+                    count = count + is_alive(coords + vec3<i32>(dx, dy, dz), dims);
+                }
+            }
+        }
+    }
+    return count;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn main(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(input_texture);
+    let coords = vec3<i32>(global_id);
+
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y || coords.z >= dimensions.z) {
+        return;
+    }
+    let me = get_cell_wrap(coords, dimensions);
+    if (!should_update_3d(coords, update_params)) {
+        textureStore(output_texture, coords, vec4<u32>(me, 0u, 0u, 0u));
+        return;
+    }
+
+    let n = count_neighbors(coords, dimensions);
+    let bit = 1u << n;
+    var next = 0u;
+    if (me == 0u) {
+        if ((rule.born & bit) != 0u) {
+            next = 1u;
+        }
+    } else if (me == 1u) {
+        if ((rule.survive & bit) != 0u) {
+            next = 1u;
+        } else if (rule.states > 2u) {
+            next = 2u;
+        }
+    } else {
+        //Decaying
+        next = me + 1u;
+        if (next >= rule.states) {
+            next = 0u;
+        }
+    }
+    textureStore(output_texture, coords, vec4<u32>(next, 0u, 0u, 0u));
+}
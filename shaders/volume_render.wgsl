@@ -0,0 +1,115 @@
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+//Orbit camera basis. w of eye holds tan(fov/2), w of forward holds the aspect ratio, and w of right holds the number of cell states.
+struct OrbitCamera {
+    eye: vec4<f32>,
+    forward: vec4<f32>,
+    right: vec4<f32>,
+    up: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+@group(0) @binding(0)
+var volume: texture_3d<u32>;
+@group(1) @binding(0)
+var<uniform> cam: OrbitCamera;
+
+//Returns the distances along the ray where it enters and leaves the box.
+fn intersect_box(origin: vec3<f32>, dir: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>) -> vec2<f32> {
+    let inv = 1.0 / dir;
+    let t0 = (box_min - origin) * inv;
+    let t1 = (box_max - origin) * inv;
+    let t_min = min(t0, t1);
+    let t_max = max(t0, t1);
+    let near = max(max(t_min.x, t_min.y), t_min.z);
+    let far = min(min(t_max.x, t_max.y), t_max.z);
+    return vec2<f32>(near, far);
+}
+
+fn state_color(state: u32, states: u32) -> vec3<f32> {
+    let alive = vec3<f32>(0.09, 0.75, 0.2);
+    let dying = vec3<f32>(0.6, 0.1, 0.05);
+    if (state == 1u || states <= 2u) {
+        return alive;
+    }
+    let t = f32(state - 1u) / f32(states - 1u);
+    return mix(vec3<f32>(0.9, 0.8, 0.1), dying, t);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let background = vec4<f32>(0.02, 0.02, 0.03, 1.0);
+    let dims = vec3<f32>(textureDimensions(volume));
+    let dims_i = textureDimensions(volume);
+    let states = u32(cam.right.w);
+
+    //Screen position in -1..1, with y up.
+    let p = vec2<f32>(in.tex_coords.x * 2.0 - 1.0, 1.0 - in.tex_coords.y * 2.0);
+    let tan_fov = cam.eye.w;
+    let aspect = cam.forward.w;
+    let dir = normalize(cam.forward.xyz + cam.right.xyz * p.x * tan_fov * aspect + cam.up.xyz * p.y * tan_fov);
+
+    //The volume spans -0.5..0.5 on its longest axis. Work in cell units from here on.
+    let scale = max(dims.x, max(dims.y, dims.z));
+    let origin = cam.eye.xyz * scale + dims * 0.5;
+    let hit = intersect_box(origin, dir, vec3<f32>(0.0), dims);
+    if (hit.x > hit.y || hit.y < 0.0) {
+        return background;
+    }
+
+    //Amanatides and Woo voxel traversal.
+    let start = origin + dir * max(hit.x + 0.0001, 0.0);
+    var cell = clamp(vec3<i32>(floor(start)), vec3<i32>(0), dims_i - vec3<i32>(1));
+    let step = vec3<i32>(sign(dir));
+    let delta = abs(1.0 / dir);
+    var side = (sign(dir) * (vec3<f32>(cell) - start) + (sign(dir) * 0.5) + 0.5) * delta;
+    var normal = vec3<f32>(0.0);
+    let max_steps = dims_i.x + dims_i.y + dims_i.z;
+    for (var i: i32 = 0; i < max_steps; i = i + 1) {
+        let state = textureLoad(volume, cell, 0).r;
+        if (state != 0u) {
+            let light = normalize(vec3<f32>(0.4, 0.8, 0.5));
+            let diffuse = 0.35 + 0.65 * abs(dot(normal, light));
+            let depth_fade = 1.0 - 0.5 * f32(i) / f32(max_steps);
+            return vec4<f32>(state_color(state, states) * diffuse * depth_fade, 1.0);
+        }
+        if (side.x < side.y && side.x < side.z) {
+            side.x = side.x + delta.x;
+            cell.x = cell.x + step.x;
+            normal = vec3<f32>(1.0, 0.0, 0.0);
+        } else if (side.y < side.z) {
+            side.y = side.y + delta.y;
+            cell.y = cell.y + step.y;
+            normal = vec3<f32>(0.0, 1.0, 0.0);
+        } else {
+            side.z = side.z + delta.z;
+            cell.z = cell.z + step.z;
+            normal = vec3<f32>(0.0, 0.0, 1.0);
+        }
+        if (any(cell < vec3<i32>(0)) || any(cell >= dims_i)) {
+            break;
+        }
+    }
+    return background;
+}
@@ -0,0 +1,46 @@
+#include("boundary.wgsl");
+#include("pde_tools.wgsl");
+#include("update_scheme.wgsl");
+
+//State layout: r = u(t), g = u(t - dt), b = wall mask.
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var output_texture : texture_storage_2d<rgba32float, write>;
+@group(1) @binding(0) var<uniform> params : PdeParams;
+@group(2) @binding(0) var<uniform> update_params : UpdateParams;
+
+@compute @workgroup_size(16, 16)
+fn main(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(input_texture);
+    let coords = vec2<i32>(global_id.xy);
+
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+    if (!should_update(coords, update_params)) {
+        textureStore(output_texture, coords.xy, textureLoad(input_texture, coords, 0));
+        return;
+    }
+
+    let me = get_float_pixel_wrap(coords, dimensions, input_texture);
+    if (me.b > 0.5) {
+        textureStore(output_texture, coords.xy, vec4<f32>(0.0, 0.0, me.b, 1.0));
+        return;
+    }
+
+    let source = source_at(coords, params);
+    if (source >= 0) {
+        let v = source_value(source, params);
+        textureStore(output_texture, coords.xy, vec4<f32>(v, me.r, me.b, 1.0));
+        return;
+    }
+
+    //Three level scheme: u(t+dt) = 2u(t) - u(t-dt) + c^2 * laplacian(u(t)), with damping applied to the velocity term.
+    let c2 = params.wave_speed * params.wave_speed;
+    let gamma = params.damping;
+    let lap = laplacian(coords, me, dimensions, input_texture, params.boundary);
+    let next = (2.0 * me.r - (1.0 - gamma) * me.g + c2 * lap) / (1.0 + gamma);
+
+    textureStore(output_texture, coords.xy, vec4<f32>(next, me.r, me.b, 1.0));
+}
@@ -6,7 +6,7 @@ use crate::renderer::Renderer;
 
 use super::{
     input::Input,
-    math::{FVec2, FVec3},
+    math::{FVec2, FVec3, IVec2},
 };
 
 #[repr(C)]
@@ -39,6 +39,20 @@ impl Camera {
         }
     }
 
+    //Converts a window position in pixels to texture coordinates in the simulation. Matches cam_to_tex_coords in the render shaders. The result isn't wrapped into 0..1.
+    pub fn screen_to_tex(&self, pos: FVec2, window_size: IVec2) -> FVec2 {
+        let p = FVec2::new(pos.x / window_size.x as f32, pos.y / window_size.y as f32);
+        let ar = window_size.y as f32 / window_size.x as f32;
+        let r = 1.0 / self.zoom;
+        let cam_rect_size = FVec2::new(r, r * ar);
+        let cx = (self.position.x + 1.0) / 2.0;
+        let cy = 1.0 - ((self.position.y + 1.0) / 2.0);
+        FVec2::new(
+            cx - (cam_rect_size.x / 2.0) + (p.x * cam_rect_size.x),
+            cy - (cam_rect_size.y / 2.0) + (p.y * cam_rect_size.y),
+        )
+    }
+
     pub fn handle_input(&mut self, input: &Input, renderer: &Renderer) {
         self.zoom = (self.zoom + input.scroll_delta * ZOOM_SPEED * self.zoom).clamp(1.0, 50.0);
 
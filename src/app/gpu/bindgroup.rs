@@ -1,7 +1,129 @@
 use std::rc::Rc;
 
+use wgpu::util::DeviceExt;
+
 use super::Gpu;
 
 pub trait ToBindgroup {
     fn to_bind_group(&self, gpu: &Gpu) -> wgpu::BindGroup;
 }
+
+//A buffer that remembers what was last written to it, so unchanged data isn't uploaded again.
+pub struct CachedBuffer {
+    buffer: wgpu::Buffer,
+    contents: Vec<u8>,
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+}
+
+impl CachedBuffer {
+    pub fn new(
+        gpu: &Gpu,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        contents: &[u8],
+    ) -> CachedBuffer {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        CachedBuffer {
+            buffer: CachedBuffer::create(gpu, label, usage, contents),
+            contents: contents.to_vec(),
+            label,
+            usage,
+        }
+    }
+
+    fn create(gpu: &Gpu, label: &str, usage: wgpu::BufferUsages, contents: &[u8]) -> wgpu::Buffer {
+        gpu.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage,
+            })
+    }
+
+    //Uploads the contents if they changed. Returns true if the size changed and the buffer was replaced,
+    //in which case bind groups using it have to be rebuilt.
+    pub fn write(&mut self, gpu: &Gpu, contents: &[u8]) -> bool {
+        if contents == self.contents.as_slice() {
+            return false;
+        }
+        let resized = contents.len() != self.contents.len();
+        if resized {
+            self.buffer = CachedBuffer::create(gpu, self.label, self.usage, contents);
+        } else {
+            gpu.queue.write_buffer(&self.buffer, 0, contents);
+        }
+        self.contents = contents.to_vec();
+        resized
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+//One uniform struct per step of a batch, packed into a single buffer with a bind group over each.
+//Lets every step see its own parameters without a submit in between. The buffer only grows, so it's reused between batches.
+pub struct UniformBatch {
+    label: &'static str,
+    buffer: Option<wgpu::Buffer>,
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl UniformBatch {
+    pub fn new(label: &'static str) -> UniformBatch {
+        UniformBatch {
+            label,
+            buffer: None,
+            bind_groups: Vec::new(),
+        }
+    }
+
+    pub fn write<T: bytemuck::Pod>(
+        &mut self,
+        gpu: &Gpu,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: &[T],
+    ) {
+        let size = std::mem::size_of::<T>();
+        let alignment = gpu.device.limits().min_uniform_buffer_offset_alignment as usize;
+        let stride = size.div_ceil(alignment) * alignment;
+        if uniforms.len() > self.bind_groups.len() {
+            let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: (stride * uniforms.len()) as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.bind_groups = (0..uniforms.len())
+                .map(|i| {
+                    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some(self.label),
+                        layout,
+                        entries: &[wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &buffer,
+                                offset: (i * stride) as u64,
+                                size: std::num::NonZeroU64::new(size as u64),
+                            }),
+                        }],
+                    })
+                })
+                .collect();
+            self.buffer = Some(buffer);
+        }
+        let mut contents = vec![0u8; stride * uniforms.len()];
+        for (chunk, uniform) in contents.chunks_exact_mut(stride).zip(uniforms) {
+            chunk[..size].copy_from_slice(bytemuck::bytes_of(uniform));
+        }
+        if let Some(buffer) = &self.buffer {
+            gpu.queue.write_buffer(buffer, 0, &contents);
+        }
+    }
+
+    //Bind groups for the steps of the last batch written, in order. Can be longer than that batch, after a bigger one.
+    pub fn bind_groups(&self) -> &[wgpu::BindGroup] {
+        &self.bind_groups
+    }
+}
@@ -1,7 +1,10 @@
 use winit::window::Window;
 pub mod bindgroup;
+pub mod pipeline;
+pub mod readback;
 pub struct Gpu {
-    pub surface: wgpu::Surface,
+    //None for headless devices, which can run simulations but can't present to a window.
+    pub surface: Option<wgpu::Surface>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
@@ -50,11 +53,54 @@ impl Gpu {
         };
         surface.configure(&device, &config);
         Gpu {
-            surface,
+            surface: Some(surface),
             device,
             queue,
             config,
             size,
         }
     }
+
+    //Creates a device without a window or surface, for running simulations on machines without a display.
+    //Prefers a hardware adapter and falls back to a software one such as lavapipe or WARP.
+    pub fn new_headless() -> Result<Gpu, String> {
+        //The GL backend can't run the compute shaders, so only Vulkan, Metal, DX12 and WebGPU are tried.
+        let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
+        let request = |force_fallback_adapter| {
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter,
+                compatible_surface: None,
+            }))
+        };
+        let adapter = request(false)
+            .or_else(|| request(true))
+            .ok_or_else(|| "No GPU adapter found, not even a software fallback.".to_owned())?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+                label: None,
+            },
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        //Nothing is presented, but the renderers still read the format and size from here.
+        let size = winit::dpi::PhysicalSize::new(1, 1);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        Ok(Gpu {
+            surface: None,
+            device,
+            queue,
+            config,
+            size,
+        })
+    }
 }
@@ -0,0 +1,113 @@
+/* Compute pipelines with explicit bind group layouts.
+Layouts derived from a shader treat every texture_2d<f32> as filterable, which float32 textures aren't without an extra feature.
+Simulations that keep their state in float32 textures spell their layouts out with these helpers instead.
+*/
+
+use super::Gpu;
+use crate::app::wgsl_preproc::WgslPreProcessor;
+
+pub fn texture_entry(
+    binding: u32,
+    sample_type: wgpu::TextureSampleType,
+    view_dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type,
+        },
+        count: None,
+    }
+}
+
+//A texture_2d<f32> that's only read with textureLoad, so it can hold float32 texels.
+pub fn unfilterable_texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    texture_entry(
+        binding,
+        wgpu::TextureSampleType::Float { filterable: false },
+        wgpu::TextureViewDimension::D2,
+    )
+}
+
+pub fn storage_texture_entry(
+    binding: u32,
+    format: wgpu::TextureFormat,
+    view_dimension: wgpu::TextureViewDimension,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::StorageTexture {
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            format,
+            view_dimension,
+        },
+        count: None,
+    }
+}
+
+pub fn buffer_entry(binding: u32, ty: wgpu::BufferBindingType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub fn uniform_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    buffer_entry(binding, wgpu::BufferBindingType::Uniform)
+}
+
+pub fn create_layout(
+    gpu: &Gpu,
+    label: &str,
+    entries: &[wgpu::BindGroupLayoutEntry],
+) -> wgpu::BindGroupLayout {
+    gpu.device
+        .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries,
+        })
+}
+
+//Loads and preprocesses a shader from ./shaders.
+pub fn create_shader(gpu: &Gpu, file: &str, name: &str) -> wgpu::ShaderModule {
+    let shader_src = WgslPreProcessor::load_and_process(file, "./shaders").unwrap();
+    gpu.device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(format!("{} shader", name).as_str()),
+            source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+        })
+}
+
+//bind_group_layouts are the layouts of groups 0, 1, 2 and so on.
+pub fn create_compute_pipeline(
+    gpu: &Gpu,
+    shader: &wgpu::ShaderModule,
+    name: &str,
+    entry_point: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+) -> wgpu::ComputePipeline {
+    let layout = gpu
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(format!("{} pipeline layout", name).as_str()),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+    gpu.device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(format!("{} compute pipeline", name).as_str()),
+            layout: Some(&layout),
+            module: shader,
+            entry_point,
+        })
+}
@@ -0,0 +1,120 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+use super::Gpu;
+
+//States of a slot's map, set from its callback.
+const MAP_PENDING: u8 = 0;
+const MAP_DONE: u8 = 1;
+const MAP_FAILED: u8 = 2;
+
+struct ReadbackSlot<T> {
+    buffer: wgpu::Buffer,
+    //What the caller recorded with the copy, handed back with the data.
+    tag: T,
+    size: u64,
+    //Recorded into a submission, waiting for the map to finish.
+    in_flight: bool,
+    //Set from the map callback once the buffer can be read, or the map failed.
+    map_state: Arc<AtomicU8>,
+}
+
+//Small results copied back from the GPU without waiting on it. Each copy goes into one of a few staging buffers,
+//which is mapped once its submission is made and read by a later poll. When every buffer is still waiting, copies are skipped.
+pub struct ReadbackRing<T> {
+    slots: Vec<ReadbackSlot<T>>,
+    //Slot written by the last record, mapped by submitted.
+    recorded: Option<usize>,
+}
+
+impl<T: Copy + Default> ReadbackRing<T> {
+    pub fn new(gpu: &Gpu, label: &str, size: u64, count: usize) -> ReadbackRing<T> {
+        ReadbackRing {
+            slots: (0..count)
+                .map(|_| ReadbackSlot {
+                    buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(label),
+                        size,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    }),
+                    tag: T::default(),
+                    size: 0,
+                    in_flight: false,
+                    map_state: Arc::new(AtomicU8::new(MAP_PENDING)),
+                })
+                .collect(),
+            recorded: None,
+        }
+    }
+
+    //True when a record would be skipped. Lets callers leave out work whose result couldn't be read back.
+    pub fn is_full(&self) -> bool {
+        self.slots.iter().all(|s| s.in_flight)
+    }
+
+    //Copies the start of src into a free staging buffer. Returns false if none was free.
+    pub fn record(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        src: &wgpu::Buffer,
+        size: u64,
+        tag: T,
+    ) -> bool {
+        let index = match self.slots.iter().position(|s| !s.in_flight) {
+            Some(index) => index,
+            None => return false,
+        };
+        let slot = &mut self.slots[index];
+        encoder.copy_buffer_to_buffer(src, 0, &slot.buffer, 0, size);
+        slot.tag = tag;
+        slot.size = size;
+        slot.in_flight = true;
+        self.recorded = Some(index);
+        true
+    }
+
+    //Starts mapping what the last record copied. Call after submitting its encoder.
+    pub fn submitted(&mut self) {
+        if let Some(index) = self.recorded.take() {
+            let slot = &self.slots[index];
+            let map_state = slot.map_state.clone();
+            slot.buffer
+                .slice(..slot.size)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let state = if result.is_ok() { MAP_DONE } else { MAP_FAILED };
+                    map_state.store(state, Ordering::Release);
+                });
+        }
+    }
+
+    //Every copy the GPU has finished since the last poll, in no particular order. Never waits.
+    //Copies whose map failed are dropped and their buffers freed for the next record.
+    pub fn poll(&mut self, gpu: &Gpu) -> Vec<(T, Vec<u8>)> {
+        gpu.device.poll(wgpu::Maintain::Poll);
+        let mut results = Vec::new();
+        for slot in self.slots.iter_mut() {
+            if !slot.in_flight {
+                continue;
+            }
+            match slot.map_state.swap(MAP_PENDING, Ordering::Acquire) {
+                MAP_DONE => {
+                    let data = slot.buffer.slice(..slot.size).get_mapped_range().to_vec();
+                    slot.buffer.unmap();
+                    results.push((slot.tag, data));
+                }
+                MAP_FAILED => {
+                    //The buffer is usually left unmapped, which makes unmap a validation error, so it's scoped and ignored.
+                    gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+                    slot.buffer.unmap();
+                    drop(gpu.device.pop_error_scope());
+                }
+                _ => continue,
+            }
+            slot.in_flight = false;
+        }
+        results
+    }
+}
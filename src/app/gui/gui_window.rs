@@ -1,11 +1,19 @@
-use super::{error_window::ErrorWindow, neural_window::NeuralWindow};
+use super::{
+    error_window::ErrorWindow, nca_window::NcaWindow, neural_window::NeuralWindow,
+    pde_window::PdeWindow, rule_window::RuleWindow,
+};
 use crate::app::{
     gpu::Gpu,
     math::UVec2,
-    rule::Rule,
+    rule::{Rule, MAX_SPECIES},
+    rule3d::Rule3D,
     sim_renderer::{RendererType, SimulationRenderer},
     simulation::{
+        boundary::Boundary,
+        cycles::CycleAction,
         neural_parameters::{NeuralCreationParameters, NeuralParameters},
+        packed::MAX_PACKED_SIZE,
+        update_parameters::UpdateScheme,
         SimulationState, SimulationType,
     },
     App,
@@ -17,10 +25,26 @@ pub struct GuiWindow {
     selected_simulation_type: SimulationType,
     sim_size: UVec2,
     rule_str: String,
+    species: u32,
+    per_species_rules: bool,
+    species_rule_strs: [String; MAX_SPECIES],
+    volume_rule_str: String,
+    volume_size: u32,
     error_window: Option<ErrorWindow>,
     neural_window: NeuralWindow,
+    pde_window: PdeWindow,
+    nca_window: NcaWindow,
+    rule_window: RuleWindow,
     updates_per_frame: u32,
     update_delay: u32,
+    update_scheme: UpdateScheme,
+    update_alpha: f32,
+    update_block_size: u32,
+    update_seed: u64,
+    verify_generations: usize,
+    verify_result: Option<String>,
+    pub step_count: usize,
+    run_until_generation: usize,
 }
 
 impl GuiWindow {
@@ -29,25 +53,60 @@ impl GuiWindow {
             sim_state: SimulationState::default(),
             selected_simulation_type: SimulationType::Totalistic,
             rule_str: "B3/S23".to_owned(),
+            species: 1,
+            per_species_rules: false,
+            species_rule_strs: Default::default(),
+            volume_rule_str: "445".to_owned(),
+            volume_size: 64,
             sim_size: UVec2::new(512, 512),
             error_window: None,
             neural_window: NeuralWindow::new(),
+            pde_window: PdeWindow::new(),
+            nca_window: NcaWindow::new(),
+            rule_window: RuleWindow::new(),
             updates_per_frame: 1,
             update_delay: 0,
+            update_scheme: UpdateScheme::Synchronous,
+            update_alpha: 0.5,
+            update_block_size: 2,
+            update_seed: 0,
+            verify_generations: 100,
+            verify_result: None,
+            step_count: 10,
+            run_until_generation: 1000,
         }
     }
 
     fn remake_sim(&mut self, app: &mut App, sim_renderer: &mut SimulationRenderer, gpu: &Gpu) {
-        let r_type = match self.selected_simulation_type {
-            SimulationType::Totalistic => RendererType::Totalistic,
-            SimulationType::Neural => RendererType::Neural,
-        };
         match self.selected_simulation_type {
             SimulationType::Totalistic => {
                 let rule_create = Rule::from_rule_str(self.rule_str.as_str());
                 match rule_create {
                     Ok(rule) => {
-                        app.simulation.totalistic_state.params.rule = rule;
+                        let params = &mut app.simulation.totalistic_state.params;
+                        params.set_rule(rule);
+                        params.species = self.species;
+                        if self.per_species_rules {
+                            for s in 0..self.species as usize {
+                                let species_rule_str = self.species_rule_strs[s].as_str();
+                                if species_rule_str.is_empty() {
+                                    continue;
+                                }
+                                match Rule::from_rule_str(species_rule_str) {
+                                    Ok(species_rule) => params.rules[s] = species_rule,
+                                    Err(_) => {
+                                        let e = format!(
+                                            "Cause: Rule creation error for species {}.",
+                                            s + 1
+                                        );
+                                        self.error_window = Some(ErrorWindow::new(
+                                            "Simulation Creation Error",
+                                            e.as_str(),
+                                        ));
+                                    }
+                                }
+                            }
+                        }
                     }
                     Err(_) => {
                         let e = format!("Cause: {}", "Rule creation error.");
@@ -59,9 +118,32 @@ impl GuiWindow {
             SimulationType::Neural => {
                 app.simulation.neural_state.params.filter = self.neural_window.get_filter();
             }
+            SimulationType::Wave | SimulationType::Heat => {
+                app.simulation.pde_state.params.settings = self.pde_window.get_settings();
+            }
+            SimulationType::Volume => {
+                match Rule3D::from_rule_str(self.volume_rule_str.as_str()) {
+                    Ok(rule) => app.simulation.volume_state.params.rule = rule,
+                    Err(_) => {
+                        let e = format!("Cause: {}", "3D rule creation error.");
+                        self.error_window =
+                            Some(ErrorWindow::new("Simulation Creation Error", e.as_str()));
+                    }
+                }
+                app.simulation.volume_state.params.size = self.volume_size;
+            }
+            SimulationType::GrowingNca => {}
         }
         app.simulation
             .remake(gpu, self.sim_size, self.selected_simulation_type);
+        let r_type = match self.selected_simulation_type {
+            SimulationType::Totalistic if app.simulation.is_packed() => RendererType::Packed,
+            SimulationType::Totalistic => RendererType::Totalistic,
+            SimulationType::Neural => RendererType::Neural,
+            SimulationType::Wave | SimulationType::Heat => RendererType::Pde,
+            SimulationType::Volume => RendererType::Volume,
+            SimulationType::GrowingNca => RendererType::Nca,
+        };
         sim_renderer.set_renderer_type(r_type);
     }
 
@@ -98,8 +180,81 @@ impl GuiWindow {
                         if is_paused { "Paused" } else { "Running" },
                     );
                 });
+                //Stepping is only offered while paused. The '.' and 'N' keys do the same as the step buttons.
+                //Runs are spread over frames, with a progress bar until they reach their generation.
+                let run_progress = app.simulation.run_progress();
+                let can_step = is_paused && run_progress.is_none();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(can_step, egui::Button::new("Step 1"))
+                        .clicked()
+                    {
+                        app.simulation.run_for(1);
+                    }
+                    if ui
+                        .add_enabled(can_step, egui::Button::new("Step N"))
+                        .clicked()
+                    {
+                        app.simulation.run_for(self.step_count);
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut self.step_count)
+                            .clamp_range(1..=1_000_000)
+                            .prefix("N: "),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(can_step, egui::Button::new("Run Until"))
+                        .clicked()
+                    {
+                        app.simulation.run_until(self.run_until_generation);
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut self.run_until_generation)
+                            .clamp_range(0..=100_000_000)
+                            .prefix("Generation: "),
+                    );
+                });
+                if let Some((generation, target)) = run_progress {
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancel").clicked() {
+                            app.simulation.cancel_run();
+                        }
+                        ui.add(
+                            egui::ProgressBar::new(generation as f32 / target as f32)
+                                .text(format!("{} / {}", generation, target)),
+                        );
+                    });
+                }
 
-                self.neural_window.ui(ctx, app);
+                //Applies to the running simulation straight away.
+                let running_type = app.simulation.get_simulation_type();
+                if !matches!(
+                    running_type,
+                    SimulationType::Volume | SimulationType::GrowingNca
+                ) {
+                    let mut boundary = app.simulation.get_boundary(running_type);
+                    egui::ComboBox::from_label("Boundary")
+                        .selected_text(boundary.name())
+                        .show_ui(ui, |ui| {
+                            for b in Boundary::ALL {
+                                ui.selectable_value(&mut boundary, b, b.name());
+                            }
+                        });
+                    app.simulation.set_boundary(running_type, boundary);
+                }
+
+                self.neural_window.ui(ctx, gpu, app);
+                if self.selected_simulation_type.is_pde() {
+                    self.pde_window.ui(ctx, app);
+                }
+                if self.selected_simulation_type == SimulationType::GrowingNca {
+                    self.nca_window.ui(ctx, app);
+                }
+                if self.selected_simulation_type == SimulationType::Totalistic {
+                    self.rule_window.ui(ctx, gpu, app, sim_renderer.size);
+                }
                 let filter = self.neural_window.get_filter();
                 ui.label(format!(
                     "Simulation is {}",
@@ -114,6 +269,101 @@ impl GuiWindow {
                     self.sim_state.fps, self.sim_state.ups
                 ));
                 ui.label(format!("Generation: {}", self.sim_state.generations));
+                ui.checkbox(&mut app.simulation.statistics.enabled, "Live statistics");
+                if let Some(stats) = &self.sim_state.statistics {
+                    ui.label(format!(
+                        "Live: {}, Births: {}, Deaths: {} (generation {})",
+                        stats.live, stats.births, stats.deaths, stats.generation
+                    ));
+                    for (c, name) in ["R", "G", "B", "A"].iter().enumerate() {
+                        ui.label(format!(
+                            "{}: mean {:.4}, variance {:.4}",
+                            name, stats.mean[c], stats.variance[c]
+                        ));
+                    }
+                }
+                //Steps the live simulation, checking every generation against the cpu reference engine.
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.verify_generations)
+                            .clamp_range(1..=10000)
+                            .suffix(" gens"),
+                    );
+                    if ui.button("Verify Against CPU").clicked() {
+                        let result = app
+                            .simulation
+                            .verify_against_cpu(gpu, self.verify_generations);
+                        self.verify_result = Some(result.to_string());
+                    }
+                });
+                if let Some(result) = &self.verify_result {
+                    ui.label(result);
+                }
+
+                ui.separator();
+                ui.heading("History");
+                let history = &mut app.simulation.history;
+                ui.checkbox(&mut history.enabled, "Keep history");
+                ui.horizontal(|ui| {
+                    ui.label("Memory budget:");
+                    ui.add(
+                        egui::Slider::new(&mut history.budget_mb, 16..=4096)
+                            .logarithmic(true)
+                            .suffix(" MB"),
+                    );
+                });
+                //Scrubbing pauses, since running on would overwrite what's shown. Resuming drops the later generations.
+                if let Some((oldest, newest)) = app.simulation.history.range() {
+                    let current = app.simulation.get_simulation_state_mut().generations;
+                    let mut generation = current.clamp(oldest, newest);
+                    let mut scrubbed = false;
+                    ui.horizontal(|ui| {
+                        if ui.button("<").clicked() && generation > oldest {
+                            generation -= 1;
+                            scrubbed = true;
+                        }
+                        scrubbed |= ui
+                            .add(egui::Slider::new(&mut generation, oldest..=newest).integer())
+                            .changed();
+                        if ui.button(">").clicked() && generation < newest {
+                            generation += 1;
+                            scrubbed = true;
+                        }
+                    });
+                    ui.label(format!("Kept generations {} to {}", oldest, newest));
+                    if scrubbed && app.simulation.rewind(gpu, generation) {
+                        self.sim_state.paused = true;
+                    }
+                }
+                ui.separator();
+                ui.heading("Cycles");
+                let cycles = &mut app.simulation.cycles;
+                ui.checkbox(&mut cycles.enabled, "Detect cycles");
+                ui.horizontal(|ui| {
+                    ui.label("When found:");
+                    ui.radio_value(&mut cycles.action, CycleAction::Report, "Report");
+                    ui.radio_value(&mut cycles.action, CycleAction::Pause, "Pause");
+                    ui.radio_value(&mut cycles.action, CycleAction::Restart, "Restart");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Longest period:");
+                    ui.add(
+                        egui::DragValue::new(&mut cycles.window)
+                            .clamp_range(1..=65536)
+                            .suffix(" gens"),
+                    );
+                });
+                match cycles.outcome {
+                    Some(outcome) => ui.label(outcome.to_string()),
+                    None if cycles.enabled => ui.label("No repeat found yet"),
+                    None => ui.label("Not checking"),
+                };
+                if cycles.restarts > 0 {
+                    ui.label(format!("Restarted {} times", cycles.restarts));
+                    if let Some(outcome) = cycles.last_restart {
+                        ui.label(format!("Last soup: {}", outcome));
+                    }
+                }
                 ui.separator();
                 ui.heading("Simulation Type");
                 ui.horizontal(|ui| {
@@ -127,16 +377,79 @@ impl GuiWindow {
                         SimulationType::Neural,
                         "Neural",
                     );
+                    ui.radio_value(
+                        &mut self.selected_simulation_type,
+                        SimulationType::Wave,
+                        "Wave",
+                    );
+                    ui.radio_value(
+                        &mut self.selected_simulation_type,
+                        SimulationType::Heat,
+                        "Heat",
+                    );
+                    ui.radio_value(
+                        &mut self.selected_simulation_type,
+                        SimulationType::Volume,
+                        "3D",
+                    );
+                    ui.radio_value(
+                        &mut self.selected_simulation_type,
+                        SimulationType::GrowingNca,
+                        "Growing NCA",
+                    );
                 });
+                if self.selected_simulation_type == SimulationType::Volume {
+                    //Survival/birth/states/neighborhood, or a named rule like "amoeba"
+                    ui.label("3D Rule String:");
+                    ui.add_sized(
+                        [120.0, 15.0],
+                        egui::TextEdit::singleline(&mut self.volume_rule_str),
+                    );
+                    ui.label("Volume Size:");
+                    ui.add(egui::Slider::new(&mut self.volume_size, 16..=256).integer());
+                    ui.label("Right drag to orbit, scroll to zoom.");
+                }
                 //Rule
                 ui.label("Rule String:");
                 ui.add_sized([80.0, 15.0], egui::TextEdit::singleline(&mut self.rule_str));
+                ui.horizontal(|ui| {
+                    ui.label("Species:");
+                    ui.add(egui::Slider::new(&mut self.species, 1..=MAX_SPECIES as u32).integer());
+                });
+                if self.species > 1 {
+                    ui.checkbox(&mut self.per_species_rules, "Per species rules");
+                    if self.per_species_rules {
+                        for s in 0..self.species as usize {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Species {}:", s + 1));
+                                ui.add_sized(
+                                    [80.0, 15.0],
+                                    egui::TextEdit::singleline(&mut self.species_rule_strs[s]),
+                                );
+                            });
+                        }
+                    }
+                }
+                //Packed grids hold 32 cells per word, so they can be far bigger than textures.
+                let packed = &mut app.simulation.totalistic_state.packed.enabled;
+                let mut max_size = 8192;
+                if self.species == 1 {
+                    ui.checkbox(packed, "Bit packed cells");
+                    if *packed {
+                        max_size = MAX_PACKED_SIZE;
+                        ui.label(
+                            "Rounded up to a multiple of 32 wide. No rule mask, history, statistics or cycle detection.",
+                        );
+                    }
+                }
+                self.sim_size.x = self.sim_size.x.min(max_size);
+                self.sim_size.y = self.sim_size.y.min(max_size);
 
                 ui.horizontal(|ui| {
                     ui.label("Width:");
-                    ui.add(egui::Slider::new(&mut self.sim_size.x, 8..=8192).integer());
+                    ui.add(egui::Slider::new(&mut self.sim_size.x, 8..=max_size).integer());
                     ui.label("Height:");
-                    ui.add(egui::Slider::new(&mut self.sim_size.y, 8..=8192).integer());
+                    ui.add(egui::Slider::new(&mut self.sim_size.y, 8..=max_size).integer());
                 });
 
                 if ui.button("Recreate Simulation").clicked() {
@@ -146,7 +459,11 @@ impl GuiWindow {
                 ui.separator();
                 ui.heading("Simulation Update Rate");
                 ui.label("Updates Per Frame:");
-                ui.add(egui::Slider::new(&mut self.updates_per_frame, 1..=100).integer());
+                ui.add(
+                    egui::Slider::new(&mut self.updates_per_frame, 1..=10000)
+                        .logarithmic(true)
+                        .integer(),
+                );
                 ui.label("Update Delay:");
                 ui.add(egui::Slider::new(&mut self.update_delay, 0..=1000).integer());
                 if ui.button("Apply").clicked() {
@@ -157,6 +474,59 @@ impl GuiWindow {
                     app.time
                         .update_delays(self.updates_per_frame, self.update_delay);
                 }
+
+                ui.separator();
+                ui.heading("Update Scheme");
+                ui.horizontal(|ui| {
+                    ui.radio_value(
+                        &mut self.update_scheme,
+                        UpdateScheme::Synchronous,
+                        "Synchronous",
+                    );
+                    ui.radio_value(
+                        &mut self.update_scheme,
+                        UpdateScheme::RandomSequential {
+                            alpha: self.update_alpha,
+                        },
+                        "Random",
+                    );
+                    ui.radio_value(
+                        &mut self.update_scheme,
+                        UpdateScheme::Checkerboard,
+                        "Checkerboard",
+                    );
+                    ui.radio_value(
+                        &mut self.update_scheme,
+                        UpdateScheme::BlockSequential {
+                            block_size: self.update_block_size,
+                        },
+                        "Block",
+                    );
+                });
+                match &mut self.update_scheme {
+                    UpdateScheme::RandomSequential { alpha } => {
+                        ui.label("Update Probability:");
+                        ui.add(egui::Slider::new(alpha, 0.0..=1.0));
+                        self.update_alpha = *alpha;
+                    }
+                    UpdateScheme::BlockSequential { block_size } => {
+                        ui.label("Block Size:");
+                        ui.add(egui::Slider::new(block_size, 1..=8).integer());
+                        self.update_block_size = *block_size;
+                    }
+                    _ => {}
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Seed:");
+                    ui.add(egui::DragValue::new(&mut self.update_seed));
+                });
+                if ui.button("Apply Scheme").clicked() {
+                    let update = app
+                        .simulation
+                        .get_update_parameters_mut(self.selected_simulation_type);
+                    update.scheme = self.update_scheme;
+                    update.set_seed(self.update_seed);
+                }
             });
 
         //Return gui response
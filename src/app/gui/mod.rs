@@ -1,6 +1,10 @@
 pub mod error_window;
 pub mod gui_window;
+pub mod nca_window;
 pub mod neural_window;
+pub mod pde_window;
+pub mod rule_window;
+pub mod search_window;
 use egui::FontDefinitions;
 use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
 use egui_winit_platform::{Platform, PlatformDescriptor};
@@ -25,6 +29,11 @@ impl Gui {
         &mut self.gui_window.sim_state
     }
 
+    //Generations stepped by the Step N button and its shortcut.
+    pub fn get_step_count(&self) -> usize {
+        self.gui_window.step_count
+    }
+
     pub fn new(gpu: &Gpu, window: &Window) -> Gui {
         let size = window.inner_size();
         let platform = Platform::new(PlatformDescriptor {
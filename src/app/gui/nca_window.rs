@@ -0,0 +1,41 @@
+use egui::{Context, Response};
+
+use crate::app::{simulation::nca_parameters::NcaModel, App};
+
+pub struct NcaWindow {
+    model_path: String,
+    status: String,
+}
+
+impl NcaWindow {
+    pub fn new() -> NcaWindow {
+        NcaWindow {
+            model_path: String::new(),
+            status: "No model loaded. Cells won't change.".to_owned(),
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &Context, app: &mut App) -> Response {
+        let w = egui::Window::new("Growing NCA").show(ctx, |ui| {
+            ui.label("Model (.json file or directory of .npy files):");
+            ui.text_edit_singleline(&mut self.model_path);
+            if ui.button("Load").clicked() {
+                match NcaModel::load(self.model_path.as_str()) {
+                    Ok(model) => {
+                        self.status = format!("Loaded model with {} hidden units.", model.hidden);
+                        app.simulation.nca_state.params.model = model;
+                    }
+                    Err(e) => self.status = format!("Couldn't load model: {}", e),
+                }
+            }
+            ui.label(self.status.as_str());
+            ui.label(format!(
+                "Hidden units: {}",
+                app.simulation.nca_state.params.model.hidden
+            ));
+            ui.label("Recreate the simulation to plant a new seed.");
+        });
+
+        w.unwrap().response
+    }
+}
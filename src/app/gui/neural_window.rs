@@ -1,91 +1,594 @@
-use egui::{Context, Response, Slider};
+use std::collections::VecDeque;
 
-use crate::app::{simulation::neural_parameters::NeuralFilter, App};
+use egui::{Context, Response, Slider, TextureHandle};
+
+use super::{error_window::ErrorWindow, search_window::SearchWindow};
+use crate::app::{
+    gpu::Gpu,
+    image_util::ImageData,
+    simulation::{
+        neural_parameters::{
+            Activation, ChannelColours, NeuralFilter, Symmetry, COLOUR_CHANNELS, MAX_CHANNELS,
+            MAX_KERNEL_SIZE,
+        },
+        neural_presets::NeuralPreset,
+        NeuralState, DEFAULT_ACTIVATION_EXPR, DEFAULT_UPDATE_EXPR,
+    },
+    App,
+};
 
 pub struct NeuralWindow {
     filter: NeuralFilter,
+    //Output channel shown in the channel tabs.
+    selected_channel: usize,
+    kernel_size: u32,
+    //Edit kernels as a radial profile instead of a grid of weights.
+    radial: bool,
+    //Grid edits are mirrored to the partner taps of this symmetry.
+    symmetry: Symmetry,
+    //Standard deviation of the noise added by Mutate.
+    mutation_strength: f32,
+    //Recent filters, newest last, for stepping back after a randomise, mutate or preset.
+    history: VecDeque<NeuralFilter>,
+    //Weight at each integer distance from the kernel centre.
+    profile: [f32; MAX_KERNEL_SIZE as usize / 2 + 1],
+    //WGSL expression for activation(x). Can use x and the channel's activation a.
+    activation_expr: String,
+    //WGSL expression for the new cell colour. Can use next, me and conv.
+    update_expr: String,
+    error_window: Option<ErrorWindow>,
+    //Built in presets first, then the ones added or loaded by the user.
+    presets: Vec<NeuralPreset>,
+    builtin_presets: usize,
+    //Rendered lazily, one per frame, so opening the window doesn't stall.
+    thumbnails: Vec<Option<TextureHandle>>,
+    preset_name: String,
+    presets_path: String,
+    preset_status: String,
+    search_window: SearchWindow,
 }
 
+//Edge size and length of the headless runs used for preset thumbnails.
+const THUMBNAIL_SIZE: u32 = 64;
+const THUMBNAIL_STEPS: usize = 120;
+const HISTORY_LENGTH: usize = 20;
+
 impl NeuralWindow {
     pub fn new() -> NeuralWindow {
         NeuralWindow {
             filter: NeuralFilter::default(),
+            selected_channel: 0,
+            kernel_size: 3,
+            radial: false,
+            symmetry: Symmetry::None,
+            mutation_strength: 0.1,
+            history: VecDeque::new(),
+            profile: [0.0; MAX_KERNEL_SIZE as usize / 2 + 1],
+            activation_expr: DEFAULT_ACTIVATION_EXPR.to_owned(),
+            update_expr: DEFAULT_UPDATE_EXPR.to_owned(),
+            error_window: None,
+            builtin_presets: NeuralPreset::builtin().len(),
+            thumbnails: vec![None; NeuralPreset::builtin().len()],
+            presets: NeuralPreset::builtin(),
+            preset_name: String::new(),
+            presets_path: "presets.json".to_owned(),
+            preset_status: String::new(),
+            search_window: SearchWindow::new(),
         }
     }
 
-    pub fn get_filter(&self) -> NeuralFilter {
-        self.filter
+    fn render_thumbnail(ctx: &Context, gpu: &Gpu, preset: &NeuralPreset) -> TextureHandle {
+        let pipeline = NeuralState::create_pipeline(
+            gpu,
+            preset.activation_expr.as_str(),
+            preset.update_expr.as_str(),
+        );
+        //Presets whose shader doesn't compile get a black thumbnail.
+        let frames = match pipeline {
+            Ok(pipeline) => NeuralState::run_headless(
+                gpu,
+                &pipeline,
+                &preset.filter,
+                &preset.memory,
+                THUMBNAIL_SIZE,
+                &[THUMBNAIL_STEPS],
+            ),
+            Err(_) => vec![],
+        };
+        thumbnail_texture(
+            ctx,
+            format!("preset thumbnail {}", preset.name).as_str(),
+            frames.first(),
+            preset.filter.channels,
+            &preset.colours,
+        )
     }
-    pub fn ui(&mut self, ctx: &Context, app: &mut App) -> Response {
-        let edit_size = [40.0, 15.0];
-        let w = egui::Window::new("Neural Settings").show(ctx, |ui| {
-            let slider_size = [10.0, 15.0];
 
-            ui.horizontal(|ui| {
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[0], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
+    fn push_history(&mut self) {
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.filter.clone());
+    }
+
+    //Replaces the edited filter and sends it straight to the simulation.
+    fn set_filter(&mut self, filter: NeuralFilter, app: &mut App) {
+        self.filter = filter;
+        self.kernel_size = self.filter.get_kernel_size();
+        app.simulation.neural_state.params.filter = self.filter.clone();
+    }
+
+    fn update_rule_ui(&mut self, ui: &mut egui::Ui) {
+        let f = &mut self.filter;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut f.use_bias, "Bias");
+            ui.checkbox(&mut f.residual, "Residual");
+            if f.residual {
+                ui.add(
+                    Slider::new(&mut f.time_step, 0.001..=1.0)
+                        .logarithmic(true)
+                        .text("dt"),
                 );
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[1], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Out of range:");
+            ui.radio_value(&mut f.wrap, false, "Clamp");
+            ui.radio_value(&mut f.wrap, true, "Wrap");
+        });
+        ui.add(Slider::new(&mut f.fire_rate, 0.0..=1.0).text("Fire rate"));
+    }
+
+    fn explore_ui(&mut self, ui: &mut egui::Ui, app: &mut App) {
+        ui.horizontal(|ui| {
+            ui.label("Symmetry:");
+            let before = self.symmetry;
+            ui.radio_value(&mut self.symmetry, Symmetry::None, "None");
+            ui.radio_value(&mut self.symmetry, Symmetry::Full, "Full");
+            ui.radio_value(&mut self.symmetry, Symmetry::Horizontal, "Horizontal");
+            ui.radio_value(&mut self.symmetry, Symmetry::Vertical, "Vertical");
+            if self.symmetry != before {
+                self.filter.symmetrise(self.symmetry);
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Randomise").clicked() {
+                self.push_history();
+                let mut filter = self.filter.clone();
+                filter.randomise(self.symmetry);
+                self.set_filter(filter, app);
+            }
+            if ui.button("Mutate").clicked() {
+                self.push_history();
+                let mut filter = self.filter.clone();
+                filter.mutate(self.mutation_strength, self.symmetry);
+                self.set_filter(filter, app);
+            }
+            ui.add(Slider::new(&mut self.mutation_strength, 0.0..=1.0).text("Strength"));
+        });
+        ui.horizontal(|ui| {
+            let back = ui.add_enabled(!self.history.is_empty(), egui::Button::new("Back"));
+            if back.clicked() {
+                if let Some(filter) = self.history.pop_back() {
+                    self.set_filter(filter, app);
+                }
+            }
+            ui.label(format!("History: {}", self.history.len()));
+        });
+    }
+
+    //Memory and colours only change how cells look, so they're edited live on the simulation instead of waiting for Apply.
+    fn colour_ui(&mut self, ui: &mut egui::Ui, app: &mut App) {
+        let params = &mut app.simulation.neural_state.params;
+        let names = ["Red", "Green", "Blue"];
+        ui.label("Memory:");
+        for (c, memory) in params.memory.iter_mut().enumerate() {
+            if c < self.filter.channels as usize {
+                ui.label(format!("{}: computed by the filter", names[c]));
+                continue;
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut memory.enabled, names[c]);
+                ui.label("Source:");
+                egui::ComboBox::from_id_source(("memory source", c))
+                    .selected_text(names[memory.source as usize % COLOUR_CHANNELS])
+                    .show_ui(ui, |ui| {
+                        for (s, name) in names.iter().enumerate() {
+                            ui.selectable_value(&mut memory.source, s as u32, *name);
+                        }
+                    });
+            });
+            if memory.enabled {
+                ui.add(Slider::new(&mut memory.threshold, 0.0..=1.0).text("Threshold"));
+                ui.add(Slider::new(&mut memory.rearm, 0.0..=1.0).text("Rearm below"));
+                ui.add(Slider::new(&mut memory.decay, 0.5..=1.0).text("Decay"));
+                ui.add(Slider::new(&mut memory.fade_below, 0.0..=1.0).text("Fade below"));
+                ui.add(Slider::new(&mut memory.fade_decay, 0.0..=1.0).text("Fade decay"));
+            }
+        }
+        ui.separator();
+        ui.checkbox(&mut params.colours.auto, "Automatic colours");
+        if !params.colours.auto {
+            for (c, colour) in params.colours.colours.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_rgb(colour);
+                    ui.label(format!("{} channel", names[c]));
+                });
+            }
+        }
+    }
+
+    fn load_preset(&mut self, index: usize, gpu: &Gpu, app: &mut App) {
+        self.push_history();
+        let preset = self.presets[index].clone();
+        self.filter = preset.filter;
+        self.kernel_size = self.filter.get_kernel_size();
+        self.activation_expr = preset.activation_expr;
+        self.update_expr = preset.update_expr;
+        let result = app.simulation.neural_state.recompile(
+            gpu,
+            self.activation_expr.as_str(),
+            self.update_expr.as_str(),
+        );
+        if let Err(e) = result {
+            self.error_window = Some(ErrorWindow::new("Shader Compile Error", e.as_str()));
+        }
+        let params = &mut app.simulation.neural_state.params;
+        params.filter = self.filter.clone();
+        params.memory = preset.memory;
+        params.colours = preset.colours;
+    }
+
+    fn presets_ui(&mut self, ui: &mut egui::Ui, gpu: &Gpu, app: &mut App) {
+        if let Some(missing) = self.thumbnails.iter().position(|t| t.is_none()) {
+            self.thumbnails[missing] = Some(NeuralWindow::render_thumbnail(
+                ui.ctx(),
+                gpu,
+                &self.presets[missing],
+            ));
+        }
+        let mut selected = None;
+        egui::Grid::new("presets").show(ui, |ui| {
+            for (i, preset) in self.presets.iter().enumerate() {
+                ui.vertical(|ui| {
+                    let clicked = match &self.thumbnails[i] {
+                        Some(t) => ui
+                            .add(egui::ImageButton::new(t.id(), [64.0, 64.0]))
+                            .clicked(),
+                        None => ui
+                            .add_sized([72.0, 72.0], egui::Button::new("..."))
+                            .clicked(),
+                    };
+                    if clicked {
+                        selected = Some(i);
+                    }
+                    ui.label(preset.name.as_str());
+                });
+                if i % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+        if let Some(i) = selected {
+            self.load_preset(i, gpu, app);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut self.preset_name);
+            if ui.button("Add Current").clicked() && !self.preset_name.is_empty() {
+                self.presets.push(NeuralPreset {
+                    name: self.preset_name.clone(),
+                    activation_expr: self.activation_expr.clone(),
+                    update_expr: self.update_expr.clone(),
+                    filter: self.filter.clone(),
+                    memory: app.simulation.neural_state.params.memory,
+                    colours: app.simulation.neural_state.params.colours,
+                });
+                self.thumbnails.push(None);
+            }
+        });
+        //Only user presets are written, the built in ones are always there.
+        ui.label("Presets file (.json or .toml):");
+        ui.text_edit_singleline(&mut self.presets_path);
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                let user_presets = &self.presets[self.builtin_presets..];
+                self.preset_status =
+                    match NeuralPreset::save_file(self.presets_path.as_str(), user_presets) {
+                        Ok(()) => format!("Saved {} presets.", user_presets.len()),
+                        Err(e) => format!("Couldn't save: {}", e),
+                    };
+            }
+            if ui.button("Load").clicked() {
+                match NeuralPreset::load_file(self.presets_path.as_str()) {
+                    Ok(loaded) => {
+                        self.preset_status = format!("Loaded {} presets.", loaded.len());
+                        self.presets.truncate(self.builtin_presets);
+                        self.thumbnails.truncate(self.builtin_presets);
+                        self.thumbnails.extend(loaded.iter().map(|_| None));
+                        self.presets.extend(loaded);
+                    }
+                    Err(e) => self.preset_status = format!("Couldn't load: {}", e),
+                }
+            }
+        });
+        ui.label(self.preset_status.as_str());
+    }
+
+    fn shader_ui(&mut self, ui: &mut egui::Ui, gpu: &Gpu, app: &mut App) {
+        ui.label("activation(x, a) =");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.activation_expr)
+                .code_editor()
+                .desired_rows(2),
+        );
+        ui.label("update(next, me, conv) =");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.update_expr)
+                .code_editor()
+                .desired_rows(2),
+        );
+        ui.horizontal(|ui| {
+            if ui.button("Compile").clicked() {
+                let result = app.simulation.neural_state.recompile(
+                    gpu,
+                    self.activation_expr.as_str(),
+                    self.update_expr.as_str(),
                 );
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[2], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
+                if let Err(e) = result {
+                    self.error_window = Some(ErrorWindow::new("Shader Compile Error", e.as_str()));
+                }
+            }
+            if ui.button("Reset").clicked() {
+                self.activation_expr = DEFAULT_ACTIVATION_EXPR.to_owned();
+                self.update_expr = DEFAULT_UPDATE_EXPR.to_owned();
+            }
+        });
+    }
+
+    pub fn get_filter(&self) -> NeuralFilter {
+        self.filter.clone()
+    }
+
+    fn activation_ui(ui: &mut egui::Ui, activation: &mut Activation, id: usize) {
+        egui::ComboBox::from_id_source(("activation", id))
+            .selected_text(activation.name())
+            .show_ui(ui, |ui| {
+                for a in Activation::ALL {
+                    //Keep the current parameters when reselecting the same function.
+                    if ui
+                        .selectable_label(activation.name() == a.name(), a.name())
+                        .clicked()
+                        && activation.name() != a.name()
+                    {
+                        *activation = a;
+                    }
+                }
+            });
+        match activation {
+            Activation::Sin { frequency } => {
+                ui.label("Frequency:");
+                ui.add(Slider::new(frequency, 0.1..=20.0));
+            }
+            Activation::Gaussian { scale } | Activation::InverseGaussian { scale } => {
+                ui.label("Scale:");
+                ui.add(Slider::new(scale, 0.01..=5.0));
+            }
+            Activation::Polynomial { coefficients } => {
+                for (i, c) in coefficients.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("x^{}:", i));
+                        ui.add(Slider::new(c, -2.0..=2.0));
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    //Grid of weights, laid out like the kernel taps. Small kernels get sliders, larger ones drag values to keep the window a sane size.
+    fn kernel_grid(ui: &mut egui::Ui, weights: &mut [f32], kernel_size: u32, id: usize) {
+        let slider_size = [10.0, 15.0];
+        egui::Grid::new(("kernel", id))
+            .spacing([2.0, 2.0])
+            .show(ui, |ui| {
+                for row in weights.chunks_mut(kernel_size as usize) {
+                    for w in row {
+                        if kernel_size <= 3 {
+                            ui.add_sized(
+                                slider_size,
+                                Slider::new(w, -1.0..=1.0)
+                                    .logarithmic(false)
+                                    .smart_aim(false),
+                            );
+                        } else {
+                            ui.add(
+                                egui::DragValue::new(w)
+                                    .speed(0.01)
+                                    .clamp_range(-1.0..=1.0)
+                                    .fixed_decimals(2),
+                            );
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    //Sets every tap from the profile value at its rounded distance from the centre. Taps outside the radius are zero.
+    fn apply_profile(&self, weights: &mut [f32]) {
+        let r = (self.kernel_size / 2) as i32;
+        for (tap, w) in weights.iter_mut().enumerate() {
+            let dx = (tap as i32 % self.kernel_size as i32) - r;
+            let dy = (tap as i32 / self.kernel_size as i32) - r;
+            let d = ((dx * dx + dy * dy) as f32).sqrt().round() as usize;
+            *w = if d as i32 <= r { self.profile[d] } else { 0.0 };
+        }
+    }
+
+    fn kernel_ui(&mut self, ui: &mut egui::Ui, output: usize, input: usize) {
+        if self.radial {
+            if ui.button("Apply Profile").clicked() {
+                let mut weights = self.filter.kernel(output, input).to_vec();
+                self.apply_profile(&mut weights);
+                self.filter
+                    .kernel_mut(output, input)
+                    .copy_from_slice(&weights);
+            }
+        } else {
+            let mut weights = self.filter.kernel(output, input).to_vec();
+            NeuralWindow::kernel_grid(
+                ui,
+                &mut weights,
+                self.kernel_size,
+                output * MAX_CHANNELS + input,
+            );
+            let kernel = self.filter.kernel(output, input);
+            if let Some(tap) = (0..weights.len()).find(|t| weights[*t] != kernel[*t]) {
+                self.filter
+                    .set_weight(output, input, tap, weights[tap], self.symmetry);
+            }
+        }
+    }
+
+    fn channel_ui(&mut self, ui: &mut egui::Ui) {
+        let channels = self.filter.channels as usize;
+        self.selected_channel = self.selected_channel.min(channels - 1);
+        ui.horizontal(|ui| {
+            for c in 0..channels {
+                ui.selectable_value(&mut self.selected_channel, c, format!("Channel {}", c + 1));
+            }
+        });
+        let c = self.selected_channel;
+        ui.horizontal(|ui| {
+            ui.label("Bias:");
+            ui.add(Slider::new(&mut self.filter.bias[c], -1.0..=1.0));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Activation:");
+            NeuralWindow::activation_ui(ui, &mut self.filter.activations[c], c);
+        });
+        if self.radial {
+            for (d, w) in self.profile[..=(self.kernel_size / 2) as usize]
+                .iter_mut()
+                .enumerate()
+            {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Distance {}:", d));
+                    ui.add(Slider::new(w, -1.0..=1.0));
+                });
+            }
+        }
+        for i in 0..channels {
+            if channels > 1 {
+                ui.label(format!("From channel {}:", i + 1));
+            }
+            self.kernel_ui(ui, c, i);
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &Context, gpu: &Gpu, app: &mut App) -> Response {
+        //Check for error window close
+        if let Some(ew) = &mut self.error_window {
+            ew.ui(ctx);
+            if ew.should_close() {
+                self.error_window = None;
+            }
+        }
+        let w = egui::Window::new("Neural Settings").show(ctx, |ui| {
+            egui::CollapsingHeader::new("Presets").show(ui, |ui| {
+                self.presets_ui(ui, gpu, app);
+            });
+            egui::CollapsingHeader::new("Discover").show(ui, |ui| {
+                let picked = self.search_window.ui(
+                    ui,
+                    gpu,
+                    &self.filter,
+                    (self.activation_expr.as_str(), self.update_expr.as_str()),
+                    self.symmetry,
                 );
+                //The search ran with the window's shader, which may not be compiled into the simulation yet.
+                if let Some(filter) = picked {
+                    self.push_history();
+                    self.set_filter(filter, app);
+                    let result = app.simulation.neural_state.recompile(
+                        gpu,
+                        self.activation_expr.as_str(),
+                        self.update_expr.as_str(),
+                    );
+                    if let Err(e) = result {
+                        self.error_window =
+                            Some(ErrorWindow::new("Shader Compile Error", e.as_str()));
+                    }
+                }
+                if let Some(ew) = self.search_window.error_window.take() {
+                    self.error_window = Some(ew);
+                }
             });
             ui.horizontal(|ui| {
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[3], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
-                );
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[4], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
-                );
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[5], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
-                );
+                ui.label("Channels:");
+                ui.add(Slider::new(&mut self.filter.channels, 1..=MAX_CHANNELS as u32).integer());
             });
             ui.horizontal(|ui| {
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[6], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
-                );
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[7], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
-                );
-                ui.add_sized(
-                    slider_size,
-                    Slider::new(&mut self.filter.weights[8], -1.0..=1.0)
-                        .logarithmic(false)
-                        .smart_aim(false),
-                );
+                ui.label("Kernel Size:");
+                ui.add(Slider::new(&mut self.kernel_size, 1..=MAX_KERNEL_SIZE).step_by(2.0));
+            });
+            if self.kernel_size != self.filter.get_kernel_size() {
+                self.filter.set_kernel_size(self.kernel_size);
+                self.kernel_size = self.filter.get_kernel_size();
+            }
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.radial, false, "Grid");
+                ui.radio_value(&mut self.radial, true, "Radial");
+            });
+            self.update_rule_ui(ui);
+            self.explore_ui(ui, app);
+            egui::ScrollArea::both().max_height(500.0).show(ui, |ui| {
+                self.channel_ui(ui);
+            });
+            egui::CollapsingHeader::new("Memory and Colours").show(ui, |ui| {
+                self.colour_ui(ui, app);
             });
             if ui.button("Apply").clicked() {
-                app.simulation.neural_state.params.filter = self.filter;
+                app.simulation.neural_state.params.filter = self.filter.clone();
             }
+            ui.separator();
+            self.shader_ui(ui, gpu, app);
         });
 
         w.unwrap().response
     }
 }
+
+//Shows the cells of a square headless run the same way neural_render.wgsl shows the simulation with the given colours.
+pub fn thumbnail_texture(
+    ctx: &Context,
+    name: &str,
+    image: Option<&ImageData>,
+    channels: u32,
+    colours: &ChannelColours,
+) -> TextureHandle {
+    let size = image.map_or(THUMBNAIL_SIZE as usize, |i| i.size.x as usize);
+    let mut rgba = vec![0u8; size * size * 4];
+    if let Some(image) = image {
+        let colours = colours.resolve(channels);
+        for (out, cell) in rgba.chunks_exact_mut(4).zip(image.data.chunks_exact(4)) {
+            for (i, out) in out[..3].iter_mut().enumerate() {
+                let v: f32 = colours
+                    .iter()
+                    .zip(cell)
+                    .map(|(colour, c)| *c as f32 / 255.0 * colour[i])
+                    .sum();
+                *out = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+    }
+    for out in rgba.chunks_exact_mut(4) {
+        out[3] = 255;
+    }
+    ctx.load_texture(
+        name,
+        egui::ColorImage::from_rgba_unmultiplied([size, size], &rgba),
+    )
+}
@@ -0,0 +1,101 @@
+use egui::{Context, Response, Slider};
+
+use crate::app::{
+    image_util::ImageUtil,
+    math::UVec2,
+    simulation::pde_parameters::{PdeSettings, PointSource, MAX_SOURCES},
+    App,
+};
+
+pub struct PdeWindow {
+    settings: PdeSettings,
+    wall_mask_path: String,
+    wall_mask_status: String,
+}
+
+impl PdeWindow {
+    pub fn new() -> PdeWindow {
+        PdeWindow {
+            settings: PdeSettings::default(),
+            wall_mask_path: String::new(),
+            wall_mask_status: "Using default double slit walls.".to_owned(),
+        }
+    }
+
+    pub fn get_settings(&self) -> PdeSettings {
+        self.settings.clone()
+    }
+
+    pub fn ui(&mut self, ctx: &Context, app: &mut App) -> Response {
+        let w = egui::Window::new("PDE Settings").show(ctx, |ui| {
+            ui.label("Wave Speed (Courant number):");
+            ui.add(Slider::new(&mut self.settings.wave_speed, 0.0..=0.7));
+            ui.label("Damping:");
+            ui.add(Slider::new(&mut self.settings.damping, 0.0..=0.1).logarithmic(true));
+            ui.label("Diffusivity:");
+            ui.add(Slider::new(&mut self.settings.diffusivity, 0.0..=0.25));
+            ui.label("Time Step:");
+            ui.add(Slider::new(&mut self.settings.time_step, 0.001..=1.0).logarithmic(true));
+
+            ui.separator();
+            ui.heading("Sources");
+            let mut remove = None;
+            for (i, source) in self.settings.sources.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label("X:");
+                    ui.add(egui::DragValue::new(&mut source.position.x));
+                    ui.label("Y:");
+                    ui.add(egui::DragValue::new(&mut source.position.y));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Amplitude:");
+                    ui.add(egui::DragValue::new(&mut source.amplitude).speed(0.01));
+                    ui.label("Frequency:");
+                    ui.add(egui::DragValue::new(&mut source.frequency).speed(0.01));
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                self.settings.sources.remove(i);
+            }
+            if self.settings.sources.len() < MAX_SOURCES && ui.button("Add Source").clicked() {
+                let size = app.simulation.size;
+                self.settings.sources.push(PointSource {
+                    position: UVec2::new(size.x / 2, size.y / 2),
+                    amplitude: 1.0,
+                    frequency: 0.5,
+                });
+            }
+
+            ui.separator();
+            ui.heading("Walls");
+            ui.label("Wall mask image (bright pixels are walls):");
+            ui.text_edit_singleline(&mut self.wall_mask_path);
+            ui.horizontal(|ui| {
+                if ui.button("Load").clicked() {
+                    match ImageUtil::load_wall_mask(self.wall_mask_path.as_str()) {
+                        Ok(mask) => {
+                            app.simulation.pde_state.wall_mask = Some(mask);
+                            self.wall_mask_status =
+                                "Loaded. Recreate the simulation to apply.".to_owned();
+                        }
+                        Err(e) => self.wall_mask_status = format!("Couldn't load mask: {}", e),
+                    }
+                }
+                if ui.button("Default").clicked() {
+                    app.simulation.pde_state.wall_mask = None;
+                    self.wall_mask_status = "Using default double slit walls.".to_owned();
+                }
+            });
+            ui.label(self.wall_mask_status.as_str());
+
+            if ui.button("Apply").clicked() {
+                app.simulation.pde_state.params.settings = self.get_settings();
+            }
+        });
+
+        w.unwrap().response
+    }
+}
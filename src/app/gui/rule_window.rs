@@ -0,0 +1,139 @@
+use egui::{Context, Response, Slider};
+
+use crate::app::{
+    gpu::Gpu,
+    math::{FVec2, IVec2, UVec2},
+    rule::Rule,
+    simulation::totalistic_parameters::MAX_REGION_RULES,
+    App,
+};
+
+//Lets different regions of the grid run different rules. Rules are picked per cell from a rule mask, which can be painted with the mouse or loaded from an image.
+pub struct RuleWindow {
+    use_rule_mask: bool,
+    rule_strs: Vec<String>,
+    painting: bool,
+    brush_index: usize,
+    brush_radius: u32,
+    mask_path: String,
+    status: String,
+}
+
+impl RuleWindow {
+    pub fn new() -> RuleWindow {
+        RuleWindow {
+            use_rule_mask: false,
+            rule_strs: vec!["B3/S23".to_owned(), "B36/S23".to_owned()],
+            painting: false,
+            brush_index: 1,
+            brush_radius: 16,
+            mask_path: String::new(),
+            status: String::new(),
+        }
+    }
+
+    fn apply_rules(&mut self, app: &mut App) {
+        let mut rules = Vec::new();
+        for (i, rule_str) in self.rule_strs.iter().enumerate() {
+            match Rule::from_rule_str(rule_str.as_str()) {
+                Ok(rule) => rules.push(rule),
+                Err(_) => {
+                    self.status = format!("Rule {} is invalid.", i);
+                    return;
+                }
+            }
+        }
+        let params = &mut app.simulation.totalistic_state.params;
+        params.region_rules = rules;
+        params.use_rule_mask = self.use_rule_mask;
+        self.status = "Rules applied.".to_owned();
+    }
+
+    fn paint(&mut self, ctx: &Context, gpu: &Gpu, app: &mut App, window_size: IVec2) {
+        if ctx.is_pointer_over_area() || !ctx.input().pointer.primary_down() {
+            return;
+        }
+        if let Some(pos) = ctx.input().pointer.interact_pos() {
+            let ppp = ctx.pixels_per_point();
+            let tex = app
+                .camera
+                .screen_to_tex(FVec2::new(pos.x * ppp, pos.y * ppp), window_size);
+            let size = app.simulation.size;
+            let cell = UVec2::new(
+                ((tex.x.rem_euclid(1.0)) * size.x as f32) as u32,
+                ((tex.y.rem_euclid(1.0)) * size.y as f32) as u32,
+            );
+            app.simulation.totalistic_state.params.paint_rule_mask(
+                gpu,
+                cell,
+                self.brush_radius,
+                self.brush_index as u8,
+            );
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &Context, gpu: &Gpu, app: &mut App, window_size: IVec2) -> Response {
+        if self.painting {
+            self.paint(ctx, gpu, app, window_size);
+        }
+        let w = egui::Window::new("Rule Regions").show(ctx, |ui| {
+            ui.checkbox(&mut self.use_rule_mask, "Use rule mask");
+            let mut remove = None;
+            for (i, rule_str) in self.rule_strs.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", i));
+                    ui.add_sized([80.0, 15.0], egui::TextEdit::singleline(rule_str));
+                    if i > 0 && ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                self.rule_strs.remove(i);
+            }
+            if self.rule_strs.len() < MAX_REGION_RULES && ui.button("Add Rule").clicked() {
+                self.rule_strs.push("B3/S23".to_owned());
+            }
+            self.brush_index = self.brush_index.min(self.rule_strs.len() - 1);
+
+            ui.separator();
+            ui.checkbox(&mut self.painting, "Paint with left mouse");
+            ui.label("Brush Rule:");
+            ui.add(Slider::new(
+                &mut self.brush_index,
+                0..=self.rule_strs.len() - 1,
+            ));
+            ui.label("Brush Radius:");
+            ui.add(Slider::new(&mut self.brush_radius, 1..=256).integer());
+
+            ui.separator();
+            ui.label("Rule mask image (black is rule 0, white is the last rule):");
+            ui.text_edit_singleline(&mut self.mask_path);
+            ui.horizontal(|ui| {
+                if ui.button("Load").clicked() {
+                    match image::open(self.mask_path.as_str()) {
+                        Ok(img) => {
+                            self.apply_rules(app);
+                            app.simulation
+                                .totalistic_state
+                                .params
+                                .load_rule_mask(gpu, &img.to_luma8());
+                            self.status = "Rule mask loaded.".to_owned();
+                        }
+                        Err(e) => self.status = format!("Couldn't load mask: {}", e),
+                    }
+                }
+                if ui.button("Clear").clicked() {
+                    app.simulation.totalistic_state.params.clear_rule_mask(gpu);
+                }
+            });
+
+            if ui.button("Apply").clicked() {
+                self.apply_rules(app);
+            }
+            ui.label(self.status.as_str());
+        });
+
+        w.unwrap().response
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use egui::{Slider, TextureHandle};
+
+use super::{error_window::ErrorWindow, neural_window::thumbnail_texture};
+use crate::app::{
+    gpu::Gpu,
+    simulation::{
+        neural_parameters::{ChannelColours, NeuralFilter, Symmetry},
+        neural_search::{MetricWeights, NeuralSearch, SearchStrategy},
+    },
+};
+
+//Discover mode. Runs candidate filters off screen and shows the best ones in a gallery.
+pub struct SearchWindow {
+    search: Option<NeuralSearch>,
+    running: bool,
+    evolutionary: bool,
+    mutation_strength: f32,
+    //Candidates evaluated each frame while running.
+    evaluations_per_frame: u32,
+    weights: MetricWeights,
+    //Gallery thumbnails by result id.
+    thumbnails: HashMap<usize, TextureHandle>,
+    pub error_window: Option<ErrorWindow>,
+}
+
+impl SearchWindow {
+    pub fn new() -> SearchWindow {
+        SearchWindow {
+            search: None,
+            running: false,
+            evolutionary: false,
+            mutation_strength: 0.1,
+            evaluations_per_frame: 1,
+            weights: MetricWeights::default(),
+            thumbnails: HashMap::new(),
+            error_window: None,
+        }
+    }
+
+    fn weights_ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        let w = &mut self.weights;
+        for (name, value) in [
+            ("Activity", &mut w.activity),
+            ("Entropy", &mut w.entropy),
+            ("Change", &mut w.change),
+            ("Non-stationarity", &mut w.non_stationarity),
+        ] {
+            changed |= ui.add(Slider::new(value, 0.0..=2.0).text(name)).changed();
+        }
+        changed
+    }
+
+    //The search starts from the filter being edited, keeping its channels, kernel size, activations and shader.
+    //Returns a filter when one is picked from the gallery.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        gpu: &Gpu,
+        base: &NeuralFilter,
+        (activation_expr, update_expr): (&str, &str),
+        symmetry: Symmetry,
+    ) -> Option<NeuralFilter> {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.evolutionary, false, "Random");
+            ui.radio_value(&mut self.evolutionary, true, "Evolutionary");
+        });
+        if self.evolutionary {
+            ui.add(Slider::new(&mut self.mutation_strength, 0.0..=1.0).text("Mutation"));
+        }
+        ui.add(Slider::new(&mut self.evaluations_per_frame, 1..=20).text("Runs per frame"));
+        ui.label("Metric weights:");
+        let weights_changed = self.weights_ui(ui);
+
+        ui.horizontal(|ui| {
+            if ui.button("Start").clicked() {
+                match NeuralSearch::new(gpu, base.clone(), activation_expr, update_expr) {
+                    Ok(search) => {
+                        self.search = Some(search);
+                        self.thumbnails.clear();
+                        self.running = true;
+                    }
+                    Err(e) => {
+                        self.error_window =
+                            Some(ErrorWindow::new("Shader Compile Error", e.as_str()))
+                    }
+                }
+            }
+            if self.search.is_some() {
+                let label = if self.running { "Pause" } else { "Resume" };
+                if ui.button(label).clicked() {
+                    self.running = !self.running;
+                }
+            }
+        });
+
+        let search = self.search.as_mut()?;
+        search.strategy = if self.evolutionary {
+            SearchStrategy::Evolutionary {
+                mutation_strength: self.mutation_strength,
+            }
+        } else {
+            SearchStrategy::Random
+        };
+        search.symmetry = symmetry;
+        search.weights = self.weights;
+        if weights_changed {
+            search.rescore();
+        }
+        if self.running {
+            for _ in 0..self.evaluations_per_frame {
+                search.step(gpu);
+            }
+            ui.ctx().request_repaint();
+        }
+        ui.label(format!("Evaluated: {}", search.evaluated));
+
+        self.thumbnails
+            .retain(|id, _| search.results.iter().any(|r| r.id == *id));
+        let mut picked = None;
+        egui::Grid::new("search gallery").show(ui, |ui| {
+            for (i, result) in search.results.iter().enumerate() {
+                let texture = self.thumbnails.entry(result.id).or_insert_with(|| {
+                    thumbnail_texture(
+                        ui.ctx(),
+                        format!("search result {}", result.id).as_str(),
+                        Some(&result.image),
+                        result.filter.channels,
+                        &ChannelColours::default(),
+                    )
+                });
+                ui.vertical(|ui| {
+                    if ui
+                        .add(egui::ImageButton::new(texture.id(), [64.0, 64.0]))
+                        .on_hover_text(format!(
+                            "Activity {:.2}, entropy {:.2}, change {:.3}, non-stationarity {:.3}",
+                            result.metrics.activity,
+                            result.metrics.entropy,
+                            result.metrics.change,
+                            result.metrics.non_stationarity
+                        ))
+                        .clicked()
+                    {
+                        picked = Some(result.filter.clone());
+                    }
+                    ui.label(format!("{:.3}", result.score));
+                });
+                if i % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+        picked
+    }
+}
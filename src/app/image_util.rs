@@ -7,12 +7,20 @@ pub struct ImageUtil {}
 #[allow(dead_code)]
 pub type InputImageType = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
 
+pub type FloatImageType = image::ImageBuffer<image::Rgba<f32>, Vec<f32>>;
+
 #[allow(dead_code)]
 pub struct ImageData {
     pub size: IVec2,
     pub data: Vec<u8>,
 }
 
+impl ImageData {
+    pub fn into_image(self) -> InputImageType {
+        InputImageType::from_raw(self.size.x as u32, self.size.y as u32, self.data).unwrap()
+    }
+}
+
 impl ImageUtil {
     pub fn random_image_monochrome(w: u32, h: u32) -> InputImageType {
         let mut image_buffer: image::ImageBuffer<image::Rgba<u8>, Vec<u8>> =
@@ -44,7 +52,80 @@ impl ImageUtil {
         image_buffer
     }
 
-    #[allow(dead_code)]
+    //Random soup for multi species totalistic simulations. Each live cell gets a random species, stored one hot in the RGBA channels.
+    pub fn random_image_species(w: u32, h: u32, species: u32) -> InputImageType {
+        let mut image_buffer: InputImageType = image::ImageBuffer::new(w, h);
+        for (_x, _y, p) in image_buffer.enumerate_pixels_mut() {
+            let mut c: [u8; 4] = [0, 0, 0, 0];
+            if rand::random() {
+                let s = rand::random::<u32>() % species.max(1);
+                c[s as usize] = 255;
+            }
+            *p = Rgba(c);
+        }
+        image_buffer
+    }
+
+    //Random soup for volumetric automata. Cells inside a centred cube of edge size * fraction are alive with probability density, everything else is dead.
+    pub fn random_volume(size: u32, fraction: f32, density: f32) -> Vec<u32> {
+        let half = (size as f32 * fraction / 2.0).max(1.0);
+        let centre = size as f32 / 2.0;
+        let mut cells = vec![0u32; (size * size * size) as usize];
+        for z in 0..size {
+            for y in 0..size {
+                for x in 0..size {
+                    let inside = [x, y, z]
+                        .iter()
+                        .all(|c| (*c as f32 + 0.5 - centre).abs() < half);
+                    if inside && rand::random::<f32>() < density {
+                        cells[((z * size + y) * size + x) as usize] = 1;
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    //Builds the starting state for the PDE simulations. The field starts at rest, and walls are stored in the blue channel.
+    pub fn pde_image(w: u32, h: u32, wall_mask: &image::GrayImage) -> FloatImageType {
+        let mask = image::imageops::resize(wall_mask, w, h, image::imageops::FilterType::Nearest);
+        let mut image_buffer: FloatImageType = image::ImageBuffer::new(w, h);
+        for (x, y, p) in image_buffer.enumerate_pixels_mut() {
+            let wall = if mask.get_pixel(x, y).0[0] > 127 {
+                1.0
+            } else {
+                0.0
+            };
+            *p = Rgba([0.0, 0.0, wall, 1.0]);
+        }
+        image_buffer
+    }
+
+    //Loads a wall mask from an image file. Bright pixels are walls, dark pixels are open space.
+    pub fn load_wall_mask(path: &str) -> Result<image::GrayImage, image::ImageError> {
+        Ok(image::open(path)?.to_luma8())
+    }
+
+    //Default wall mask for the PDE simulations. A vertical wall with two slits, for demonstrating interference.
+    pub fn double_slit_mask(w: u32, h: u32) -> image::GrayImage {
+        let wall_x = w / 3;
+        let slit_width = (h / 32).max(1);
+        let slit_gap = h / 6;
+        let slit_a = h / 2 - slit_gap / 2 - slit_width / 2;
+        let slit_b = h / 2 + slit_gap / 2 - slit_width / 2;
+        image::GrayImage::from_fn(w, h, |x, y| {
+            let in_wall = x >= wall_x && x < wall_x + 2;
+            let in_slit = (y >= slit_a && y < slit_a + slit_width)
+                || (y >= slit_b && y < slit_b + slit_width);
+            if in_wall && !in_slit {
+                image::Luma([255])
+            } else {
+                image::Luma([0])
+            }
+        })
+    }
+
+    //Reads back an Rgba8Unorm texture. Blocks until the copy is done.
     pub fn tex_to_buffer(tex: &wgpu::Texture, gpu: &Gpu, width: u32, height: u32) -> ImageData {
         let mut encoder = gpu
             .device
@@ -87,7 +168,7 @@ impl ImageUtil {
         gpu.queue.submit(Some(encoder.finish()));
 
         let buffer_slice = output_buffer.slice(..);
-        // let mapping = buffer_slice.map_async(wgpu::MapMode::Read, |a| a.unwrap());
+        buffer_slice.map_async(wgpu::MapMode::Read, |a| a.unwrap());
 
         gpu.device.poll(wgpu::Maintain::Wait);
 
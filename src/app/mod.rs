@@ -5,13 +5,16 @@ pub mod gui;
 pub mod image_util;
 pub mod input;
 pub mod math;
+pub mod orbit_camera;
 pub mod rule;
+pub mod rule3d;
 pub mod sim_renderer;
 pub mod simulation;
 pub mod time;
+mod volume_renderer;
 pub mod wgsl_preproc;
 
-use winit::event::WindowEvent;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
 
 use crate::renderer::Renderer;
 
@@ -21,7 +24,8 @@ use self::{
     gui::Gui,
     input::Input,
     math::UVec2,
-    simulation::{neural_parameters::NeuralCreationParameters, Simulation},
+    orbit_camera::OrbitCamera,
+    simulation::{neural_parameters::NeuralCreationParameters, Simulation, SimulationType},
     time::Time,
 };
 
@@ -32,6 +36,7 @@ pub enum RemakeError {
 
 pub struct App {
     pub camera: Camera,
+    pub orbit_camera: OrbitCamera,
     pub time: Time,
     pub simulation: Simulation,
     pub input: Input,
@@ -44,6 +49,7 @@ impl App {
         App {
             time,
             camera,
+            orbit_camera: OrbitCamera::new(),
             input: Input::new(),
             simulation: s,
         }
@@ -56,6 +62,31 @@ impl App {
         }
         self.input.handle_input(event);
 
-        self.camera.handle_input(&self.input, renderer);
+        //Stepping shortcuts only work while paused, where they don't fight the update loop.
+        //'.' steps one generation and 'N' steps the count set in the gui.
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+            ..
+        } = event
+        {
+            if self.simulation.get_simulation_state_mut().paused {
+                match key {
+                    VirtualKeyCode::Period => self.simulation.run_for(1),
+                    VirtualKeyCode::N => self.simulation.run_for(gui.get_step_count()),
+                    _ => {}
+                }
+            }
+        }
+
+        if self.simulation.get_simulation_type() == SimulationType::Volume {
+            self.orbit_camera.handle_input(&self.input);
+        } else {
+            self.camera.handle_input(&self.input, renderer);
+        }
     }
 }
@@ -0,0 +1,79 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{InnerSpace, Vector3};
+
+const ORBIT_SPEED: f32 = 0.01;
+const KEY_ORBIT_SPEED: f32 = 0.03;
+const ZOOM_SPEED: f32 = 0.1;
+const FOV_Y: f32 = 45.0;
+
+use super::{input::Input, math::FVec2};
+
+//Matches the OrbitCamera struct in volume_render.wgsl
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct OrbitCameraBuffer {
+    //w is tan(fov / 2)
+    pub eye: [f32; 4],
+    //w is the aspect ratio
+    pub forward: [f32; 4],
+    //w is the number of cell states
+    pub right: [f32; 4],
+    pub up: [f32; 4],
+}
+
+//Camera for volumetric simulations. Orbits around the centre of the volume, which spans -0.5..0.5 on its longest side.
+#[derive(Debug)]
+pub struct OrbitCamera {
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    drag_start: FVec2,
+}
+
+impl OrbitCamera {
+    pub fn new() -> OrbitCamera {
+        OrbitCamera {
+            yaw: 0.6,
+            pitch: 0.4,
+            distance: 2.0,
+            drag_start: FVec2::default(),
+        }
+    }
+
+    pub fn to_buffer(&self, aspect: f32, states: u32) -> OrbitCameraBuffer {
+        let eye = Vector3::new(
+            self.distance * self.pitch.cos() * self.yaw.sin(),
+            self.distance * self.pitch.sin(),
+            self.distance * self.pitch.cos() * self.yaw.cos(),
+        );
+        let forward = (-eye).normalize();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let tan_half_fov = (FOV_Y.to_radians() / 2.0).tan();
+        OrbitCameraBuffer {
+            eye: [eye.x, eye.y, eye.z, tan_half_fov],
+            forward: [forward.x, forward.y, forward.z, aspect],
+            right: [right.x, right.y, right.z, states as f32],
+            up: [up.x, up.y, up.z, 0.0],
+        }
+    }
+
+    pub fn handle_input(&mut self, input: &Input) {
+        self.distance =
+            (self.distance - input.scroll_delta * ZOOM_SPEED * self.distance).clamp(0.2, 10.0);
+
+        if input.mouse_drag_start {
+            self.drag_start = FVec2::new(self.yaw, self.pitch);
+        }
+
+        if input.mouse_down {
+            self.yaw = self.drag_start.x - input.drag_offset.x * ORBIT_SPEED;
+            self.pitch = self.drag_start.y + input.drag_offset.y * ORBIT_SPEED;
+        } else {
+            self.yaw -= input.movement.x * KEY_ORBIT_SPEED;
+            self.pitch -= input.movement.y * KEY_ORBIT_SPEED;
+        }
+        //Stay away from the poles so the up vector stays defined.
+        self.pitch = self.pitch.clamp(-1.5, 1.5);
+    }
+}
@@ -1,6 +1,9 @@
 use bytemuck::{Pod, Zeroable};
 use regex::Regex;
 
+//Maximum number of competing species in a multi species simulation. Each species is stored in one of the RGBA channels.
+pub const MAX_SPECIES: usize = 4;
+
 #[derive(Copy, Clone, Pod, Zeroable, Debug)]
 #[repr(C)]
 pub struct Rule {
@@ -0,0 +1,115 @@
+use bytemuck::{Pod, Zeroable};
+
+//Rule for volumetric cellular automata, in the survival/birth/states/neighborhood format, like "4/4/5/M".
+//Survival and birth are comma separated lists of neighbor counts, and can contain ranges like "5-7".
+//Cells that fail the survival rule decay through the extra states before dying. Only fully alive cells count as neighbors.
+#[derive(Copy, Clone, Pod, Zeroable, Debug)]
+#[repr(C)]
+pub struct Rule3D {
+    //Bit n is set if a live cell with n live neighbors survives.
+    pub survive: u32,
+    //Bit n is set if a dead cell with n live neighbors is born.
+    pub born: u32,
+    pub states: u32,
+    pub neighborhood: u32,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Neighborhood {
+    //26 neighbors
+    Moore,
+    //6 neighbors
+    VonNeumann,
+}
+
+#[derive(Debug)]
+pub enum Rule3DCreationError {
+    InvalidRuleString,
+}
+
+//Well known rules that can be used by name instead of a rule string.
+const NAMED_RULES: &[(&str, &str)] = &[
+    ("445", "4/4/5/M"),
+    ("amoeba", "9-26/5-7,12-13,15/5/M"),
+    ("builder", "2,6,9/4,6,8-9/10/M"),
+    ("clouds", "13-26/13-14,17-19/2/M"),
+    ("crystal", "0-6/1,3/2/N"),
+    ("pyroclastic", "4-7/6-8/10/M"),
+];
+
+impl Neighborhood {
+    pub fn max_neighbors(&self) -> u32 {
+        match self {
+            Neighborhood::Moore => 26,
+            Neighborhood::VonNeumann => 6,
+        }
+    }
+}
+
+impl Rule3D {
+    pub fn from_rule_str(rstr: &str) -> Result<Rule3D, Rule3DCreationError> {
+        let trimmed = rstr.trim();
+        let rule_str = NAMED_RULES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(trimmed))
+            .map(|(_, r)| *r)
+            .unwrap_or(trimmed);
+
+        let parts: Vec<&str> = rule_str.split('/').collect();
+        if parts.len() != 4 {
+            return Err(Rule3DCreationError::InvalidRuleString);
+        }
+        let neighborhood = match parts[3] {
+            "M" | "m" => Neighborhood::Moore,
+            "N" | "n" => Neighborhood::VonNeumann,
+            _ => return Err(Rule3DCreationError::InvalidRuleString),
+        };
+        let max = neighborhood.max_neighbors();
+        let survive = Rule3D::parse_counts(parts[0], max)?;
+        let born = Rule3D::parse_counts(parts[1], max)?;
+        let states: u32 = parts[2]
+            .parse()
+            .map_err(|_| Rule3DCreationError::InvalidRuleString)?;
+        if !(2..=255).contains(&states) {
+            return Err(Rule3DCreationError::InvalidRuleString);
+        }
+        Ok(Rule3D {
+            survive,
+            born,
+            states,
+            neighborhood: match neighborhood {
+                Neighborhood::Moore => 0,
+                Neighborhood::VonNeumann => 1,
+            },
+        })
+    }
+
+    //Parses a list like "2,6,9" or "5-7,12" into a bit mask of neighbor counts.
+    fn parse_counts(s: &str, max: u32) -> Result<u32, Rule3DCreationError> {
+        let mut mask = 0u32;
+        if s.is_empty() {
+            return Ok(mask);
+        }
+        for item in s.split(',') {
+            let (lo, hi) = match item.split_once('-') {
+                Some((lo, hi)) => (lo, hi),
+                None => (item, item),
+            };
+            let lo: u32 = lo
+                .trim()
+                .parse()
+                .map_err(|_| Rule3DCreationError::InvalidRuleString)?;
+            let hi: u32 = hi
+                .trim()
+                .parse()
+                .map_err(|_| Rule3DCreationError::InvalidRuleString)?;
+            if lo > hi || hi > max {
+                return Err(Rule3DCreationError::InvalidRuleString);
+            }
+            for n in lo..=hi {
+                mask |= 1 << n;
+            }
+        }
+        Ok(mask)
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
 
 use crate::app::{
-    gpu::Gpu,
+    camera::CameraBuffer,
+    gpu::{bindgroup::CachedBuffer, Gpu},
     math::{IVec2, Vertex},
+    volume_renderer::VolumeRenderer,
     wgsl_preproc::WgslPreProcessor,
     App,
 };
@@ -14,6 +16,13 @@ use wgpu::{util::DeviceExt, Buffer};
 pub enum RendererType {
     Totalistic,
     Neural,
+    Pde,
+    //Ray marched volume, drawn by the VolumeRenderer instead of a pipeline in render_pipelines.
+    Volume,
+    //Growing NCA, which reads the unfilterable rgba32float state.
+    Nca,
+    //Bit packed two state cells, read from a storage buffer instead of a texture.
+    Packed,
 }
 
 pub struct SimulationRenderer {
@@ -24,6 +33,17 @@ pub struct SimulationRenderer {
     pub sampler: wgpu::Sampler,
     pub size: IVec2,
     renderer_type: RendererType,
+    volume_renderer: VolumeRenderer,
+    //Texture layouts for filterable and unfilterable simulation state. Shared by every pipeline of that kind.
+    filterable_texture_layout: wgpu::BindGroupLayout,
+    unfilterable_texture_layout: wgpu::BindGroupLayout,
+    packed_cells_layout: wgpu::BindGroupLayout,
+    camera_buffer: CachedBuffer,
+    render_params_buffer: CachedBuffer,
+    render_params_bind_group: wgpu::BindGroup,
+    //Bind groups over the simulation textures, keyed by Simulation::get_current_texture_key. Holds at most the two
+    //ping-pong textures of the current simulation.
+    texture_bind_groups: HashMap<(u64, usize), wgpu::BindGroup>,
 }
 
 #[repr(C)]
@@ -31,6 +51,15 @@ pub struct SimulationRenderer {
 pub struct RenderParams {
     window_size: [i32; 2],
     sim_size: [i32; 2],
+    //Number of species in a multi species totalistic simulation.
+    species: u32,
+    //Number of channels in a neural simulation.
+    channels: u32,
+    //Which edges of the grid wrap, see Boundary.
+    boundary: u32,
+    _padding: u32,
+    //Display colour of each neural channel. Only declared in neural_render.wgsl.
+    colours: [[f32; 4]; 3],
 }
 const VERTICES: &[Vertex] = &[
     Vertex {
@@ -54,69 +83,99 @@ const VERTICES: &[Vertex] = &[
 const INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
 
 impl SimulationRenderer {
-    fn create_pipeline(shader: &wgpu::ShaderModule, gpu: &Gpu) -> wgpu::RenderPipeline {
-        let texture_bind_group_layout =
-            gpu.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            // This should match the filterable field of the
-                            // corresponding Texture entry above.
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
+    //Float32 textures can't be filtered, so simulations that store their state in them need a non filtering layout.
+    fn create_texture_layout(gpu: &Gpu, filterable: bool) -> wgpu::BindGroupLayout {
+        let sampler_type = if filterable {
+            wgpu::SamplerBindingType::Filtering
+        } else {
+            wgpu::SamplerBindingType::NonFiltering
+        };
+        gpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable },
                         },
-                    ],
-                    label: Some("SimulationRenderer_texture_bind_group_layout"),
-                });
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        // This should match the filterable field of the
+                        // corresponding Texture entry above.
+                        ty: wgpu::BindingType::Sampler(sampler_type),
+                        count: None,
+                    },
+                ],
+                label: Some("SimulationRenderer_texture_bind_group_layout"),
+            })
+    }
 
-        let render_params_bind_group_layout =
-            gpu.device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
+    fn create_packed_cells_layout(gpu: &Gpu) -> wgpu::BindGroupLayout {
+        gpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("SimulationRenderer_packed_cells_bind_group_layout"),
+            })
+    }
+
+    fn create_render_params_layout(gpu: &Gpu) -> wgpu::BindGroupLayout {
+        gpu.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: wgpu::ShaderStages::FRAGMENT,
-                            ty: wgpu::BindingType::Buffer {
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
-                    ],
-                    label: Some("render_params_bind_group_layout"),
-                });
+                        count: None,
+                    },
+                ],
+                label: Some("render_params_bind_group_layout"),
+            })
+    }
 
+    fn create_pipeline(
+        shader: &wgpu::ShaderModule,
+        gpu: &Gpu,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        render_params_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
         let render_pipeline_layout =
             gpu.device
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("Render Pipeline Layout"),
 
                     bind_group_layouts: &[
-                        &texture_bind_group_layout,
-                        &render_params_bind_group_layout,
+                        texture_bind_group_layout,
+                        render_params_bind_group_layout,
                     ],
                     push_constant_ranges: &[],
                 });
@@ -175,13 +234,28 @@ impl SimulationRenderer {
             ..Default::default()
         });
 
-        let shader_types = vec![RendererType::Totalistic, RendererType::Neural];
+        let shader_types = vec![
+            RendererType::Totalistic,
+            RendererType::Neural,
+            RendererType::Pde,
+            RendererType::Nca,
+            RendererType::Packed,
+        ];
+        let filterable_texture_layout = SimulationRenderer::create_texture_layout(gpu, true);
+        let unfilterable_texture_layout = SimulationRenderer::create_texture_layout(gpu, false);
+        let packed_cells_layout = SimulationRenderer::create_packed_cells_layout(gpu);
+        let render_params_layout = SimulationRenderer::create_render_params_layout(gpu);
         let mut pipeline_map: HashMap<RendererType, wgpu::RenderPipeline> = HashMap::new();
         for s in shader_types {
             let shader_str = match s {
                 RendererType::Totalistic => "totalistic_render.wgsl",
                 RendererType::Neural => "neural_render.wgsl",
+                RendererType::Pde => "pde_render.wgsl",
+                RendererType::Nca => "nca_render.wgsl",
+                RendererType::Packed => "packed_render.wgsl",
+                RendererType::Volume => unreachable!(),
             };
+            let filterable = s != RendererType::Pde && s != RendererType::Nca;
 
             let shader_src = WgslPreProcessor::load_and_process(shader_str, "./shaders").unwrap();
             let shader = gpu
@@ -190,7 +264,19 @@ impl SimulationRenderer {
                     label: Some("Shader"),
                     source: wgpu::ShaderSource::Wgsl(shader_src.into()),
                 });
-            let pipeline = SimulationRenderer::create_pipeline(&shader, gpu);
+            let texture_layout = if s == RendererType::Packed {
+                &packed_cells_layout
+            } else if filterable {
+                &filterable_texture_layout
+            } else {
+                &unfilterable_texture_layout
+            };
+            let pipeline = SimulationRenderer::create_pipeline(
+                &shader,
+                gpu,
+                texture_layout,
+                &render_params_layout,
+            );
             pipeline_map.insert(s, pipeline);
         }
 
@@ -211,6 +297,33 @@ impl SimulationRenderer {
                 });
         let num_indices = INDICES.len() as u32;
 
+        let camera_buffer = CachedBuffer::new(
+            gpu,
+            "Camera Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::bytes_of(&CameraBuffer::zeroed()),
+        );
+        let render_params_buffer = CachedBuffer::new(
+            gpu,
+            "Render Params Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::bytes_of(&RenderParams::zeroed()),
+        );
+        let render_params_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &render_params_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: render_params_buffer.buffer().as_entire_binding(),
+                },
+            ],
+            label: Some("Render_Params_bind_group"),
+        });
+
         Self {
             vertex_buffer,
             index_buffer,
@@ -219,10 +332,21 @@ impl SimulationRenderer {
             size,
             render_pipelines: pipeline_map,
             renderer_type: r_type,
+            volume_renderer: VolumeRenderer::new(gpu),
+            filterable_texture_layout,
+            unfilterable_texture_layout,
+            packed_cells_layout,
+            camera_buffer,
+            render_params_buffer,
+            render_params_bind_group,
+            texture_bind_groups: HashMap::new(),
         }
     }
 
     pub fn set_renderer_type(&mut self, r_type: RendererType) {
+        if r_type != self.renderer_type {
+            self.texture_bind_groups.clear();
+        }
         self.renderer_type = r_type;
     }
 
@@ -234,24 +358,14 @@ impl SimulationRenderer {
         &self.render_pipelines[&self.renderer_type]
     }
 
-    fn render_simulation(
-        &mut self,
-        gpu: &Gpu,
-        app: &mut App,
-        output: &wgpu::SurfaceTexture,
-    ) -> Result<wgpu::CommandBuffer, wgpu::SurfaceError> {
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = gpu
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-
+    fn create_texture_bind_group(&self, gpu: &Gpu, app: &App) -> wgpu::BindGroup {
+        let layout = match self.renderer_type {
+            RendererType::Pde | RendererType::Nca => &self.unfilterable_texture_layout,
+            _ => &self.filterable_texture_layout,
+        };
         let render_tex = app.simulation.get_current_texture();
-        let texture_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.get_pipeline().get_bind_group_layout(0),
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -265,44 +379,73 @@ impl SimulationRenderer {
                 },
             ],
             label: Some("diffuse_bind_group"),
-        });
+        })
+    }
 
-        let camera_buffer = gpu
+    fn render_simulation(
+        &mut self,
+        gpu: &Gpu,
+        app: &mut App,
+        output: &wgpu::SurfaceTexture,
+    ) -> Result<wgpu::CommandBuffer, wgpu::SurfaceError> {
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        if self.renderer_type == RendererType::Volume {
+            return Ok(self.volume_renderer.render(
+                gpu,
+                app,
+                &view,
+                (&self.vertex_buffer, &self.index_buffer, self.num_indices),
+                self.size,
+            ));
+        }
+        let mut encoder = gpu
             .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Camera Buffer"),
-                contents: bytemuck::bytes_of(&app.camera.to_buffer()),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
             });
 
+        //The simulation only has two textures to show, so their bind groups are made once and reused until it's remade.
+        let key = app.simulation.get_current_texture_key();
+        if !self.texture_bind_groups.contains_key(&key) {
+            self.texture_bind_groups
+                .retain(|(version, _), _| *version == key.0);
+            let texture_bind_group = match app.simulation.get_packed_buffer() {
+                Some(buffer) if self.renderer_type == RendererType::Packed => {
+                    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout: &self.packed_cells_layout,
+                        entries: &[wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffer.as_entire_binding(),
+                        }],
+                        label: Some("packed_cells_bind_group"),
+                    })
+                }
+                _ => self.create_texture_bind_group(gpu, app),
+            };
+            self.texture_bind_groups.insert(key, texture_bind_group);
+        }
+        let texture_bind_group = &self.texture_bind_groups[&key];
         let isize: IVec2 = app.simulation.size.into();
+        let neural_params = &app.simulation.neural_state.params;
         let r_params = RenderParams {
             window_size: self.size.as_slice(),
             sim_size: isize.as_slice(),
+            species: app.simulation.totalistic_state.params.species,
+            channels: app.simulation.neural_state.params.filter.channels,
+            boundary: app
+                .simulation
+                .get_boundary(app.simulation.get_simulation_type())
+                .code(),
+            _padding: 0,
+            colours: neural_params.colours.resolve(neural_params.filter.channels),
         };
-
-        let render_params_buffer =
-            gpu.device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Render Params Buffer"),
-                    contents: bytemuck::bytes_of(&r_params),
-                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                });
-
-        let render_params_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &self.get_pipeline().get_bind_group_layout(1),
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: render_params_buffer.as_entire_binding(),
-                },
-            ],
-            label: Some("Render_Params_bind_group"),
-        });
+        //Only uploaded when the camera moves or a setting changes.
+        self.camera_buffer
+            .write(gpu, bytemuck::bytes_of(&app.camera.to_buffer()));
+        self.render_params_buffer
+            .write(gpu, bytemuck::bytes_of(&r_params));
 
         {
             // 1.
@@ -323,8 +466,8 @@ impl SimulationRenderer {
             });
 
             render_pass.set_pipeline(&self.get_pipeline());
-            render_pass.set_bind_group(0, &texture_bind_group, &[]);
-            render_pass.set_bind_group(1, &render_params_bind_group, &[]);
+            render_pass.set_bind_group(0, texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.render_params_bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
             render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
@@ -343,6 +486,7 @@ impl SimulationRenderer {
         match sim_render_result {
             Ok(sim_render_command_buffer) => {
                 app.time.render_tick();
+                app.simulation.poll_readbacks(gpu);
                 match app.time.get_fps() {
                     Some(fps) => {
                         let sim_state = app.simulation.get_simulation_state_mut();
@@ -354,9 +498,14 @@ impl SimulationRenderer {
 
                 //Sync gui sim state to real sim state
 
-                while app.time.can_update() && !app.simulation.get_simulation_state_mut().paused {
-                    app.simulation.step(gpu);
-                    app.time.update_tick();
+                app.simulation.step_towards_target(gpu);
+                while !app.simulation.get_simulation_state_mut().paused {
+                    let batch = app.time.batch_size();
+                    if batch == 0 {
+                        break;
+                    }
+                    app.simulation.step_n(gpu, batch as usize);
+                    app.time.update_ticks(batch);
                 }
                 Ok(sim_render_command_buffer)
             }
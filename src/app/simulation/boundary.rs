@@ -0,0 +1,145 @@
+//What lies beyond the edges of the grid.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum Boundary {
+    //Both axes wrap around.
+    #[default]
+    Torus,
+    //Cells outside the grid are always dead.
+    Dead,
+    //Cells outside the grid are always alive.
+    Alive,
+    //The grid is reflected at its edges.
+    Mirror,
+    //Both axes wrap, and crossing the top or bottom edge flips the grid left to right.
+    KleinBottle,
+    //Only the horizontal axis wraps. Cells above and below the grid are dead.
+    Cylinder,
+}
+
+impl Boundary {
+    pub const ALL: [Boundary; 6] = [
+        Boundary::Torus,
+        Boundary::Dead,
+        Boundary::Alive,
+        Boundary::Mirror,
+        Boundary::KleinBottle,
+        Boundary::Cylinder,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Torus => "Torus",
+            Boundary::Dead => "Fixed Dead",
+            Boundary::Alive => "Fixed Alive",
+            Boundary::Mirror => "Mirror",
+            Boundary::KleinBottle => "Klein Bottle",
+            Boundary::Cylinder => "Cylinder",
+        }
+    }
+
+    //Must match the BOUNDARY_* constants in boundary.wgsl
+    pub fn code(&self) -> u32 {
+        match self {
+            Boundary::Torus => 0,
+            Boundary::Dead => 1,
+            Boundary::Alive => 2,
+            Boundary::Mirror => 3,
+            Boundary::KleinBottle => 4,
+            Boundary::Cylinder => 5,
+        }
+    }
+
+    //Same as boundary_coords in boundary.wgsl. None for positions outside a fixed boundary.
+    pub fn coords(&self, x: i32, y: i32, width: i32, height: i32) -> Option<(u32, u32)> {
+        let mirror = |v: i32, size: i32| {
+            if v < 0 {
+                -v - 1
+            } else if v >= size {
+                2 * size - v - 1
+            } else {
+                v
+            }
+        };
+        let (x, y) = match self {
+            Boundary::Torus => (x.rem_euclid(width), y.rem_euclid(height)),
+            Boundary::Mirror => (mirror(x, width), mirror(y, height)),
+            Boundary::KleinBottle => {
+                let x = if y < 0 || y >= height {
+                    width - 1 - x
+                } else {
+                    x
+                };
+                (x.rem_euclid(width), y.rem_euclid(height))
+            }
+            Boundary::Cylinder => (x.rem_euclid(width), y),
+            Boundary::Dead | Boundary::Alive => (x, y),
+        };
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inside_is_unchanged() {
+        for boundary in Boundary::ALL {
+            assert_eq!(boundary.coords(2, 3, 4, 5), Some((2, 3)));
+        }
+    }
+
+    #[test]
+    fn torus_wraps_both_axes() {
+        let b = Boundary::Torus;
+        assert_eq!(b.coords(-1, 0, 4, 5), Some((3, 0)));
+        assert_eq!(b.coords(4, 0, 4, 5), Some((0, 0)));
+        assert_eq!(b.coords(0, -1, 4, 5), Some((0, 4)));
+        assert_eq!(b.coords(0, 5, 4, 5), Some((0, 0)));
+        assert_eq!(b.coords(-1, -1, 4, 5), Some((3, 4)));
+        assert_eq!(b.coords(9, 11, 4, 5), Some((1, 1)));
+    }
+
+    #[test]
+    fn fixed_boundaries_clamp_to_nothing() {
+        for b in [Boundary::Dead, Boundary::Alive] {
+            assert_eq!(b.coords(-1, 0, 4, 5), None);
+            assert_eq!(b.coords(4, 0, 4, 5), None);
+            assert_eq!(b.coords(0, -1, 4, 5), None);
+            assert_eq!(b.coords(0, 5, 4, 5), None);
+            assert_eq!(b.coords(3, 4, 4, 5), Some((3, 4)));
+        }
+    }
+
+    #[test]
+    fn mirror_reflects_at_edges() {
+        let b = Boundary::Mirror;
+        assert_eq!(b.coords(-1, 0, 4, 5), Some((0, 0)));
+        assert_eq!(b.coords(-2, 0, 4, 5), Some((1, 0)));
+        assert_eq!(b.coords(4, 0, 4, 5), Some((3, 0)));
+        assert_eq!(b.coords(5, 0, 4, 5), Some((2, 0)));
+        assert_eq!(b.coords(0, -1, 4, 5), Some((0, 0)));
+        assert_eq!(b.coords(0, 5, 4, 5), Some((0, 4)));
+        assert_eq!(b.coords(-1, 5, 4, 5), Some((0, 4)));
+    }
+
+    #[test]
+    fn klein_bottle_flips_across_top_and_bottom() {
+        let b = Boundary::KleinBottle;
+        assert_eq!(b.coords(0, -1, 4, 5), Some((3, 4)));
+        assert_eq!(b.coords(1, 5, 4, 5), Some((2, 0)));
+        assert_eq!(b.coords(-1, 2, 4, 5), Some((3, 2)));
+    }
+
+    #[test]
+    fn cylinder_only_wraps_horizontally() {
+        let b = Boundary::Cylinder;
+        assert_eq!(b.coords(-1, 2, 4, 5), Some((3, 2)));
+        assert_eq!(b.coords(4, 2, 4, 5), Some((0, 2)));
+        assert_eq!(b.coords(0, -1, 4, 5), None);
+        assert_eq!(b.coords(0, 5, 4, 5), None);
+    }
+}
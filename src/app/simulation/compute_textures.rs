@@ -1,35 +1,76 @@
 use std::rc::Rc;
 
 use crate::app::{
-    gpu::{bindgroup::ToBindgroup, Gpu},
-    image_util::InputImageType,
+    gpu::Gpu,
+    image_util::{FloatImageType, InputImageType},
 };
 
+//Size of the simulation textures. Depth is 1 for 2D simulations.
+#[derive(Clone, Copy)]
+struct TextureSize {
+    width: u32,
+    height: u32,
+    depth: u32,
+}
+
+impl TextureSize {
+    fn extent(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: self.depth,
+        }
+    }
+
+    fn dimension(&self) -> wgpu::TextureDimension {
+        if self.depth > 1 {
+            wgpu::TextureDimension::D3
+        } else {
+            wgpu::TextureDimension::D2
+        }
+    }
+}
+
+impl From<(u32, u32)> for TextureSize {
+    fn from((width, height): (u32, u32)) -> Self {
+        TextureSize {
+            width,
+            height,
+            depth: 1,
+        }
+    }
+}
+
+impl From<(u32, u32, u32)> for TextureSize {
+    fn from((width, height, depth): (u32, u32, u32)) -> Self {
+        TextureSize {
+            width,
+            height,
+            depth,
+        }
+    }
+}
+
 pub struct ComputeTextures {
     textures: [wgpu::Texture; 2],
-    bind_group_layout: Rc<wgpu::BindGroupLayout>,
+    //Built once for both ping-pong directions. Index 0 reads the first texture and writes the second.
+    bind_groups: [wgpu::BindGroup; 2],
     current_frame: usize,
+    size: TextureSize,
+    format: wgpu::TextureFormat,
+    bytes_per_pixel: u32,
 }
 
 impl ComputeTextures {
     pub fn set_image(&mut self, img: InputImageType, gpu: &Gpu) {
-        let (read, write) = self.get_read_write();
-        let tex = &self.textures[write];
+        let (_read, write) = self.get_read_write();
         let (width, height) = img.dimensions();
-        let texture_size = wgpu::Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        };
-        gpu.queue.write_texture(
-            tex.as_image_copy(),
+        ComputeTextures::write_texture(
+            &self.textures[write],
             bytemuck::cast_slice(img.as_raw()),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * width),
-                rows_per_image: None, // Doesn't need to be specified as we are writing a single image.
-            },
-            texture_size,
+            (width, height),
+            4,
+            gpu,
         );
     }
 
@@ -52,82 +93,203 @@ impl ComputeTextures {
         input_image: InputImageType,
         gpu: &Gpu,
     ) -> ComputeTextures {
-        let (width, height) = input_image.dimensions();
+        ComputeTextures::from_bytes(
+            layout,
+            bytemuck::cast_slice(input_image.as_raw()),
+            input_image.dimensions(),
+            wgpu::TextureFormat::Rgba8Unorm,
+            4,
+            gpu,
+        )
+    }
 
-        let texture_size = wgpu::Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        };
+    //Creates the textures with a 32 bit float per channel. Used by simulations that need more precision than Rgba8Unorm can hold, such as the PDE solvers.
+    pub fn new_float(
+        layout: Rc<wgpu::BindGroupLayout>,
+        input_image: FloatImageType,
+        gpu: &Gpu,
+    ) -> ComputeTextures {
+        ComputeTextures::from_bytes(
+            layout,
+            bytemuck::cast_slice(input_image.as_raw()),
+            input_image.dimensions(),
+            wgpu::TextureFormat::Rgba32Float,
+            16,
+            gpu,
+        )
+    }
 
-        let input_texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("input texture"),
-            size: texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING
-                | wgpu::TextureUsages::COPY_DST
-                | wgpu::TextureUsages::COPY_SRC
-                | wgpu::TextureUsages::STORAGE_BINDING,
-        });
+    //Creates 3D textures holding one u32 state per cell, for volumetric simulations.
+    pub fn new_volume(
+        layout: Rc<wgpu::BindGroupLayout>,
+        cells: &[u32],
+        size: (u32, u32, u32),
+        gpu: &Gpu,
+    ) -> ComputeTextures {
+        ComputeTextures::from_bytes(
+            layout,
+            bytemuck::cast_slice(cells),
+            size,
+            wgpu::TextureFormat::R32Uint,
+            4,
+            gpu,
+        )
+    }
+
+    fn from_bytes(
+        layout: Rc<wgpu::BindGroupLayout>,
+        data: &[u8],
+        size: impl Into<TextureSize>,
+        format: wgpu::TextureFormat,
+        bytes_per_pixel: u32,
+        gpu: &Gpu,
+    ) -> ComputeTextures {
+        let size = size.into();
+        let input_texture = ComputeTextures::create_texture("input texture", size, format, gpu);
+        let output_texture = ComputeTextures::create_texture("output texture", size, format, gpu);
+        ComputeTextures::write_texture(&input_texture, data, size, bytes_per_pixel, gpu);
+        let textures = [input_texture, output_texture];
+        let bind_groups = [
+            ComputeTextures::create_bind_group(&layout, &textures[0], &textures[1], gpu),
+            ComputeTextures::create_bind_group(&layout, &textures[1], &textures[0], gpu),
+        ];
+        ComputeTextures {
+            textures,
+            bind_groups,
+            current_frame: 0,
+            size,
+            format,
+            bytes_per_pixel,
+        }
+    }
 
-        let output_texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("output texture"),
-            size: texture_size,
+    fn create_texture(
+        label: &str,
+        size: TextureSize,
+        format: wgpu::TextureFormat,
+        gpu: &Gpu,
+    ) -> wgpu::Texture {
+        gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: size.extent(),
             mip_level_count: 1,
             sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
+            dimension: size.dimension(),
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING
                 | wgpu::TextureUsages::COPY_DST
                 | wgpu::TextureUsages::COPY_SRC
                 | wgpu::TextureUsages::STORAGE_BINDING,
-        });
+        })
+    }
+
+    fn write_texture(
+        tex: &wgpu::Texture,
+        data: &[u8],
+        size: impl Into<TextureSize>,
+        bytes_per_pixel: u32,
+        gpu: &Gpu,
+    ) {
+        let size = size.into();
         gpu.queue.write_texture(
-            input_texture.as_image_copy(),
-            bytemuck::cast_slice(input_image.as_raw()),
+            tex.as_image_copy(),
+            data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(4 * width),
-                rows_per_image: None, // Doesn't need to be specified as we are writing a single image.
+                bytes_per_row: std::num::NonZeroU32::new(bytes_per_pixel * size.width),
+                rows_per_image: std::num::NonZeroU32::new(size.height),
             },
-            texture_size,
+            size.extent(),
         );
-        ComputeTextures {
-            textures: [input_texture, output_texture],
-            current_frame: 0,
-            bind_group_layout: layout.clone(),
-        }
-    }
-
-    pub fn get_read_texture(&self) -> &wgpu::Texture {
-        let (read, write) = self.get_read_write();
-        &self.textures[read]
     }
-}
 
-impl ToBindgroup for ComputeTextures {
-    fn to_bind_group(&self, gpu: &crate::app::gpu::Gpu) -> wgpu::BindGroup {
-        let (read, write) = self.get_read_write();
+    fn create_bind_group(
+        layout: &wgpu::BindGroupLayout,
+        read: &wgpu::Texture,
+        write: &wgpu::Texture,
+        gpu: &Gpu,
+    ) -> wgpu::BindGroup {
         gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("compute bind group"),
-            layout: self.bind_group_layout.as_ref(),
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
                     resource: wgpu::BindingResource::TextureView(
-                        &self.textures[read].create_view(&wgpu::TextureViewDescriptor::default()),
+                        &read.create_view(&wgpu::TextureViewDescriptor::default()),
                     ),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
                     resource: wgpu::BindingResource::TextureView(
-                        &self.textures[write].create_view(&wgpu::TextureViewDescriptor::default()),
+                        &write.create_view(&wgpu::TextureViewDescriptor::default()),
                     ),
                 },
             ],
         })
     }
+
+    //Bind group that reads the state of the given frame and writes the next one.
+    pub fn bind_group(&self, frame: usize) -> &wgpu::BindGroup {
+        &self.bind_groups[frame % 2]
+    }
+
+    //Size of one generation of cells on the GPU.
+    pub fn frame_bytes(&self) -> u64 {
+        self.size.width as u64
+            * self.size.height as u64
+            * self.size.depth as u64
+            * self.bytes_per_pixel as u64
+    }
+
+    //A texture that can hold a copy of one generation, see save_frame and load_frame.
+    pub fn create_snapshot_texture(&self, gpu: &Gpu) -> wgpu::Texture {
+        gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("snapshot texture"),
+            size: self.size.extent(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: self.size.dimension(),
+            format: self.format,
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+        })
+    }
+
+    //Copies the cells of the given frame into a snapshot texture. The frame must be the current one or the one before.
+    pub fn save_frame(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: usize,
+        dst: &wgpu::Texture,
+    ) {
+        encoder.copy_texture_to_texture(
+            self.textures[frame % 2].as_image_copy(),
+            dst.as_image_copy(),
+            self.size.extent(),
+        );
+    }
+
+    //Copies a snapshot back into the texture the given frame reads from.
+    pub fn load_frame(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: usize,
+        src: &wgpu::Texture,
+    ) {
+        encoder.copy_texture_to_texture(
+            src.as_image_copy(),
+            self.textures[frame % 2].as_image_copy(),
+            self.size.extent(),
+        );
+    }
+
+    //Texture holding the given frame, or the frame before it for the other one.
+    pub fn frame_texture(&self, frame: usize) -> &wgpu::Texture {
+        &self.textures[frame % 2]
+    }
+
+    pub fn get_read_texture(&self) -> &wgpu::Texture {
+        let (read, _write) = self.get_read_write();
+        &self.textures[read]
+    }
 }
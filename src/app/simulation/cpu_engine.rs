@@ -0,0 +1,449 @@
+/* Reference implementations of the totalistic and neural shaders on the cpu.
+Slow, but simple enough to trust. Used when there's no GPU at all, and to check the shaders against in lockstep.
+Cells are read and written as Rgba8Unorm like the GPU does, so a correct shader matches these bit for bit, up to float rounding.
+*/
+
+use bytemuck::Zeroable;
+
+use crate::app::{image_util::InputImageType, rule::Rule};
+
+use super::{
+    boundary::Boundary,
+    neural_parameters::{MemoryChannel, NeuralFilter, MAX_CHANNELS},
+    update_parameters::{pcg_hash, random_unit, UpdateParamsBuffer},
+};
+
+//Neural cells go through exp, pow and a long sum, which don't round the same on every GPU. Differences up to this many
+//255ths of a channel are accepted.
+pub const NEURAL_TOLERANCE: u8 = 2;
+
+//Result of running the GPU and cpu engines side by side.
+pub enum Conformance {
+    //Every cell matched for this many generations.
+    Matched {
+        generations: usize,
+    },
+    //The first cell that differed. Later cells and generations aren't checked.
+    Mismatch {
+        generation: usize,
+        x: u32,
+        y: u32,
+        gpu: [u8; 4],
+        cpu: [u8; 4],
+    },
+    //The simulation type or its settings have no cpu implementation.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for Conformance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Conformance::Matched { generations } => {
+                write!(f, "GPU and cpu matched for {} generations", generations)
+            }
+            Conformance::Mismatch {
+                generation,
+                x,
+                y,
+                gpu,
+                cpu,
+            } => write!(
+                f,
+                "First difference in generation {} at ({}, {}): GPU {:?}, cpu {:?}",
+                generation, x, y, gpu, cpu
+            ),
+            Conformance::Unsupported(reason) => write!(f, "Can't verify: {}", reason),
+        }
+    }
+}
+
+fn unorm(v: u8) -> f32 {
+    v as f32 / 255.0
+}
+
+//Rgba8Unorm stores clamp, then round to the nearest step.
+fn to_unorm(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+//Same as get_pixel_boundary in boundary.wgsl.
+fn pixel_boundary(image: &InputImageType, boundary: Boundary, x: i32, y: i32) -> [u8; 4] {
+    let (w, h) = image.dimensions();
+    match boundary.coords(x, y, w as i32, h as i32) {
+        Some((x, y)) => image.get_pixel(x, y).0,
+        None if boundary == Boundary::Alive => [255; 4],
+        None => [0; 4],
+    }
+}
+
+fn close(a: f32, b: i32) -> bool {
+    (a - b as f32).abs() < 0.2
+}
+
+//Same as species_of in totalistic.wgsl. None for a dead cell.
+fn species_of(cell: [u8; 4], species: u32) -> Option<usize> {
+    (0..species as usize).find(|s| unorm(cell[*s]) > 0.5)
+}
+
+fn species_colour(s: Option<usize>) -> [u8; 4] {
+    let mut c = [0; 4];
+    if let Some(s) = s {
+        c[s] = 255;
+    }
+    c
+}
+
+//Same as newborn_species in totalistic.wgsl.
+fn newborn_species(counts: [i32; 4], species: u32) -> usize {
+    let species = species as usize;
+    let mut best = 0;
+    let mut present = 0;
+    let mut missing = 0;
+    for (i, c) in counts.iter().enumerate().take(species) {
+        if *c > counts[best] {
+            best = i;
+        }
+        if *c > 0 {
+            present += 1;
+        } else {
+            missing = i;
+        }
+    }
+    let ties = counts[..species]
+        .iter()
+        .filter(|c| **c == counts[best])
+        .count();
+    if ties > 1 && species == 4 && present == 3 {
+        return missing;
+    }
+    best
+}
+
+fn rule_born(rule: &Rule, n: i32) -> bool {
+    n > 0 && n <= 8 && rule.born[n as usize - 1] == 1
+}
+
+fn rule_stay_alive(rule: &Rule, n: i32) -> bool {
+    n > 0 && n <= 8 && rule.stay_alive[n as usize - 1] == 1
+}
+
+//One generation of totalistic.wgsl. rule_at gives the rule for a cell and species, see TotalisticParameters::cell_rule.
+pub fn totalistic_step(
+    input: &InputImageType,
+    species: u32,
+    rule_at: &dyn Fn(u32, u32, usize) -> Rule,
+    boundary: Boundary,
+    update: &UpdateParamsBuffer,
+) -> InputImageType {
+    let species = species.clamp(1, 4);
+    InputImageType::from_fn(input.width(), input.height(), |x, y| {
+        if !update.should_update(x, y) {
+            return *input.get_pixel(x, y);
+        }
+        let (xi, yi) = (x as i32, y as i32);
+        if species > 1 {
+            let mut counts = [0; 4];
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    if dx != 0 || dy != 0 {
+                        if let Some(s) =
+                            species_of(pixel_boundary(input, boundary, xi + dx, yi + dy), species)
+                        {
+                            counts[s] += 1;
+                        }
+                    }
+                }
+            }
+            let total = counts.iter().sum();
+            let cell = match species_of(input.get_pixel(x, y).0, species) {
+                Some(me) if rule_stay_alive(&rule_at(x, y, me), total) => species_colour(Some(me)),
+                Some(_) => species_colour(None),
+                None => {
+                    let child = newborn_species(counts, species);
+                    if rule_born(&rule_at(x, y, child), total) {
+                        species_colour(Some(child))
+                    } else {
+                        species_colour(None)
+                    }
+                }
+            };
+            return image::Rgba(cell);
+        }
+
+        //Summed in the same order as the shader, so float rounding matches too.
+        let r = |dx: i32, dy: i32| unorm(pixel_boundary(input, boundary, xi + dx, yi + dy)[0]);
+        let sum =
+            r(-1, 0) + r(1, 0) + r(0, 1) + r(0, -1) + r(-1, 1) + r(1, 1) + r(-1, -1) + r(1, -1);
+        let me = r(0, 0);
+        let rule = rule_at(x, y, 0);
+        let born = (0..8).any(|k| rule.born[k] == 1 && close(sum, k as i32 + 1));
+        let stay_alive = (0..8).any(|k| rule.stay_alive[k] == 1 && close(sum, k as i32 + 1));
+        let alive = (close(me, 0) && born) || (close(me, 1) && stay_alive);
+        let v = if alive { 255 } else { 0 };
+        image::Rgba([v, v, v, 255])
+    })
+}
+
+//One generation of neural.wgsl with the default activation and update snippets.
+pub fn neural_step(
+    input: &InputImageType,
+    filter: &NeuralFilter,
+    memory: &[MemoryChannel],
+    boundary: Boundary,
+    update: &UpdateParamsBuffer,
+) -> InputImageType {
+    let bias = if filter.use_bias {
+        filter.bias
+    } else {
+        [0.0; MAX_CHANNELS]
+    };
+    let kernel_size = filter.get_kernel_size() as i32;
+    let radius = kernel_size / 2;
+    let taps = (kernel_size * kernel_size) as usize;
+    let weights = filter.get_weights();
+    let count = (filter.channels as usize).min(MAX_CHANNELS);
+    let fire_seed = pcg_hash(update.step_seed() ^ 0x5bd1e995);
+
+    InputImageType::from_fn(input.width(), input.height(), |x, y| {
+        let fired = random_unit(x, y, fire_seed) < filter.fire_rate;
+        if !update.should_update(x, y) || !fired {
+            return *input.get_pixel(x, y);
+        }
+        let (xi, yi) = (x as i32, y as i32);
+        let me = input.get_pixel(x, y).0.map(unorm);
+
+        let mut conv = [0.0f32; 4];
+        for row in 0..kernel_size {
+            for col in 0..kernel_size {
+                let tap = (row * kernel_size + col) as usize;
+                //The top row of the kernel is at y + radius.
+                let n = pixel_boundary(input, boundary, xi + col - radius, yi + radius - row)
+                    .map(unorm);
+                for (o, c) in conv.iter_mut().enumerate().take(count) {
+                    for (i, v) in n.iter().enumerate().take(count) {
+                        *c += weights[(o * MAX_CHANNELS + i) * taps + tap] * v;
+                    }
+                }
+            }
+        }
+        let mut next = [0.0, 0.0, 0.0, 1.0];
+        for o in 0..count {
+            conv[o] += bias[o];
+            let mut value = filter.activations[o].apply(conv[o]);
+            if filter.residual {
+                value = me[o] + filter.time_step * value;
+            }
+            next[o] = if filter.wrap {
+                value - value.floor()
+            } else {
+                value.clamp(0.0, 1.0)
+            };
+        }
+
+        //Colour channels the filter doesn't compute can remember when another channel fired.
+        for (m, channel) in memory.iter().enumerate().skip(count) {
+            if !channel.enabled {
+                continue;
+            }
+            let mut g = me[m];
+            if next[(channel.source as usize).min(3)] > channel.threshold && g < channel.rearm {
+                g = 1.0;
+            } else {
+                g *= channel.decay;
+            }
+            if g < channel.fade_below {
+                g *= channel.fade_decay;
+            }
+            next[m] = g;
+        }
+        image::Rgba(next.map(to_unorm))
+    })
+}
+
+//First cell where any channel differs by more than tolerance, in row order.
+pub fn first_difference(
+    a: &InputImageType,
+    b: &InputImageType,
+    tolerance: u8,
+) -> Option<(u32, u32, [u8; 4], [u8; 4])> {
+    a.enumerate_pixels()
+        .zip(b.pixels())
+        .find(|((_, _, pa), pb)| {
+            pa.0.iter()
+                .zip(pb.0)
+                .any(|(u, v)| u.abs_diff(v) > tolerance)
+        })
+        .map(|((x, y, pa), pb)| (x, y, pa.0, pb.0))
+}
+
+//The default simulation, the game of life on gol1.png, run entirely on the cpu. For machines without a usable GPU.
+pub fn run_default(generations: usize) -> InputImageType {
+    let life = Rule::from_rule_str("B3/S23").unwrap();
+    let synchronous = UpdateParamsBuffer::zeroed();
+    let mut image = image::load_from_memory(include_bytes!("gol1.png"))
+        .unwrap()
+        .to_rgba8();
+    for _ in 0..generations {
+        image = totalistic_step(&image, 1, &|_, _, _| life, Boundary::Torus, &synchronous);
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::neural_parameters::Activation;
+    use super::*;
+
+    //A grid with the listed cells alive, coloured the way totalistic_step writes them.
+    fn grid(width: u32, height: u32, alive: &[(u32, u32)]) -> InputImageType {
+        InputImageType::from_fn(width, height, |x, y| {
+            let v = if alive.contains(&(x, y)) { 255 } else { 0 };
+            image::Rgba([v, v, v, 255])
+        })
+    }
+
+    fn life_steps(image: &InputImageType, generations: usize) -> InputImageType {
+        let life = Rule::from_rule_str("B3/S23").unwrap();
+        let synchronous = UpdateParamsBuffer::zeroed();
+        (0..generations).fold(image.clone(), |image, _| {
+            totalistic_step(&image, 1, &|_, _, _| life, Boundary::Torus, &synchronous)
+        })
+    }
+
+    #[test]
+    fn blinker_has_period_2() {
+        let horizontal = grid(5, 5, &[(1, 2), (2, 2), (3, 2)]);
+        let vertical = grid(5, 5, &[(2, 1), (2, 2), (2, 3)]);
+        assert_eq!(life_steps(&horizontal, 1), vertical);
+        assert_eq!(life_steps(&horizontal, 2), horizontal);
+    }
+
+    #[test]
+    fn glider_moves_diagonally() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let moved: Vec<_> = glider.iter().map(|(x, y)| (x + 1, y + 1)).collect();
+        assert_eq!(life_steps(&grid(8, 8, &glider), 4), grid(8, 8, &moved));
+    }
+
+    #[test]
+    fn glider_wraps_around_torus() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        //32 generations move it 8 cells, once round the grid.
+        assert_eq!(life_steps(&grid(8, 8, &glider), 32), grid(8, 8, &glider));
+    }
+
+    //A one channel grid with the listed cells' red set to 255, the rest of every pixel as given.
+    fn red_grid(width: u32, height: u32, alive: &[(u32, u32)], rest: [u8; 3]) -> InputImageType {
+        InputImageType::from_fn(width, height, |x, y| {
+            let v = if alive.contains(&(x, y)) { 255 } else { 0 };
+            image::Rgba([v, rest[0], rest[1], rest[2]])
+        })
+    }
+
+    fn identity_filter(kernel: &[f32; 9]) -> NeuralFilter {
+        let mut filter = NeuralFilter::from_slice(kernel);
+        filter.activations[0] = Activation::Identity;
+        filter
+    }
+
+    const CENTRE: [f32; 9] = [0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+    //Only the middle row's left tap, so every cell copies its left neighbour.
+    const LEFT: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+
+    #[test]
+    fn identity_kernel_applies_default_activation() {
+        let filter = NeuralFilter::from_slice(&CENTRE);
+        let synchronous = UpdateParamsBuffer::zeroed();
+        let next = neural_step(
+            &red_grid(5, 5, &[(2, 2)], [0, 0, 255]),
+            &filter,
+            &[],
+            Boundary::Torus,
+            &synchronous,
+        );
+        let live = to_unorm(Activation::default().apply(1.0));
+        let dead = to_unorm(Activation::default().apply(0.0));
+        assert_eq!(live, 87);
+        assert_eq!(dead, 0);
+        for (x, y, pixel) in next.enumerate_pixels() {
+            let red = if (x, y) == (2, 2) { live } else { dead };
+            assert_eq!(pixel.0, [red, 0, 0, 255], "cell ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn memory_channel_triggers_decays_and_rearms() {
+        let filter = identity_filter(&CENTRE);
+        let trail = MemoryChannel {
+            decay: 0.5,
+            ..MemoryChannel::trail()
+        };
+        let memory = [MemoryChannel::default(), trail, MemoryChannel::default()];
+        let synchronous = UpdateParamsBuffer::zeroed();
+        let step = |image: &InputImageType| {
+            neural_step(image, &filter, &memory, Boundary::Torus, &synchronous)
+        };
+        let green = |image: &InputImageType| image.get_pixel(0, 0).0[1];
+
+        //Red stays alive: it fires, then has to fade below rearm before firing again.
+        let mut image = red_grid(1, 1, &[(0, 0)], [0, 0, 255]);
+        let mut trace = vec![];
+        for _ in 0..4 {
+            image = step(&image);
+            trace.push(green(&image));
+        }
+        assert_eq!(trace, [255, 128, 64, 255]);
+
+        //Red dies after firing: the trail halves, then fades faster below fade_below.
+        let mut image = red_grid(1, 1, &[], [255, 0, 255]);
+        let mut trace = vec![];
+        for _ in 0..3 {
+            image = step(&image);
+            trace.push(green(&image));
+        }
+        assert_eq!(trace, [128, 64, 16]);
+    }
+
+    #[test]
+    fn edge_neighbours_follow_boundary() {
+        let filter = identity_filter(&LEFT);
+        let synchronous = UpdateParamsBuffer::zeroed();
+        let edge = red_grid(5, 5, &[(4, 2)], [0, 0, 255]);
+        let step = |boundary| neural_step(&edge, &filter, &[], boundary, &synchronous);
+
+        assert_eq!(
+            step(Boundary::Torus),
+            red_grid(5, 5, &[(0, 2)], [0, 0, 255])
+        );
+        assert_eq!(step(Boundary::Dead), red_grid(5, 5, &[], [0, 0, 255]));
+        let column: Vec<_> = (0..5).map(|y| (0, y)).collect();
+        assert_eq!(step(Boundary::Alive), red_grid(5, 5, &column, [0, 0, 255]));
+    }
+
+    #[test]
+    fn newborn_takes_majority_species() {
+        assert_eq!(newborn_species([1, 2, 0, 0], 2), 1);
+        assert_eq!(newborn_species([0, 0, 3, 0], 4), 2);
+        assert_eq!(newborn_species([1, 0, 2, 0], 3), 2);
+    }
+
+    #[test]
+    fn newborn_ties_go_to_lowest_species() {
+        assert_eq!(newborn_species([2, 2, 0, 0], 2), 0);
+        assert_eq!(newborn_species([0, 1, 1, 1], 3), 1);
+        assert_eq!(newborn_species([1, 1, 1, 0], 3), 0);
+        assert_eq!(newborn_species([0, 2, 2, 0], 4), 1);
+    }
+
+    #[test]
+    fn newborn_three_way_tie_goes_to_missing_species() {
+        assert_eq!(newborn_species([1, 1, 1, 0], 4), 3);
+        assert_eq!(newborn_species([1, 1, 0, 1], 4), 2);
+        assert_eq!(newborn_species([0, 1, 1, 1], 4), 0);
+    }
+
+    #[test]
+    fn newborn_ignores_counts_beyond_species() {
+        assert_eq!(newborn_species([1, 0, 5, 0], 2), 0);
+    }
+}
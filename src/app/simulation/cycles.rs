@@ -0,0 +1,473 @@
+/* Spots simulations that died out, stopped changing or fell into a cycle.
+Every generation of a batch gets a 64 bit hash of its cells on the GPU. The hashes are read back without waiting and
+looked up in a table of recent ones, so a repeated state is noticed a frame or two after it happens.
+With random update schemes or a neural fire rate below 1 a repeated state doesn't guarantee a cycle, it's still reported.
+*/
+
+use std::collections::{HashMap, VecDeque};
+
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    compute_textures::ComputeTextures,
+    statistics::{create_reduction_pipelines, storage_entry, StatsParamsBuffer},
+};
+use crate::app::{
+    gpu::{bindgroup::CachedBuffer, pipeline, readback::ReadbackRing, Gpu},
+    time::MAX_BATCH_SIZE,
+};
+
+const READBACK_BUFFERS: usize = 4;
+pub const DEFAULT_WINDOW: usize = 1024;
+
+//Same layout as HashPartial in statistics.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct HashBuffer {
+    a: u32,
+    b: u32,
+    live: u32,
+    _padding: u32,
+}
+
+//Implemented by hand like PartialBuffer in statistics.rs.
+const _: () = assert!(std::mem::size_of::<HashBuffer>() == 16);
+unsafe impl Zeroable for HashBuffer {}
+unsafe impl Pod for HashBuffer {}
+
+const HASH_SIZE: u64 = std::mem::size_of::<HashBuffer>() as u64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CycleOutcome {
+    //No live cells are left.
+    Died { generation: usize },
+    //The cells stopped changing.
+    Still { since: usize },
+    //The state repeats every period generations, starting at since.
+    Periodic { period: usize, since: usize },
+}
+
+impl std::fmt::Display for CycleOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CycleOutcome::Died { generation } => write!(f, "Died out at generation {}", generation),
+            CycleOutcome::Still { since } => write!(f, "Still since generation {}", since),
+            CycleOutcome::Periodic { period, since } => {
+                write!(f, "Period {} cycle since generation {}", period, since)
+            }
+        }
+    }
+}
+
+//What happens once an outcome is found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CycleAction {
+    #[default]
+    Report,
+    Pause,
+    //Starts again from a new random soup, for soup searches.
+    Restart,
+}
+
+pub struct CycleDetector {
+    pub enabled: bool,
+    pub action: CycleAction,
+    //How many generations back a repeat is looked for. Longer periods go unnoticed.
+    pub window: usize,
+    //Found since the last reset. Detection stops once there is one.
+    pub outcome: Option<CycleOutcome>,
+    //Soups restarted by CycleAction::Restart, and what ended the last one.
+    pub restarts: usize,
+    pub last_restart: Option<CycleOutcome>,
+    cells_pipeline: wgpu::ComputePipeline,
+    partials_pipeline: wgpu::ComputePipeline,
+    params: CachedBuffer,
+    partials: Option<(wgpu::Buffer, u32)>,
+    result: wgpu::Buffer,
+    //Hashes of every generation of the batch being recorded, copied back in one go.
+    batch_hashes: wgpu::Buffer,
+    //Bind groups for both ping-pong directions, and the texture version and partial count they were made for.
+    cells_bind_groups: Option<(u64, u32, [wgpu::BindGroup; 2])>,
+    partials_bind_group: Option<wgpu::BindGroup>,
+    //Tagged with the first generation of the batch, the texture version and the epoch.
+    readbacks: ReadbackRing<(usize, u64, u64)>,
+    //Bumped by reset, so batches still in flight from before it are ignored when they come back.
+    epoch: u64,
+    history: HashHistory,
+}
+
+//Hashes of recent generations, looked up to find the first repeat.
+#[derive(Default)]
+struct HashHistory {
+    //Most recent generation each hash was seen at, and the hashes in the order they came in.
+    seen: HashMap<u64, usize>,
+    order: VecDeque<(usize, u64)>,
+}
+
+impl HashHistory {
+    fn clear(&mut self) {
+        self.seen.clear();
+        self.order.clear();
+    }
+
+    //Adds the hash of a generation, keeping the last window of them. Returns how the cells ended if they died or repeat.
+    fn check(
+        &mut self,
+        window: usize,
+        generation: usize,
+        hash: u64,
+        live: u32,
+    ) -> Option<CycleOutcome> {
+        //Going back means the simulation was rewound, and the recorded future no longer happened.
+        if self.order.back().is_some_and(|(g, _)| *g >= generation) {
+            self.clear();
+        }
+        if live == 0 {
+            return Some(CycleOutcome::Died { generation });
+        }
+        if let Some(since) = self.seen.get(&hash) {
+            let period = generation - since;
+            return Some(if period == 1 {
+                CycleOutcome::Still { since: *since }
+            } else {
+                CycleOutcome::Periodic {
+                    period,
+                    since: *since,
+                }
+            });
+        }
+        self.seen.insert(hash, generation);
+        self.order.push_back((generation, hash));
+        while self.order.len() > window.max(1) {
+            let (old_generation, old_hash) = self.order.pop_front().unwrap();
+            if self.seen.get(&old_hash) == Some(&old_generation) {
+                self.seen.remove(&old_hash);
+            }
+        }
+        None
+    }
+}
+
+impl CycleDetector {
+    pub fn new(gpu: &Gpu) -> CycleDetector {
+        let (cells_pipeline, partials_pipeline) = create_reduction_pipelines(
+            gpu,
+            (
+                "hash_cells",
+                &[
+                    pipeline::unfilterable_texture_entry(0),
+                    pipeline::uniform_entry(3),
+                    storage_entry(5),
+                ],
+            ),
+            (
+                "reduce_hashes",
+                &[
+                    pipeline::uniform_entry(3),
+                    storage_entry(5),
+                    storage_entry(6),
+                ],
+            ),
+        );
+        let batch_size = HASH_SIZE * MAX_BATCH_SIZE as u64;
+        CycleDetector {
+            enabled: false,
+            action: CycleAction::default(),
+            window: DEFAULT_WINDOW,
+            outcome: None,
+            restarts: 0,
+            last_restart: None,
+            cells_pipeline,
+            partials_pipeline,
+            params: CachedBuffer::new(
+                gpu,
+                "Hash Params Buffer",
+                wgpu::BufferUsages::UNIFORM,
+                bytemuck::bytes_of(&StatsParamsBuffer::zeroed()),
+            ),
+            partials: None,
+            result: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Hash Result Buffer"),
+                size: HASH_SIZE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            batch_hashes: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Batch Hash Buffer"),
+                size: batch_size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            cells_bind_groups: None,
+            partials_bind_group: None,
+            readbacks: ReadbackRing::new(gpu, "Hash Readback Buffer", batch_size, READBACK_BUFFERS),
+            epoch: 0,
+            history: HashHistory::default(),
+        }
+    }
+
+    //Forgets every hash and outcome. For when the simulation starts over.
+    pub fn reset(&mut self) {
+        self.epoch += 1;
+        self.outcome = None;
+        self.history.clear();
+    }
+
+    //Gets ready to hash a batch. Returns false if the batch shouldn't be hashed, in which case record_hash does nothing.
+    pub fn prepare(
+        &mut self,
+        gpu: &Gpu,
+        textures: &ComputeTextures,
+        texture_version: u64,
+        size: (u32, u32),
+        channels: u32,
+    ) -> bool {
+        if !self.enabled || self.outcome.is_some() || self.readbacks.is_full() {
+            return false;
+        }
+        let params = StatsParamsBuffer::new(channels, size);
+        let partial_count = params.partial_count();
+        self.params.write(gpu, bytemuck::bytes_of(&params));
+        let stale = !matches!(
+            &self.cells_bind_groups,
+            Some((version, count, _)) if *version == texture_version && *count == partial_count
+        );
+        if stale {
+            self.create_bind_groups(gpu, textures, texture_version, partial_count);
+        }
+        true
+    }
+
+    //Hashes the generation just written by step index of the batch.
+    pub fn record_hash(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        size: (u32, u32),
+        generation: usize,
+        index: usize,
+    ) {
+        let (cells_bind_groups, partials_bind_group) =
+            match (&self.cells_bind_groups, &self.partials_bind_group) {
+                (Some((_, _, cells)), Some(partials)) => (cells, partials),
+                _ => return,
+            };
+        let workgroups = StatsParamsBuffer::workgroups(size);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("State hash"),
+            });
+            compute_pass.set_pipeline(&self.cells_pipeline);
+            compute_pass.set_bind_group(0, &cells_bind_groups[generation % 2], &[]);
+            compute_pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+            compute_pass.set_pipeline(&self.partials_pipeline);
+            compute_pass.set_bind_group(0, partials_bind_group, &[]);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(
+            &self.result,
+            0,
+            &self.batch_hashes,
+            index as u64 * HASH_SIZE,
+            HASH_SIZE,
+        );
+    }
+
+    //Copies back the hashes of a batch of n generations, the first being first_generation.
+    pub fn finish_batch(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        first_generation: usize,
+        n: usize,
+        texture_version: u64,
+    ) {
+        self.readbacks.record(
+            encoder,
+            &self.batch_hashes,
+            n as u64 * HASH_SIZE,
+            (first_generation, texture_version, self.epoch),
+        );
+    }
+
+    //Starts reading back the last batch. Call after submitting its encoder.
+    pub fn submitted(&mut self) {
+        self.readbacks.submitted();
+    }
+
+    //Checks the hashes the GPU has finished since the last poll. Returns an outcome the first time one is found.
+    pub fn poll(&mut self, gpu: &Gpu, texture_version: u64) -> Option<CycleOutcome> {
+        let mut batches = self.readbacks.poll(gpu);
+        batches
+            .retain(|((_, version, epoch), _)| *version == texture_version && *epoch == self.epoch);
+        batches.sort_by_key(|((first, _, _), _)| *first);
+        for ((first, _, _), data) in batches {
+            for (i, chunk) in data.chunks_exact(HASH_SIZE as usize).enumerate() {
+                if self.outcome.is_some() {
+                    return None;
+                }
+                let hash: HashBuffer = bytemuck::pod_read_unaligned(chunk);
+                self.outcome = self.history.check(
+                    self.window,
+                    first + i,
+                    (hash.a as u64) << 32 | hash.b as u64,
+                    hash.live,
+                );
+                if self.outcome.is_some() {
+                    return self.outcome;
+                }
+            }
+        }
+        None
+    }
+
+    fn create_bind_groups(
+        &mut self,
+        gpu: &Gpu,
+        textures: &ComputeTextures,
+        texture_version: u64,
+        partial_count: u32,
+    ) {
+        if self
+            .partials
+            .as_ref()
+            .is_none_or(|(_, capacity)| *capacity < partial_count)
+        {
+            self.partials = Some((
+                gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Hash Partials Buffer"),
+                    size: partial_count as u64 * HASH_SIZE,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                }),
+                partial_count,
+            ));
+        }
+        let partials = &self.partials.as_ref().unwrap().0;
+        let cells_layout = self.cells_pipeline.get_bind_group_layout(0);
+        let cells_bind_group = |frame: usize| {
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Hash cells bind group"),
+                layout: &cells_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(
+                            &textures
+                                .frame_texture(frame)
+                                .create_view(&wgpu::TextureViewDescriptor::default()),
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: self.params.buffer().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: partials.as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        self.cells_bind_groups = Some((
+            texture_version,
+            partial_count,
+            [cells_bind_group(0), cells_bind_group(1)],
+        ));
+        self.partials_bind_group = Some(gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hash partials bind group"),
+            layout: &self.partials_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.params.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: partials.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: self.result.as_entire_binding(),
+                },
+            ],
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Feeds hashes of consecutive generations from 0, stopping at the first outcome.
+    fn run(window: usize, hashes: &[(u64, u32)]) -> Option<CycleOutcome> {
+        let mut history = HashHistory::default();
+        hashes
+            .iter()
+            .enumerate()
+            .find_map(|(generation, (hash, live))| history.check(window, generation, *hash, *live))
+    }
+
+    #[test]
+    fn still_life() {
+        let hashes = [(1, 5), (2, 4), (3, 4), (3, 4)];
+        assert_eq!(run(16, &hashes), Some(CycleOutcome::Still { since: 2 }));
+    }
+
+    #[test]
+    fn oscillator() {
+        let hashes = [(1, 9), (2, 3), (3, 3), (2, 3), (3, 3)];
+        assert_eq!(
+            run(16, &hashes),
+            Some(CycleOutcome::Periodic {
+                period: 2,
+                since: 1
+            })
+        );
+        let hashes = [(1, 9), (2, 3), (3, 3), (4, 3), (2, 3)];
+        assert_eq!(
+            run(16, &hashes),
+            Some(CycleOutcome::Periodic {
+                period: 3,
+                since: 1
+            })
+        );
+    }
+
+    #[test]
+    fn extinction() {
+        let hashes = [(1, 9), (2, 3), (0, 0), (0, 0)];
+        assert_eq!(run(16, &hashes), Some(CycleOutcome::Died { generation: 2 }));
+    }
+
+    #[test]
+    fn changing_state_has_no_outcome() {
+        let hashes: Vec<_> = (0..100).map(|h| (h, 1)).collect();
+        assert_eq!(run(16, &hashes), None);
+    }
+
+    #[test]
+    fn periods_longer_than_window_are_missed() {
+        let hashes = [(1, 1), (2, 1), (3, 1), (4, 1), (1, 1), (2, 1)];
+        assert_eq!(run(3, &hashes), None);
+        assert_eq!(
+            run(4, &hashes),
+            Some(CycleOutcome::Periodic {
+                period: 4,
+                since: 0
+            })
+        );
+    }
+
+    #[test]
+    fn rewinding_forgets_later_generations() {
+        let mut history = HashHistory::default();
+        assert_eq!(history.check(16, 0, 1, 1), None);
+        assert_eq!(history.check(16, 1, 2, 1), None);
+        assert_eq!(history.check(16, 2, 3, 1), None);
+        //Back at generation 1, the hashes of 1 and 2 no longer count.
+        assert_eq!(history.check(16, 1, 3, 1), None);
+        assert_eq!(
+            history.check(16, 2, 3, 1),
+            Some(CycleOutcome::Still { since: 1 })
+        );
+    }
+}
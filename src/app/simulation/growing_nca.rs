@@ -0,0 +1,265 @@
+use std::rc::Rc;
+
+use crate::app::{
+    gpu::{pipeline, Gpu},
+    math::UVec2,
+};
+
+use super::{
+    nca_parameters::{NcaParameters, NCA_CHANNELS},
+    update_parameters::{UpdateParameters, UpdateScheme},
+};
+
+//The 16 channel state is split over 4 rgba32float textures.
+const STATE_TEXTURES: usize = NCA_CHANNELS / 4;
+
+//State, plus bind groups of the alive, update and mask passes. See nca.wgsl for the passes.
+//The updated state before life masking and the pre update life mask are only reached through the bind groups.
+pub struct NcaTextures {
+    state: [wgpu::Texture; STATE_TEXTURES],
+    bind_groups: [wgpu::BindGroup; 3],
+    size: UVec2,
+}
+
+impl NcaTextures {
+    //Starts from a single living seed cell in the middle, with every channel except RGB set to 1.
+    //layouts are the group 0 layouts of the alive, update and mask pipelines.
+    fn new(gpu: &Gpu, layouts: &[wgpu::BindGroupLayout; 3], size: UVec2) -> NcaTextures {
+        let create = |label: &str, format| {
+            gpu.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::STORAGE_BINDING,
+            })
+        };
+        let rgba = wgpu::TextureFormat::Rgba32Float;
+        let s = [0, 1, 2, 3].map(|_| create("NCA state texture", rgba));
+        let u = [0, 1, 2, 3].map(|_| create("NCA updated texture", rgba));
+        let alive = create("NCA alive texture", wgpu::TextureFormat::R32Float);
+        let bind_groups = [
+            NcaTextures::create_bind_group(gpu, &layouts[0], &[(0, &s[0]), (8, &alive)]),
+            NcaTextures::create_bind_group(
+                gpu,
+                &layouts[1],
+                &[
+                    (0, &s[0]),
+                    (1, &s[1]),
+                    (2, &s[2]),
+                    (3, &s[3]),
+                    (4, &u[0]),
+                    (5, &u[1]),
+                    (6, &u[2]),
+                    (7, &u[3]),
+                ],
+            ),
+            NcaTextures::create_bind_group(
+                gpu,
+                &layouts[2],
+                &[
+                    (9, &u[0]),
+                    (10, &u[1]),
+                    (11, &u[2]),
+                    (12, &u[3]),
+                    (13, &alive),
+                    (14, &s[0]),
+                    (15, &s[1]),
+                    (16, &s[2]),
+                    (17, &s[3]),
+                ],
+            ),
+        ];
+        let textures = NcaTextures {
+            state: s,
+            bind_groups,
+            size,
+        };
+        textures.seed(gpu);
+        textures
+    }
+
+    pub fn seed(&self, gpu: &Gpu) {
+        let pixels = (self.size.x * self.size.y) as usize;
+        let centre = (self.size.y / 2 * self.size.x + self.size.x / 2) as usize;
+        for (t, tex) in self.state.iter().enumerate() {
+            let mut data = vec![0.0f32; pixels * 4];
+            for c in 0..4 {
+                if t * 4 + c >= 3 {
+                    data[centre * 4 + c] = 1.0;
+                }
+            }
+            gpu.queue.write_texture(
+                tex.as_image_copy(),
+                bytemuck::cast_slice(&data),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(16 * self.size.x),
+                    rows_per_image: std::num::NonZeroU32::new(self.size.y),
+                },
+                wgpu::Extent3d {
+                    width: self.size.x,
+                    height: self.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    //Channels 0 to 3, which hold the colour and alpha.
+    pub fn get_rgba_texture(&self) -> &wgpu::Texture {
+        &self.state[0]
+    }
+
+    fn create_bind_group(
+        gpu: &Gpu,
+        layout: &wgpu::BindGroupLayout,
+        entries: &[(u32, &wgpu::Texture)],
+    ) -> wgpu::BindGroup {
+        let views: Vec<(u32, wgpu::TextureView)> = entries
+            .iter()
+            .map(|(b, t)| (*b, t.create_view(&wgpu::TextureViewDescriptor::default())))
+            .collect();
+        let entries: Vec<wgpu::BindGroupEntry> = views
+            .iter()
+            .map(|(b, v)| wgpu::BindGroupEntry {
+                binding: *b,
+                resource: wgpu::BindingResource::TextureView(v),
+            })
+            .collect();
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("NCA textures bind group"),
+            layout,
+            entries: &entries,
+        })
+    }
+}
+
+pub struct GrowingNcaState {
+    alive_pipeline: wgpu::ComputePipeline,
+    update_pipeline: wgpu::ComputePipeline,
+    mask_pipeline: wgpu::ComputePipeline,
+    //Group 0 layouts of the three passes. The textures are float32, so these are explicit rather than derived from the shader.
+    texture_layouts: [wgpu::BindGroupLayout; 3],
+    pub params: NcaParameters,
+    //Stochastic cell updates. Defaults to the fire rate of 0.5 used in training.
+    pub update: UpdateParameters,
+}
+
+impl GrowingNcaState {
+    pub fn new(gpu: &Gpu) -> GrowingNcaState {
+        let rgba = wgpu::TextureFormat::Rgba32Float;
+        let d2 = wgpu::TextureViewDimension::D2;
+        let alive_layout = pipeline::create_layout(
+            gpu,
+            "NCA alive textures layout",
+            &[
+                pipeline::unfilterable_texture_entry(0),
+                pipeline::storage_texture_entry(8, wgpu::TextureFormat::R32Float, d2),
+            ],
+        );
+        let update_entries: Vec<_> = (0..4)
+            .map(pipeline::unfilterable_texture_entry)
+            .chain((4..8).map(|b| pipeline::storage_texture_entry(b, rgba, d2)))
+            .collect();
+        let update_layout =
+            pipeline::create_layout(gpu, "NCA update textures layout", &update_entries);
+        let mask_entries: Vec<_> = (9..14)
+            .map(pipeline::unfilterable_texture_entry)
+            .chain((14..18).map(|b| pipeline::storage_texture_entry(b, rgba, d2)))
+            .collect();
+        let mask_layout = pipeline::create_layout(gpu, "NCA mask textures layout", &mask_entries);
+        let params_layout = pipeline::create_layout(
+            gpu,
+            "NCA params layout",
+            &[
+                pipeline::buffer_entry(0, wgpu::BufferBindingType::Storage { read_only: true }),
+                pipeline::uniform_entry(1),
+            ],
+        );
+        let update_params_layout = pipeline::create_layout(
+            gpu,
+            "NCA update params layout",
+            &[pipeline::uniform_entry(0)],
+        );
+
+        let shader = pipeline::create_shader(gpu, "nca.wgsl", "NCA");
+        let create = |entry_point: &str, layouts: &[&wgpu::BindGroupLayout]| {
+            pipeline::create_compute_pipeline(
+                gpu,
+                &shader,
+                format!("NCA {}", entry_point).as_str(),
+                entry_point,
+                layouts,
+            )
+        };
+        let alive_pipeline = create("alive", &[&alive_layout]);
+        let update_pipeline = create(
+            "update",
+            &[&update_layout, &params_layout, &update_params_layout],
+        );
+        let mask_pipeline = create("mask", &[&mask_layout]);
+        let mut update = UpdateParameters::new(Rc::new(update_params_layout));
+        update.scheme = UpdateScheme::RandomSequential { alpha: 0.5 };
+        GrowingNcaState {
+            alive_pipeline,
+            update_pipeline,
+            mask_pipeline,
+            texture_layouts: [alive_layout, update_layout, mask_layout],
+            params: NcaParameters::new(gpu, params_layout),
+            update,
+        }
+    }
+
+    pub fn create_textures(&self, gpu: &Gpu, size: UVec2) -> NcaTextures {
+        NcaTextures::new(gpu, &self.texture_layouts, size)
+    }
+
+    //Records n generations starting at first_generation into a single submission.
+    pub fn steps(
+        &mut self,
+        gpu: &Gpu,
+        textures: &NcaTextures,
+        first_generation: usize,
+        n: usize,
+        (x, y): (u32, u32),
+    ) {
+        self.params.prepare(gpu);
+        self.update.write_batch(gpu, first_generation, n);
+        let [alive_bind_group, update_bind_group, mask_bind_group] = &textures.bind_groups;
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("NCA step"),
+            });
+            for update_params_bind_group in &self.update.batch_bind_groups()[..n] {
+                compute_pass.set_pipeline(&self.alive_pipeline);
+                compute_pass.set_bind_group(0, alive_bind_group, &[]);
+                compute_pass.dispatch_workgroups(x, y, 1);
+
+                compute_pass.set_pipeline(&self.update_pipeline);
+                compute_pass.set_bind_group(0, update_bind_group, &[]);
+                compute_pass.set_bind_group(1, self.params.bind_group(), &[]);
+                compute_pass.set_bind_group(2, update_params_bind_group, &[]);
+                compute_pass.dispatch_workgroups(x, y, 1);
+
+                compute_pass.set_pipeline(&self.mask_pipeline);
+                compute_pass.set_bind_group(0, mask_bind_group, &[]);
+                compute_pass.dispatch_workgroups(x, y, 1);
+            }
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+    }
+}
@@ -0,0 +1,143 @@
+/* Recent generations kept on the GPU so the simulation can be rewound.
+Snapshots are copies of the simulation texture recorded in the same submission as the steps, so keeping them needs no readback.
+How many are kept follows from a memory budget, so small grids keep a long history and huge ones a short one.
+*/
+
+use std::collections::VecDeque;
+
+use super::compute_textures::ComputeTextures;
+use crate::app::gpu::Gpu;
+
+pub const DEFAULT_BUDGET_MB: u32 = 256;
+
+struct Snapshot {
+    generation: usize,
+    texture: wgpu::Texture,
+}
+
+pub struct History {
+    pub enabled: bool,
+    //Memory the snapshots may use, in megabytes.
+    pub budget_mb: u32,
+    //Consecutive generations, oldest first.
+    snapshots: VecDeque<Snapshot>,
+    //Snapshots from this generation on were reserved for the batch being recorded and still need their copy.
+    pending_from: usize,
+    //Textures of dropped snapshots, reused so recording doesn't allocate every generation.
+    spare: Vec<wgpu::Texture>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            enabled: true,
+            budget_mb: DEFAULT_BUDGET_MB,
+            snapshots: VecDeque::new(),
+            pending_from: 0,
+            spare: Vec::new(),
+        }
+    }
+
+    //Number of generations that fit in the budget for simulations the size of textures.
+    pub fn capacity(&self, textures: &ComputeTextures) -> usize {
+        if !self.enabled {
+            return 0;
+        }
+        let budget = self.budget_mb as u64 * 1024 * 1024;
+        (budget / textures.frame_bytes().max(1)) as usize
+    }
+
+    //Forgets every generation. The snapshots no longer match once the textures are remade.
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.spare.clear();
+    }
+
+    //Oldest and newest generation that can be rewound to.
+    pub fn range(&self) -> Option<(usize, usize)> {
+        Some((
+            self.snapshots.front()?.generation,
+            self.snapshots.back()?.generation,
+        ))
+    }
+
+    fn get(&self, generation: usize) -> Option<&Snapshot> {
+        let first = self.snapshots.front()?.generation;
+        self.snapshots.get(generation.checked_sub(first)?)
+    }
+
+    //Makes room for the generations a batch of n steps from first will produce, plus first itself if it isn't kept yet.
+    //Anything after first is a future that was rewound past, and is dropped. Call save for each generation while recording.
+    pub fn reserve(&mut self, gpu: &Gpu, textures: &ComputeTextures, first: usize, n: usize) {
+        let capacity = self.capacity(textures);
+        while self.snapshots.back().is_some_and(|s| s.generation > first) {
+            let dropped = self.snapshots.pop_back().unwrap();
+            self.spare.push(dropped.texture);
+        }
+        if capacity == 0 {
+            self.clear();
+            return;
+        }
+        let start = match self.snapshots.back() {
+            Some(last) if last.generation == first => first + 1,
+            _ => first,
+        };
+        let start = start.max((first + n + 1).saturating_sub(capacity));
+        if self
+            .snapshots
+            .back()
+            .is_some_and(|last| last.generation + 1 != start)
+        {
+            //A batch longer than the history leaves a gap, and snapshots have to stay consecutive.
+            self.spare
+                .extend(self.snapshots.drain(..).map(|s| s.texture));
+        }
+        for generation in start..=first + n {
+            while self.snapshots.len() >= capacity {
+                let dropped = self.snapshots.pop_front().unwrap();
+                self.spare.push(dropped.texture);
+            }
+            let texture = match self.spare.pop() {
+                Some(texture) => texture,
+                None => textures.create_snapshot_texture(gpu),
+            };
+            self.snapshots.push_back(Snapshot {
+                generation,
+                texture,
+            });
+        }
+        self.spare.truncate(capacity - self.snapshots.len());
+        self.pending_from = start;
+    }
+
+    //Copies the cells of a generation reserved by reserve. Does nothing for generations that aren't kept.
+    pub fn save(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        textures: &ComputeTextures,
+        generation: usize,
+    ) {
+        if generation < self.pending_from {
+            return;
+        }
+        if let Some(snapshot) = self.get(generation) {
+            textures.save_frame(encoder, generation, &snapshot.texture);
+        }
+    }
+
+    //Copies a kept generation back into the simulation textures. Returns false if it isn't kept.
+    pub fn load(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        textures: &ComputeTextures,
+        generation: usize,
+    ) -> bool {
+        match self.get(generation) {
+            Some(snapshot) => {
+                textures.load_frame(encoder, generation, &snapshot.texture);
+                true
+            }
+            None => false,
+        }
+    }
+}
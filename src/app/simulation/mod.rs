@@ -4,37 +4,102 @@
 use std::rc::Rc;
 
 use self::{
-    compute_textures::ComputeTextures, neural_parameters::NeuralParameters,
+    boundary::Boundary,
+    compute_textures::ComputeTextures,
+    cpu_engine::Conformance,
+    cycles::{CycleAction, CycleDetector},
+    growing_nca::{GrowingNcaState, NcaTextures},
+    history::History,
+    neural_parameters::{MemoryChannel, NeuralFilter, NeuralParameters, COLOUR_CHANNELS},
+    packed::{PackedCells, PackedState},
+    pde_parameters::PdeParameters,
+    statistics::{CellStatistics, Statistics},
     totalistic_parameters::TotalisticParameters,
+    update_parameters::UpdateParameters,
+    volume_parameters::VolumeParameters,
 };
 
 use super::{
-    gpu::{bindgroup::ToBindgroup, Gpu},
+    gpu::{pipeline, Gpu},
     gui::Gui,
     image_util::{self, ImageUtil},
     math::UVec2,
+    time::MAX_BATCH_SIZE,
     wgsl_preproc::WgslPreProcessor,
 };
 
+pub mod boundary;
 pub mod compute_textures;
+pub mod cpu_engine;
+pub mod cycles;
+pub mod growing_nca;
+pub mod history;
+pub mod nca_parameters;
 pub mod neural_parameters;
+pub mod neural_presets;
+pub mod neural_search;
+pub mod packed;
+pub mod pde_parameters;
+pub mod statistics;
 pub mod totalistic_parameters;
+pub mod update_parameters;
+pub mod volume_parameters;
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum SimulationType {
     Totalistic,
     Neural,
+    Wave,
+    Heat,
+    //Volumetric automata on a 3D grid.
+    Volume,
+    //Growing neural cellular automata with a pretrained model.
+    GrowingNca,
+}
+
+impl SimulationType {
+    //PDE simulations keep their state in float textures instead of Rgba8Unorm.
+    pub fn is_pde(&self) -> bool {
+        matches!(self, SimulationType::Wave | SimulationType::Heat)
+    }
 }
 
 pub struct NeuralState {
     pipeline: wgpu::ComputePipeline,
+    //Snippets the pipeline was compiled with. The cpu engine only knows the defaults.
+    activation_expr: String,
+    update_expr: String,
     pub params: NeuralParameters,
+    pub update: UpdateParameters,
 }
 
 pub struct TotalisticState {
     pipeline: wgpu::ComputePipeline,
     pub params: TotalisticParameters,
+    pub update: UpdateParameters,
+    //Bit packed storage for two state rules.
+    pub packed: PackedState,
+}
+
+//The wave and heat equation solvers. Both share the same parameters and state texture layout.
+pub struct PdeState {
+    wave_pipeline: wgpu::ComputePipeline,
+    heat_pipeline: wgpu::ComputePipeline,
+    //The state is Rgba32Float, which can't be filtered, so the layouts are explicit rather than derived from the shaders.
+    textures_layout: Rc<wgpu::BindGroupLayout>,
+    pub params: PdeParameters,
+    pub wave_update: UpdateParameters,
+    pub heat_update: UpdateParameters,
+    pub wall_mask: Option<image::GrayImage>,
 }
+
+pub struct VolumeState {
+    pipeline: wgpu::ComputePipeline,
+    textures_layout: Rc<wgpu::BindGroupLayout>,
+    pub params: VolumeParameters,
+    pub update: UpdateParameters,
+}
+
 pub struct SimulationState {
     pub paused: bool,
     //Frames per second
@@ -42,6 +107,8 @@ pub struct SimulationState {
     //Updates per second
     pub ups: u32,
     pub generations: usize,
+    //Latest population statistics, when they're enabled. A frame or two behind generations.
+    pub statistics: Option<CellStatistics>,
 }
 
 impl Default for SimulationState {
@@ -51,6 +118,7 @@ impl Default for SimulationState {
             fps: 0,
             ups: 0,
             generations: 0,
+            statistics: None,
         }
     }
 }
@@ -59,24 +127,66 @@ pub struct Simulation {
     simulation_type: SimulationType,
     pub neural_state: NeuralState,
     pub totalistic_state: TotalisticState,
+    pub pde_state: PdeState,
+    pub volume_state: VolumeState,
+    pub nca_state: GrowingNcaState,
     compute_textures: ComputeTextures,
+    //The 16 channel NCA state doesn't fit in ComputeTextures. Only set while running a growing NCA.
+    nca_textures: Option<NcaTextures>,
+    //Only set while running a packed two state simulation, in which case compute_textures is a 1x1 placeholder.
+    packed_cells: Option<PackedCells>,
+    //Recent generations that can be rewound to. Not kept for growing NCAs.
+    pub history: History,
+    pub statistics: Statistics,
+    pub cycles: CycleDetector,
+    //Set when the cycle detector paused the simulation, so the gui's pause state can follow.
+    pause_requested: bool,
+    //Generation the step controls are running to while paused. Reached a bounded batch per frame, see step_towards_target.
+    run_target: Option<usize>,
+    //Counts how many times the textures were remade, so renderers know when bind groups over them are stale.
+    texture_version: u64,
     current_frame: usize,
     sim_state: SimulationState,
     pub size: UVec2,
+    //Number of layers in the simulation. 1 for everything except volumetric automata.
+    pub depth: u32,
 }
 
+pub const DEFAULT_ACTIVATION_EXPR: &str = "activation(x, a)";
+pub const DEFAULT_UPDATE_EXPR: &str = "next";
+
 impl NeuralState {
     pub fn new(gpu: &Gpu) -> NeuralState {
-        let layout = NeuralState::create_pipeline(gpu);
+        let layout =
+            NeuralState::create_pipeline(gpu, DEFAULT_ACTIVATION_EXPR, DEFAULT_UPDATE_EXPR)
+                .unwrap();
         let params_bind_group_layout = Rc::new(layout.get_bind_group_layout(1));
+        let update_bind_group_layout = Rc::new(layout.get_bind_group_layout(2));
         NeuralState {
             pipeline: layout,
-            params: NeuralParameters::new(params_bind_group_layout),
+            activation_expr: DEFAULT_ACTIVATION_EXPR.to_owned(),
+            update_expr: DEFAULT_UPDATE_EXPR.to_owned(),
+            params: NeuralParameters::new(params_bind_group_layout, gpu),
+            update: UpdateParameters::new(update_bind_group_layout),
         }
     }
-    pub fn create_pipeline(gpu: &Gpu) -> wgpu::ComputePipeline {
+
+    //Builds the neural pipeline with user written WGSL for activation(x) and the cell update.
+    //Returns a readable error instead of panicking if the snippets don't compile.
+    pub fn create_pipeline(
+        gpu: &Gpu,
+        activation_expr: &str,
+        update_expr: &str,
+    ) -> Result<wgpu::ComputePipeline, String> {
         let shader_root = "./shaders";
-        let shader_src = WgslPreProcessor::load_and_process("neural.wgsl", shader_root).unwrap();
+        let shader_src = WgslPreProcessor::load_and_process("neural.wgsl", shader_root)
+            .map_err(|e| format!("{:?}", e))?;
+        let (shader_src, snippets) = WgslPreProcessor::insert_snippets(
+            shader_src.as_str(),
+            &[("activation", activation_expr), ("update", update_expr)],
+        )
+        .map_err(|e| format!("{:?}", e))?;
+        WgslPreProcessor::validate(shader_src.as_str(), &snippets)?;
         let shader = gpu
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -92,7 +202,84 @@ impl NeuralState {
                 module: &shader,
                 entry_point: "main",
             });
-        pipeline
+        Ok(pipeline)
+    }
+
+    //Runs a filter and its memory channels off screen on a small random soup, reading back the cells after each of
+    //the given step counts. Used for preset thumbnails and for scoring filters in the search.
+    pub fn run_headless(
+        gpu: &Gpu,
+        pipeline: &wgpu::ComputePipeline,
+        filter: &NeuralFilter,
+        memory: &[MemoryChannel; COLOUR_CHANNELS],
+        size: u32,
+        snapshots: &[usize],
+    ) -> Vec<image_util::ImageData> {
+        let input_image = if filter.channels > 1 {
+            ImageUtil::random_image_color(size, size)
+        } else {
+            ImageUtil::random_image_monochrome(size, size)
+        };
+        let mut textures =
+            ComputeTextures::new(Rc::new(pipeline.get_bind_group_layout(0)), input_image, gpu);
+        let mut params = NeuralParameters::new(Rc::new(pipeline.get_bind_group_layout(1)), gpu);
+        params.filter = filter.clone();
+        params.memory = *memory;
+        params.prepare(gpu);
+        let mut update = UpdateParameters::new(Rc::new(pipeline.get_bind_group_layout(2)));
+        let workgroups = size.div_ceil(16);
+
+        let mut frames = Vec::with_capacity(snapshots.len());
+        let mut step = 0;
+        for &snapshot in snapshots {
+            //The batch buffer is rewritten for every submission, so long gaps go in several.
+            while step < snapshot {
+                let batch = (snapshot - step).min(MAX_BATCH_SIZE as usize);
+                update.write_batch(gpu, step, batch);
+                let mut encoder = gpu
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                for update_bind_group in &update.batch_bind_groups()[..batch] {
+                    let mut compute_pass =
+                        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                            label: Some("Headless neural step"),
+                        });
+                    compute_pass.set_pipeline(pipeline);
+                    compute_pass.set_bind_group(0, textures.bind_group(step), &[]);
+                    compute_pass.set_bind_group(1, params.bind_group(), &[]);
+                    compute_pass.set_bind_group(2, update_bind_group, &[]);
+                    compute_pass.dispatch_workgroups(workgroups, workgroups, 1);
+                    step += 1;
+                }
+                gpu.queue.submit(Some(encoder.finish()));
+            }
+            textures.set_current_frame(step);
+            frames.push(ImageUtil::tex_to_buffer(
+                textures.get_read_texture(),
+                gpu,
+                size,
+                size,
+            ));
+        }
+        frames
+    }
+
+    //Swaps in a pipeline compiled from user snippets. The old pipeline is kept if compilation fails.
+    pub fn recompile(
+        &mut self,
+        gpu: &Gpu,
+        activation_expr: &str,
+        update_expr: &str,
+    ) -> Result<(), String> {
+        self.pipeline = NeuralState::create_pipeline(gpu, activation_expr, update_expr)?;
+        self.activation_expr = activation_expr.to_owned();
+        self.update_expr = update_expr.to_owned();
+        Ok(())
+    }
+
+    pub fn uses_default_snippets(&self) -> bool {
+        self.activation_expr.trim() == DEFAULT_ACTIVATION_EXPR
+            && self.update_expr.trim() == DEFAULT_UPDATE_EXPR
     }
 }
 
@@ -100,9 +287,13 @@ impl TotalisticState {
     pub fn new(gpu: &Gpu) -> TotalisticState {
         let layout = TotalisticState::create_pipeline(gpu);
         let params_bind_group_layout = Rc::new(layout.get_bind_group_layout(1));
+        let update_bind_group_layout = Rc::new(layout.get_bind_group_layout(2));
+        let packed = PackedState::new(gpu, &update_bind_group_layout);
         TotalisticState {
             pipeline: layout,
-            params: TotalisticParameters::new(params_bind_group_layout),
+            params: TotalisticParameters::new(params_bind_group_layout, gpu),
+            update: UpdateParameters::new(update_bind_group_layout),
+            packed,
         }
     }
     pub fn create_pipeline(gpu: &Gpu) -> wgpu::ComputePipeline {
@@ -128,6 +319,93 @@ impl TotalisticState {
     }
 }
 
+impl PdeState {
+    pub fn new(gpu: &Gpu) -> PdeState {
+        let textures_layout = pipeline::create_layout(
+            gpu,
+            "PDE textures layout",
+            &[
+                pipeline::unfilterable_texture_entry(0),
+                pipeline::storage_texture_entry(
+                    1,
+                    wgpu::TextureFormat::Rgba32Float,
+                    wgpu::TextureViewDimension::D2,
+                ),
+            ],
+        );
+        let params_layout =
+            pipeline::create_layout(gpu, "PDE params layout", &[pipeline::uniform_entry(0)]);
+        let update_layout =
+            pipeline::create_layout(gpu, "PDE update layout", &[pipeline::uniform_entry(0)]);
+        let layouts = [&textures_layout, &params_layout, &update_layout];
+        let create = |file: &str, name: &str| {
+            let shader = pipeline::create_shader(gpu, file, name);
+            pipeline::create_compute_pipeline(gpu, &shader, name, "main", &layouts)
+        };
+        let wave_pipeline = create("wave.wgsl", "Wave");
+        let heat_pipeline = create("heat.wgsl", "Heat");
+        let update_bind_group_layout = Rc::new(update_layout);
+        PdeState {
+            wave_pipeline,
+            heat_pipeline,
+            textures_layout: Rc::new(textures_layout),
+            params: PdeParameters::new(Rc::new(params_layout)),
+            wave_update: UpdateParameters::new(update_bind_group_layout.clone()),
+            heat_update: UpdateParameters::new(update_bind_group_layout),
+            wall_mask: None,
+        }
+    }
+    fn create_textures(&self, gpu: &Gpu, size: UVec2) -> ComputeTextures {
+        let layout = self.textures_layout.clone();
+        let mask = match &self.wall_mask {
+            Some(mask) => mask.clone(),
+            None => ImageUtil::double_slit_mask(size.x, size.y),
+        };
+        ComputeTextures::new_float(layout, ImageUtil::pde_image(size.x, size.y, &mask), gpu)
+    }
+}
+
+impl VolumeState {
+    pub fn new(gpu: &Gpu) -> VolumeState {
+        let d3 = wgpu::TextureViewDimension::D3;
+        let textures_layout = pipeline::create_layout(
+            gpu,
+            "Volume textures layout",
+            &[
+                pipeline::texture_entry(0, wgpu::TextureSampleType::Uint, d3),
+                pipeline::storage_texture_entry(1, wgpu::TextureFormat::R32Uint, d3),
+            ],
+        );
+        let params_layout =
+            pipeline::create_layout(gpu, "Volume params layout", &[pipeline::uniform_entry(0)]);
+        let update_layout =
+            pipeline::create_layout(gpu, "Volume update layout", &[pipeline::uniform_entry(0)]);
+        let shader = pipeline::create_shader(gpu, "volume.wgsl", "Volume");
+        let pipeline = pipeline::create_compute_pipeline(
+            gpu,
+            &shader,
+            "Volume",
+            "main",
+            &[&textures_layout, &params_layout, &update_layout],
+        );
+        let params_bind_group_layout = Rc::new(params_layout);
+        let update_bind_group_layout = Rc::new(update_layout);
+        VolumeState {
+            pipeline,
+            textures_layout: Rc::new(textures_layout),
+            params: VolumeParameters::new(params_bind_group_layout, gpu),
+            update: UpdateParameters::new(update_bind_group_layout),
+        }
+    }
+
+    fn create_textures(&self, gpu: &Gpu) -> ComputeTextures {
+        let layout = self.textures_layout.clone();
+        let n = self.params.size;
+        let cells = ImageUtil::random_volume(n, self.params.soup_size, self.params.soup_density);
+        ComputeTextures::new_volume(layout, &cells, (n, n, n), gpu)
+    }
+}
+
 impl Simulation {
     pub fn new(gpu: &Gpu, size: UVec2) -> Simulation {
         let n_state = NeuralState::new(gpu);
@@ -138,75 +416,475 @@ impl Simulation {
             .to_rgba8();
         let mut s_state = SimulationState::default();
         s_state.paused = true;
+        let mut totalistic_state = TotalisticState::new(gpu);
+        totalistic_state.params.resize_rule_mask(gpu, size);
         Simulation {
             simulation_type: SimulationType::Totalistic,
             neural_state: NeuralState::new(gpu),
-            totalistic_state: TotalisticState::new(gpu),
+            totalistic_state,
+            pde_state: PdeState::new(gpu),
+            volume_state: VolumeState::new(gpu),
+            nca_state: GrowingNcaState::new(gpu),
             compute_textures: ComputeTextures::new(textures_layout, input_image, gpu),
+            nca_textures: None,
+            packed_cells: None,
+            history: History::new(),
+            statistics: Statistics::new(gpu),
+            cycles: CycleDetector::new(gpu),
+            pause_requested: false,
+            run_target: None,
+            texture_version: 0,
             current_frame: 0,
             sim_state: s_state,
             size,
+            depth: 1,
         }
     }
 
     pub fn remake(&mut self, gpu: &Gpu, size: UVec2, s_type: SimulationType) {
         self.size = size;
+        self.depth = 1;
         /*let input_image = match s_type {
             SimulationType::Totalistic => ImageUtil::random_image_monochrome(size.x, size.y),
             SimulationType::Neural => ImageUtil::random_image_color(size.x, size.y),
         };*/
-        let input_image = image::load_from_memory(include_bytes!("gol1.png"))
-            .unwrap()
-            .to_rgba8();
-        let isize = input_image.dimensions();
-        self.size = UVec2::new(isize.0, isize.1);
+        self.nca_textures = None;
+        self.packed_cells = None;
+        if s_type.is_pde() {
+            self.compute_textures = self.pde_state.create_textures(gpu, size);
+        } else if s_type == SimulationType::Volume {
+            let n = self.volume_state.params.size;
+            self.size = UVec2::new(n, n);
+            self.depth = n;
+            self.compute_textures = self.volume_state.create_textures(gpu);
+        } else if s_type == SimulationType::GrowingNca {
+            self.nca_textures = Some(self.nca_state.create_textures(gpu, size));
+        } else if s_type == SimulationType::Totalistic && self.uses_packed_cells() {
+            self.size = packed::packed_size(size);
+            let packed = &self.totalistic_state.packed;
+            self.packed_cells = Some(packed.random_cells(gpu, self.size));
+            let layout = Rc::new(self.totalistic_state.pipeline.get_bind_group_layout(0));
+            self.compute_textures =
+                ComputeTextures::new(layout, ImageUtil::random_image_monochrome(1, 1), gpu);
+        } else if s_type == SimulationType::Totalistic && self.totalistic_state.params.species > 1 {
+            let input_image = ImageUtil::random_image_species(
+                size.x,
+                size.y,
+                self.totalistic_state.params.species,
+            );
+            let layout = Rc::new(self.totalistic_state.pipeline.get_bind_group_layout(0));
+            self.compute_textures = ComputeTextures::new(layout, input_image, gpu);
+        } else if s_type == SimulationType::Neural && self.neural_state.params.filter.channels > 1 {
+            let input_image = ImageUtil::random_image_color(size.x, size.y);
+            let layout = Rc::new(self.neural_state.pipeline.get_bind_group_layout(0));
+            self.compute_textures = ComputeTextures::new(layout, input_image, gpu);
+        } else {
+            let input_image = image::load_from_memory(include_bytes!("gol1.png"))
+                .unwrap()
+                .to_rgba8();
+            let isize = input_image.dimensions();
+            self.size = UVec2::new(isize.0, isize.1);
+            let layout = Rc::new(self.neural_state.pipeline.get_bind_group_layout(0));
+            self.compute_textures = ComputeTextures::new(layout, input_image, gpu);
+        }
+        //Packed simulations don't use the rule mask, which would take 8 times the memory of the cells.
+        if self.packed_cells.is_none() {
+            self.totalistic_state
+                .params
+                .resize_rule_mask(gpu, self.size);
+        }
+        self.simulation_type = s_type;
+        self.restart_generations();
+    }
+
+    //Starts again from a new random soup of the same size and rule. Simulations that don't start from a soup are remade instead.
+    pub fn reseed(&mut self, gpu: &Gpu) {
+        let s_type = self.simulation_type;
+        let (w, h) = (self.size.x, self.size.y);
+        let input_image = match s_type {
+            SimulationType::Totalistic if self.packed_cells.is_some() => {
+                let packed = &self.totalistic_state.packed;
+                self.packed_cells = Some(packed.random_cells(gpu, self.size));
+                self.restart_generations();
+                return;
+            }
+            SimulationType::Totalistic if self.totalistic_state.params.species > 1 => {
+                ImageUtil::random_image_species(w, h, self.totalistic_state.params.species)
+            }
+            SimulationType::Neural if self.neural_state.params.filter.channels > 1 => {
+                ImageUtil::random_image_color(w, h)
+            }
+            SimulationType::Totalistic | SimulationType::Neural => {
+                ImageUtil::random_image_monochrome(w, h)
+            }
+            _ => {
+                self.remake(gpu, self.size, s_type);
+                return;
+            }
+        };
         let layout = Rc::new(self.neural_state.pipeline.get_bind_group_layout(0));
         self.compute_textures = ComputeTextures::new(layout, input_image, gpu);
+        self.restart_generations();
+    }
+
+    //Bookkeeping for new textures, which start over at generation 0.
+    fn restart_generations(&mut self) {
+        let s_type = self.simulation_type;
+        self.get_update_parameters_mut(s_type).reset();
+        self.texture_version += 1;
+        self.history.clear();
+        self.cycles.reset();
+        self.run_target = None;
+        self.sim_state.statistics = None;
         self.current_frame = 0;
-        self.simulation_type = s_type;
+        self.sim_state.generations = 0;
+        self.pde_state.params.time = 0.0;
     }
 
-    pub fn step(&mut self, gpu: &Gpu) {
+    //Runs n generations in a single submission.
+    pub fn step_n(&mut self, gpu: &Gpu, n: usize) {
         if !self.get_simulation_state_mut().paused {
-            self.do_step(gpu);
+            self.do_steps(gpu, n);
+        }
+    }
+
+    //Runs n more generations while paused. For the step controls, which shouldn't wait on Time's pacing.
+    pub fn run_for(&mut self, n: usize) {
+        self.run_until(self.current_frame + n);
+    }
+
+    //Runs to the given generation while paused. Does nothing if it's already been passed.
+    pub fn run_until(&mut self, generation: usize) {
+        self.run_target = (generation > self.current_frame).then_some(generation);
+    }
+
+    pub fn cancel_run(&mut self) {
+        self.run_target = None;
+    }
+
+    //The current generation and the one being run to, while the step controls are running.
+    pub fn run_progress(&self) -> Option<(usize, usize)> {
+        self.run_target.map(|target| (self.current_frame, target))
+    }
+
+    //Called once a frame. Runs at most one batch towards the target so long runs don't stall rendering,
+    //and stays paused once it's reached. Unpausing hands over to the normal pacing and drops the target.
+    pub fn step_towards_target(&mut self, gpu: &Gpu) {
+        let target = match self.run_target {
+            Some(target) if self.sim_state.paused => target,
+            _ => {
+                self.run_target = None;
+                return;
+            }
+        };
+        let batch = target
+            .saturating_sub(self.current_frame)
+            .min(MAX_BATCH_SIZE as usize);
+        self.do_steps(gpu, batch);
+        if self.current_frame >= target {
+            self.run_target = None;
+            self.pause_requested = true;
         }
     }
 
     fn do_step(&mut self, gpu: &Gpu) {
-        let mut encoder = gpu
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.do_steps(gpu, 1);
+    }
 
-        self.compute_textures.set_current_frame(self.current_frame);
-        let texture_bind_group = self.compute_textures.to_bind_group(gpu);
-        let params_bind_group = match self.simulation_type {
-            SimulationType::Totalistic => self.totalistic_state.params.to_bind_group(gpu),
-            SimulationType::Neural => self.neural_state.params.to_bind_group(gpu),
+    fn do_steps(&mut self, gpu: &Gpu, n: usize) {
+        if n == 0 {
+            return;
+        }
+        if self.packed_cells.is_some() {
+            self.do_packed_steps(gpu, n);
+            return;
+        }
+        if let Some(textures) = &self.nca_textures {
+            let dispatch = self.compute_work_group_count((self.size.x, self.size.y), (16, 16));
+            self.nca_state
+                .steps(gpu, textures, self.current_frame, n, dispatch);
+            self.current_frame += n;
+            self.get_simulation_state_mut().generations = self.current_frame;
+            return;
+        }
+
+        //Parameters only change between batches, except for the PDE time which moves every generation.
+        //Everything that changed is uploaded first, then the bind groups are only borrowed while recording.
+        let first = self.current_frame;
+        let s_type = self.simulation_type;
+        match s_type {
+            SimulationType::Totalistic => self.totalistic_state.params.prepare(gpu),
+            SimulationType::Neural => self.neural_state.params.prepare(gpu),
+            SimulationType::Volume => self.volume_state.params.prepare(gpu),
+            SimulationType::Wave | SimulationType::Heat => {
+                self.pde_state.params.write_batch(gpu, first, n)
+            }
+            _ => {}
+        }
+        self.get_update_parameters_mut(s_type)
+            .write_batch(gpu, first, n);
+        self.history.reserve(gpu, &self.compute_textures, first, n);
+        //Statistics and hashes only cover 2D textures. Volumes and NCAs are left out.
+        let size = (self.size.x, self.size.y);
+        let channels = match s_type {
+            SimulationType::Totalistic => self.totalistic_state.params.species,
+            SimulationType::Neural => self.neural_state.params.filter.channels,
+            _ => 1,
         };
+        let hashing = s_type != SimulationType::Volume
+            && self.cycles.prepare(
+                gpu,
+                &self.compute_textures,
+                self.texture_version,
+                size,
+                channels,
+            );
+        let params_bind_groups = match s_type {
+            SimulationType::Totalistic => {
+                std::slice::from_ref(self.totalistic_state.params.bind_group())
+            }
+            SimulationType::Neural => std::slice::from_ref(self.neural_state.params.bind_group()),
+            SimulationType::Wave | SimulationType::Heat => {
+                self.pde_state.params.batch_bind_groups()
+            }
+            SimulationType::Volume => std::slice::from_ref(self.volume_state.params.bind_group()),
+            SimulationType::GrowingNca => unreachable!(),
+        };
+        let update_bind_groups = self.get_update_parameters(s_type).batch_bind_groups();
         let pipeline = match self.simulation_type {
             SimulationType::Totalistic => &self.totalistic_state.pipeline,
             SimulationType::Neural => &self.neural_state.pipeline,
+            SimulationType::Wave => &self.pde_state.wave_pipeline,
+            SimulationType::Heat => &self.pde_state.heat_pipeline,
+            SimulationType::Volume => &self.volume_state.pipeline,
+            SimulationType::GrowingNca => unreachable!(),
         };
         // Dispatch
 
-        let (dispatch_with, dispatch_height) =
-            self.compute_work_group_count((self.size.x as u32, self.size.y as u32), (16, 16));
-        {
+        let (dispatch_with, dispatch_height, dispatch_depth) =
+            if self.simulation_type == SimulationType::Volume {
+                self.compute_work_group_count_3d((self.size.x, self.size.y, self.depth), (4, 4, 4))
+            } else {
+                let (x, y) = self
+                    .compute_work_group_count((self.size.x as u32, self.size.y as u32), (16, 16));
+                (x, y, 1)
+            };
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.history
+            .save(&mut encoder, &self.compute_textures, first);
+        //One pass per generation, so each one sees the previous generation's writes.
+        for i in 0..n {
             let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Totalistic step"),
+                label: Some("Simulation step"),
             });
             compute_pass.set_pipeline(pipeline);
-            compute_pass.set_bind_group(0, &texture_bind_group, &[]);
-            compute_pass.set_bind_group(1, &params_bind_group, &[]);
+            compute_pass.set_bind_group(0, self.compute_textures.bind_group(first + i), &[]);
+            compute_pass.set_bind_group(
+                1,
+                if params_bind_groups.len() == 1 {
+                    &params_bind_groups[0]
+                } else {
+                    &params_bind_groups[i]
+                },
+                &[],
+            );
+            compute_pass.set_bind_group(2, &update_bind_groups[i], &[]);
 
-            compute_pass.dispatch_workgroups(dispatch_with, dispatch_height, 1);
+            compute_pass.dispatch_workgroups(dispatch_with, dispatch_height, dispatch_depth);
+            drop(compute_pass);
+            self.history
+                .save(&mut encoder, &self.compute_textures, first + i + 1);
+            if hashing {
+                self.cycles
+                    .record_hash(&mut encoder, size, first + i + 1, i);
+            }
         }
+        if hashing {
+            self.cycles
+                .finish_batch(&mut encoder, first + 1, n, self.texture_version);
+        }
+
+        //Statistics only cover the last generation of a batch.
+        if self.statistics.enabled && s_type != SimulationType::Volume {
+            self.statistics.record(
+                gpu,
+                &mut encoder,
+                &self.compute_textures,
+                self.texture_version,
+                size,
+                first + n,
+                channels,
+            );
+        }
+        gpu.queue.submit(Some(encoder.finish()));
+        self.statistics.submitted();
+        self.cycles.submitted();
+        self.current_frame += n;
+        self.compute_textures.set_current_frame(self.current_frame);
+        self.get_simulation_state_mut().generations = self.current_frame;
+    }
+
+    //Packed simulations step on their own, without history, statistics or cycle detection, which all work on textures.
+    fn do_packed_steps(&mut self, gpu: &Gpu, n: usize) {
+        let cells = match &self.packed_cells {
+            Some(cells) => cells,
+            None => return,
+        };
+        let first = self.current_frame;
+        let state = &mut self.totalistic_state;
+        state
+            .packed
+            .prepare(gpu, &state.params.rules[0], state.params.boundary, cells);
+        state.update.write_batch(gpu, first, n);
+        let update_bind_groups = state.update.batch_bind_groups();
+        let (x, y) = state.packed.work_group_count(cells);
 
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for (i, update_bind_group) in update_bind_groups.iter().enumerate().take(n) {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Packed simulation step"),
+            });
+            compute_pass.set_pipeline(state.packed.pipeline());
+            compute_pass.set_bind_group(0, cells.bind_group(first + i), &[]);
+            compute_pass.set_bind_group(1, state.packed.bind_group(), &[]);
+            compute_pass.set_bind_group(2, update_bind_group, &[]);
+            compute_pass.dispatch_workgroups(x, y, 1);
+        }
         gpu.queue.submit(Some(encoder.finish()));
-        self.current_frame += 1;
+        self.current_frame += n;
         self.get_simulation_state_mut().generations = self.current_frame;
     }
 
+    //Picks up statistics and state hashes the GPU has finished, acting on any cycle found. Called every frame,
+    //it never waits on the GPU.
+    pub fn poll_readbacks(&mut self, gpu: &Gpu) {
+        if !self.statistics.enabled {
+            self.sim_state.statistics = None;
+        } else if let Some(stats) = self.statistics.poll(gpu, self.texture_version) {
+            self.sim_state.statistics = Some(stats);
+        }
+
+        if let Some(outcome) = self.cycles.poll(gpu, self.texture_version) {
+            match self.cycles.action {
+                CycleAction::Report => {}
+                CycleAction::Pause => {
+                    self.sim_state.paused = true;
+                    self.pause_requested = true;
+                    self.run_target = None;
+                }
+                CycleAction::Restart => {
+                    self.cycles.restarts += 1;
+                    self.cycles.last_restart = Some(outcome);
+                    self.reseed(gpu);
+                }
+            }
+        }
+    }
+
+    //Goes back, or forward again, to a generation kept in the history. Stepping from there drops the generations after it.
+    pub fn rewind(&mut self, gpu: &Gpu, generation: usize) -> bool {
+        if self.nca_textures.is_some() {
+            return false;
+        }
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        if !self
+            .history
+            .load(&mut encoder, &self.compute_textures, generation)
+        {
+            return false;
+        }
+        self.cycles.reset();
+        self.run_target = None;
+        gpu.queue.submit(Some(encoder.finish()));
+        self.current_frame = generation;
+        self.compute_textures.set_current_frame(generation);
+        self.get_simulation_state_mut().generations = generation;
+        true
+    }
+
+    //Steps the GPU and the cpu reference engine side by side, stopping at the first cell they disagree on.
+    //Both start every generation from the GPU's cells, so a difference points at the step that caused it.
+    pub fn verify_against_cpu(&mut self, gpu: &Gpu, generations: usize) -> Conformance {
+        let s_type = self.simulation_type;
+        let packed = self.packed_cells.is_some();
+        let tolerance = match s_type {
+            _ if self.nca_textures.is_some() => {
+                return Conformance::Unsupported("growing NCAs have no cpu engine".to_owned())
+            }
+            SimulationType::Totalistic => 0,
+            SimulationType::Neural if self.neural_state.uses_default_snippets() => {
+                cpu_engine::NEURAL_TOLERANCE
+            }
+            SimulationType::Neural => {
+                return Conformance::Unsupported(
+                    "custom activation or update code only runs on the GPU".to_owned(),
+                )
+            }
+            _ => {
+                return Conformance::Unsupported(
+                    "only totalistic and neural simulations have a cpu engine".to_owned(),
+                )
+            }
+        };
+        let mut cells = self.read_cells(gpu);
+        for _ in 0..generations {
+            self.do_step(gpu);
+            let update = self.get_update_parameters_mut(s_type).to_buffer();
+            let expected = if s_type == SimulationType::Totalistic {
+                let params = &self.totalistic_state.params;
+                cpu_engine::totalistic_step(
+                    &cells,
+                    params.species,
+                    &|x, y, s| {
+                        if packed {
+                            params.rules[s]
+                        } else {
+                            params.cell_rule(x, y, s)
+                        }
+                    },
+                    params.boundary,
+                    &update,
+                )
+            } else {
+                let params = &self.neural_state.params;
+                cpu_engine::neural_step(
+                    &cells,
+                    &params.filter,
+                    &params.memory,
+                    params.boundary,
+                    &update,
+                )
+            };
+            cells = self.read_cells(gpu);
+            if let Some((x, y, gpu_cell, cpu_cell)) =
+                cpu_engine::first_difference(&cells, &expected, tolerance)
+            {
+                return Conformance::Mismatch {
+                    generation: self.current_frame,
+                    x,
+                    y,
+                    gpu: gpu_cell,
+                    cpu: cpu_cell,
+                };
+            }
+        }
+        Conformance::Matched { generations }
+    }
+
+    fn read_cells(&self, gpu: &Gpu) -> image_util::InputImageType {
+        if let Some(cells) = &self.packed_cells {
+            return cells.read_image(gpu, self.current_frame);
+        }
+        ImageUtil::tex_to_buffer(self.get_current_texture(), gpu, self.size.x, self.size.y)
+            .into_image()
+    }
+
     fn compute_work_group_count(
         &self,
         (width, height): (u32, u32),
@@ -218,21 +896,116 @@ impl Simulation {
         (x, y)
     }
 
+    fn compute_work_group_count_3d(
+        &self,
+        (width, height, depth): (u32, u32, u32),
+        (workgroup_width, workgroup_height, workgroup_depth): (u32, u32, u32),
+    ) -> (u32, u32, u32) {
+        let (x, y) =
+            self.compute_work_group_count((width, height), (workgroup_width, workgroup_height));
+        let z = depth.div_ceil(workgroup_depth);
+
+        (x, y, z)
+    }
+
+    //Each simulation type keeps its own boundary. Volumes and growing NCAs always wrap.
+    pub fn get_boundary(&self, s_type: SimulationType) -> Boundary {
+        match s_type {
+            SimulationType::Totalistic => self.totalistic_state.params.boundary,
+            SimulationType::Neural => self.neural_state.params.boundary,
+            SimulationType::Wave | SimulationType::Heat => self.pde_state.params.boundary,
+            SimulationType::Volume | SimulationType::GrowingNca => Boundary::Torus,
+        }
+    }
+
+    pub fn set_boundary(&mut self, s_type: SimulationType, boundary: Boundary) {
+        match s_type {
+            SimulationType::Totalistic => self.totalistic_state.params.boundary = boundary,
+            SimulationType::Neural => self.neural_state.params.boundary = boundary,
+            SimulationType::Wave | SimulationType::Heat => {
+                self.pde_state.params.boundary = boundary
+            }
+            SimulationType::Volume | SimulationType::GrowingNca => {}
+        }
+    }
+
+    //Each simulation type keeps its own update scheme.
+    pub fn get_update_parameters(&self, s_type: SimulationType) -> &UpdateParameters {
+        match s_type {
+            SimulationType::Totalistic => &self.totalistic_state.update,
+            SimulationType::Neural => &self.neural_state.update,
+            SimulationType::Wave => &self.pde_state.wave_update,
+            SimulationType::Heat => &self.pde_state.heat_update,
+            SimulationType::Volume => &self.volume_state.update,
+            SimulationType::GrowingNca => &self.nca_state.update,
+        }
+    }
+
+    pub fn get_update_parameters_mut(&mut self, s_type: SimulationType) -> &mut UpdateParameters {
+        match s_type {
+            SimulationType::Totalistic => &mut self.totalistic_state.update,
+            SimulationType::Neural => &mut self.neural_state.update,
+            SimulationType::Wave => &mut self.pde_state.wave_update,
+            SimulationType::Heat => &mut self.pde_state.heat_update,
+            SimulationType::Volume => &mut self.volume_state.update,
+            SimulationType::GrowingNca => &mut self.nca_state.update,
+        }
+    }
+
+    pub fn get_simulation_type(&self) -> SimulationType {
+        self.simulation_type
+    }
+
     pub fn get_simulation_state_mut(&mut self) -> &mut SimulationState {
         &mut self.sim_state
     }
 
     pub fn get_current_texture(&self) -> &wgpu::Texture {
+        if let Some(textures) = &self.nca_textures {
+            return textures.get_rgba_texture();
+        }
         self.compute_textures.get_read_texture()
     }
 
+    //Whether remaking as a totalistic simulation would pack the cells. Only two state rules can be packed.
+    pub fn uses_packed_cells(&self) -> bool {
+        self.totalistic_state.packed.enabled && self.totalistic_state.params.species == 1
+    }
+
+    pub fn is_packed(&self) -> bool {
+        self.packed_cells.is_some()
+    }
+
+    //Buffer of packed cells to render, in place of get_current_texture. None unless the simulation is packed.
+    pub fn get_packed_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.packed_cells
+            .as_ref()
+            .map(|cells| cells.read_buffer(self.current_frame))
+    }
+
+    //Identifies the texture get_current_texture or the buffer get_packed_buffer returns. Changes when the textures are remade
+    //or a step flips the ping-pong pair.
+    pub fn get_current_texture_key(&self) -> (u64, usize) {
+        let slot = if self.nca_textures.is_some() {
+            0
+        } else {
+            self.current_frame % 2
+        };
+        (self.texture_version, slot)
+    }
+
     pub fn sync_state_from_gui(&mut self, gui: &mut Gui) {
-        let sim_state = self.get_simulation_state_mut();
         let gui_sim_state = gui.get_simulation_state_mut();
+        if self.pause_requested {
+            gui_sim_state.paused = true;
+            self.pause_requested = false;
+        }
+        let sim_state = self.get_simulation_state_mut();
         sim_state.paused = gui_sim_state.paused;
         gui_sim_state.fps = sim_state.fps;
         gui_sim_state.ups = sim_state.ups;
         gui_sim_state.generations = sim_state.generations;
+        gui_sim_state.statistics = sim_state.statistics;
         let _s = self.size;
     }
 }
@@ -0,0 +1,425 @@
+use std::{fs, path::Path};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::app::gpu::{bindgroup::CachedBuffer, Gpu};
+
+//Growing neural cellular automata, from Mordvintsev et al. 2020.
+//Cells have 16 channels. The first 4 are RGBA, and alpha marks living cells.
+pub const NCA_CHANNELS: usize = 16;
+//Every channel is perceived through an identity, Sobel x and Sobel y filter.
+pub const NCA_PERCEPTION: usize = NCA_CHANNELS * 3;
+
+#[derive(Debug)]
+pub enum NcaLoadError {
+    FileRead(String),
+    Parse(String),
+    Shape(String),
+}
+
+impl std::fmt::Display for NcaLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NcaLoadError::FileRead(e) => write!(f, "couldn't read file: {}", e),
+            NcaLoadError::Parse(e) => write!(f, "couldn't parse weights: {}", e),
+            NcaLoadError::Shape(e) => write!(f, "unexpected weight shape: {}", e),
+        }
+    }
+}
+
+//Weights of the two 1x1 convolution layers: perception -> hidden with ReLU, then hidden -> channel update.
+//Stored flat in the order w0, b0, w1, b1, which is the order Keras' model.get_weights() returns them in.
+//w0 and w1 are indexed [input * outputs + output].
+#[derive(Clone)]
+pub struct NcaModel {
+    pub hidden: usize,
+    weights: Vec<f32>,
+}
+
+impl NcaModel {
+    //A model that never changes any cell. Used until one is loaded.
+    pub fn empty() -> NcaModel {
+        NcaModel::from_arrays(vec![
+            vec![0.0; NCA_PERCEPTION],
+            vec![0.0; 1],
+            vec![0.0; NCA_CHANNELS],
+            vec![0.0; NCA_CHANNELS],
+        ])
+        .unwrap()
+    }
+
+    //Loads a model exported from the reference notebooks.
+    //A .json file holds the list of layer arrays, e.g. json.dump([w.tolist() for w in model.get_weights()]).
+    //A directory holds one .npy file per layer array, loaded in file name order, e.g. np.save(f"{i}.npy", w).
+    pub fn load(path: &str) -> Result<NcaModel, NcaLoadError> {
+        let p = Path::new(path);
+        let arrays = if p.is_dir() {
+            let mut files: Vec<_> = fs::read_dir(p)
+                .map_err(|e| NcaLoadError::FileRead(e.to_string()))?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|f| f.extension().is_some_and(|ext| ext == "npy"))
+                .collect();
+            //Numeric names sort as numbers so 10.npy comes after 9.npy
+            files.sort_by_key(|f| {
+                let stem = f
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                (stem.parse::<u32>().unwrap_or(u32::MAX), stem)
+            });
+            files
+                .iter()
+                .map(|f| {
+                    let bytes = fs::read(f).map_err(|e| NcaLoadError::FileRead(e.to_string()))?;
+                    NcaModel::parse_npy(&bytes)
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            let contents =
+                fs::read_to_string(p).map_err(|e| NcaLoadError::FileRead(e.to_string()))?;
+            NcaModel::parse_json(&contents)?
+        };
+        NcaModel::from_arrays(arrays)
+    }
+
+    //Checks the layer sizes against each other and the channel count, then packs them.
+    fn from_arrays(arrays: Vec<Vec<f32>>) -> Result<NcaModel, NcaLoadError> {
+        let (w0, b0, w1) = match arrays.as_slice() {
+            [w0, b0, w1] | [w0, b0, w1, _] => (w0, b0, w1),
+            _ => {
+                return Err(NcaLoadError::Shape(format!(
+                    "Expected 3 or 4 layer arrays, found {}",
+                    arrays.len()
+                )))
+            }
+        };
+        let hidden = b0.len();
+        if hidden == 0 || w0.len() != NCA_PERCEPTION * hidden {
+            return Err(NcaLoadError::Shape(format!(
+                "First layer has {} weights, expected {} x {}",
+                w0.len(),
+                NCA_PERCEPTION,
+                hidden
+            )));
+        }
+        if w1.len() != hidden * NCA_CHANNELS {
+            return Err(NcaLoadError::Shape(format!(
+                "Second layer has {} weights, expected {} x {}",
+                w1.len(),
+                hidden,
+                NCA_CHANNELS
+            )));
+        }
+        //The second layer has no bias in some exports.
+        let b1 = match arrays.get(3) {
+            Some(b1) if b1.len() == NCA_CHANNELS => b1.clone(),
+            Some(b1) => {
+                return Err(NcaLoadError::Shape(format!(
+                    "Second layer has {} biases, expected {}",
+                    b1.len(),
+                    NCA_CHANNELS
+                )))
+            }
+            None => vec![0.0; NCA_CHANNELS],
+        };
+        let weights = [w0.as_slice(), b0, w1, &b1].concat();
+        Ok(NcaModel { hidden, weights })
+    }
+
+    fn parse_json(contents: &str) -> Result<Vec<Vec<f32>>, NcaLoadError> {
+        let value: serde_json::Value =
+            serde_json::from_str(contents).map_err(|e| NcaLoadError::Parse(e.to_string()))?;
+        let layers = value
+            .as_array()
+            .ok_or_else(|| NcaLoadError::Parse("Expected a list of layers".to_owned()))?;
+        layers
+            .iter()
+            .map(|l| {
+                let mut flat = Vec::new();
+                NcaModel::flatten_json(l, &mut flat)?;
+                Ok(flat)
+            })
+            .collect()
+    }
+
+    fn flatten_json(value: &serde_json::Value, out: &mut Vec<f32>) -> Result<(), NcaLoadError> {
+        match value {
+            serde_json::Value::Number(n) => out.push(n.as_f64().unwrap_or(0.0) as f32),
+            serde_json::Value::Array(a) => {
+                for v in a {
+                    NcaModel::flatten_json(v, out)?;
+                }
+            }
+            _ => {
+                return Err(NcaLoadError::Parse(
+                    "Layers can only contain numbers".to_owned(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    //Reads a C ordered little endian float32 or float64 array from a version 1 or 2 .npy file.
+    fn parse_npy(bytes: &[u8]) -> Result<Vec<f32>, NcaLoadError> {
+        let err = |s: &str| NcaLoadError::Parse(s.to_owned());
+        if bytes.len() < 10 || &bytes[..6] != b"\x93NUMPY" {
+            return Err(err("Not a .npy file"));
+        }
+        let (header_len, header_start) = match bytes[6] {
+            1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+            _ if bytes.len() >= 12 => (
+                u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+                12,
+            ),
+            _ => return Err(err("Truncated .npy header")),
+        };
+        let data_start = header_start + header_len;
+        let header = bytes
+            .get(header_start..data_start)
+            .map(String::from_utf8_lossy)
+            .ok_or_else(|| err("Truncated .npy header"))?;
+        if header.contains("'fortran_order': True") {
+            return Err(err("Fortran ordered arrays aren't supported"));
+        }
+        let data = &bytes[data_start..];
+        if header.contains("'<f4'") {
+            Ok(data
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .collect())
+        } else if header.contains("'<f8'") {
+            Ok(data
+                .chunks_exact(8)
+                .map(|c| {
+                    f64::from_le_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]) as f32
+                })
+                .collect())
+        } else {
+            Err(err("Only float32 and float64 arrays are supported"))
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub struct NcaParamsBuffer {
+    hidden: u32,
+    _padding: [u32; 3],
+}
+
+pub struct NcaParameters {
+    pub model: NcaModel,
+    weights_buffer: CachedBuffer,
+    params_buffer: CachedBuffer,
+    bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl NcaParameters {
+    pub fn new(gpu: &Gpu, layout: wgpu::BindGroupLayout) -> NcaParameters {
+        let model = NcaModel::empty();
+        let weights_buffer = CachedBuffer::new(
+            gpu,
+            "NCA Weights Buffer",
+            wgpu::BufferUsages::STORAGE,
+            bytemuck::cast_slice(&model.weights),
+        );
+        let params_buffer = CachedBuffer::new(
+            gpu,
+            "NCA Params Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::bytes_of(&NcaParamsBuffer::zeroed()),
+        );
+        let bind_group =
+            NcaParameters::create_bind_group(gpu, &layout, &weights_buffer, &params_buffer);
+        let mut params = NcaParameters {
+            model,
+            weights_buffer,
+            params_buffer,
+            bind_group,
+            bind_group_layout: layout,
+        };
+        params.prepare(gpu);
+        params
+    }
+
+    pub fn to_buffer(&self) -> NcaParamsBuffer {
+        NcaParamsBuffer {
+            hidden: self.model.hidden as u32,
+            _padding: [0; 3],
+        }
+    }
+
+    //Uploads the model if it changed since the last step. Call before using bind_group.
+    //A model with a different hidden size replaces the weights buffer, so the bind group is rebuilt with it.
+    pub fn prepare(&mut self, gpu: &Gpu) {
+        let resized = self
+            .weights_buffer
+            .write(gpu, bytemuck::cast_slice(&self.model.weights));
+        self.params_buffer
+            .write(gpu, bytemuck::bytes_of(&self.to_buffer()));
+        if resized {
+            self.bind_group = NcaParameters::create_bind_group(
+                gpu,
+                &self.bind_group_layout,
+                &self.weights_buffer,
+                &self.params_buffer,
+            );
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    fn create_bind_group(
+        gpu: &Gpu,
+        layout: &wgpu::BindGroupLayout,
+        weights_buffer: &CachedBuffer,
+        params_buffer: &CachedBuffer,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("NCA Parameters bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: weights_buffer.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.buffer().as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A version 1 or 2 .npy file with the given dtype, fortran order and raw data.
+    fn npy(version: u8, descr: &str, fortran_order: bool, data: &[u8]) -> Vec<u8> {
+        let order = if fortran_order { "True" } else { "False" };
+        let header = format!(
+            "{{'descr': '{}', 'fortran_order': {}, 'shape': (3,), }}\n",
+            descr, order
+        );
+        let mut bytes = b"\x93NUMPY".to_vec();
+        bytes.extend([version, 0]);
+        if version == 1 {
+            bytes.extend((header.len() as u16).to_le_bytes());
+        } else {
+            bytes.extend((header.len() as u32).to_le_bytes());
+        }
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn f64_bytes(values: &[f64]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    //Layer arrays for a model with the given hidden size.
+    fn arrays(hidden: usize) -> Vec<Vec<f32>> {
+        vec![
+            vec![0.5; NCA_PERCEPTION * hidden],
+            vec![0.25; hidden],
+            vec![1.0; hidden * NCA_CHANNELS],
+            vec![2.0; NCA_CHANNELS],
+        ]
+    }
+
+    #[test]
+    fn parses_float32_npy() {
+        let bytes = npy(1, "<f4", false, &f32_bytes(&[1.0, -2.5, 3.0]));
+        assert_eq!(NcaModel::parse_npy(&bytes).unwrap(), vec![1.0, -2.5, 3.0]);
+    }
+
+    #[test]
+    fn parses_float64_npy() {
+        let bytes = npy(1, "<f8", false, &f64_bytes(&[0.5, 4.0, -1.0]));
+        assert_eq!(NcaModel::parse_npy(&bytes).unwrap(), vec![0.5, 4.0, -1.0]);
+    }
+
+    #[test]
+    fn parses_version_2_header() {
+        let bytes = npy(2, "<f4", false, &f32_bytes(&[7.0, 8.0, 9.0]));
+        assert_eq!(NcaModel::parse_npy(&bytes).unwrap(), vec![7.0, 8.0, 9.0]);
+    }
+
+    #[test]
+    fn rejects_malformed_npy() {
+        let data = f32_bytes(&[1.0, 2.0, 3.0]);
+        let mut bad_magic = npy(1, "<f4", false, &data);
+        bad_magic[1] = b'X';
+        let mut truncated = npy(1, "<f4", false, &data);
+        truncated.truncate(20);
+        let malformed = [
+            bad_magic,
+            truncated,
+            b"\x93NUM".to_vec(),
+            npy(1, "<f4", true, &data),
+            npy(1, "<i4", false, &data),
+            npy(1, ">f4", false, &data),
+        ];
+        for bytes in malformed {
+            assert!(matches!(
+                NcaModel::parse_npy(&bytes),
+                Err(NcaLoadError::Parse(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn packs_layers_in_order() {
+        let model = NcaModel::from_arrays(arrays(2)).unwrap();
+        assert_eq!(model.hidden, 2);
+        assert_eq!(model.weights, arrays(2).concat());
+    }
+
+    #[test]
+    fn missing_second_bias_is_zero() {
+        let mut layers = arrays(3);
+        layers.pop();
+        let model = NcaModel::from_arrays(layers).unwrap();
+        assert_eq!(model.hidden, 3);
+        assert!(model.weights[model.weights.len() - NCA_CHANNELS..]
+            .iter()
+            .all(|w| *w == 0.0));
+    }
+
+    #[test]
+    fn rejects_mismatched_shapes() {
+        let mut wrong_first = arrays(2);
+        wrong_first[0].pop();
+        let mut wrong_second = arrays(2);
+        wrong_second[2].push(0.0);
+        let mut wrong_bias = arrays(2);
+        wrong_bias[3].pop();
+        let no_hidden = vec![vec![], vec![], vec![], vec![0.0; NCA_CHANNELS]];
+        let too_few = arrays(2)[..2].to_vec();
+        let mut too_many = arrays(2);
+        too_many.push(vec![0.0]);
+        for layers in [
+            wrong_first,
+            wrong_second,
+            wrong_bias,
+            no_hidden,
+            too_few,
+            too_many,
+        ] {
+            assert!(matches!(
+                NcaModel::from_arrays(layers),
+                Err(NcaLoadError::Shape(_))
+            ));
+        }
+    }
+}
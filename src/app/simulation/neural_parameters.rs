@@ -1,57 +1,504 @@
 use std::rc::Rc;
 
 use bytemuck::{Pod, Zeroable};
-use wgpu::util::DeviceExt;
+use serde::{Deserialize, Serialize};
 
+use super::boundary::Boundary;
 use crate::app::{
-    gpu::{self, bindgroup::ToBindgroup, Gpu},
+    gpu::{bindgroup::CachedBuffer, Gpu},
     math::UVec2,
 };
 
+pub const MAX_CHANNELS: usize = 4;
+//Kernels can be any odd size up to this. Must match MAX_RADIUS in neural.wgsl.
+pub const MAX_KERNEL_SIZE: u32 = 15;
+
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod)]
-pub struct NeuralFilterBuffer {
-    w0: f32,
-    w1: f32,
-    w2: f32,
-    w3: f32,
-    w4: f32,
-    w5: f32,
-    w6: f32,
-    w7: f32,
-    w8: f32,
-}
-
-#[derive(Clone, Copy)]
+pub struct NeuralChannelsBuffer {
+    channels: u32,
+    kernel_size: u32,
+    residual: u32,
+    wrap: u32,
+    time_step: f32,
+    fire_rate: f32,
+    boundary: u32,
+    _padding: u32,
+    bias: [f32; MAX_CHANNELS],
+    activations: [ActivationBuffer; MAX_CHANNELS],
+}
+
+//A neural CA with up to MAX_CHANNELS channels. Each output channel convolves every input channel with its own kernel, adds a bias and applies its activation.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct NeuralFilter {
-    pub weights: [f32; 9],
+    pub channels: u32,
+    kernel_size: u32,
+    //One kernel_size * kernel_size kernel per output and input channel pair, see kernel_index.
+    //Taps are in row order, starting at the top left. Sent to the shader as a storage buffer.
+    weights: Vec<f32>,
+    pub bias: [f32; MAX_CHANNELS],
+    //Bias is only added when this is set, so it can be switched off without losing the values.
+    #[serde(default = "enabled")]
+    pub use_bias: bool,
+    //Residual updates add time_step * activation(conv) to the old cell instead of replacing it.
+    #[serde(default)]
+    pub residual: bool,
+    #[serde(default = "default_time_step")]
+    pub time_step: f32,
+    //Wrap values outside [0, 1] back into range instead of clamping them.
+    #[serde(default)]
+    pub wrap: bool,
+    //Chance of each cell updating in a step, on top of the update scheme.
+    #[serde(default = "default_fire_rate")]
+    pub fire_rate: f32,
+    pub activations: [Activation; MAX_CHANNELS],
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn default_time_step() -> f32 {
+    1.0
+}
+
+fn default_fire_rate() -> f32 {
+    1.0
 }
 
 impl Default for NeuralFilter {
     fn default() -> Self {
-        Self {
-            weights: [-0.72, 0.90, -0.68, 0.92, 0.68, 0.91, -0.68, 0.9, -0.72],
-        }
+        NeuralFilter::from_slice(&[-0.72, 0.90, -0.68, 0.92, 0.68, 0.91, -0.68, 0.9, -0.72])
     }
 }
 
 impl NeuralFilter {
-    pub fn to_buffer(&self) -> NeuralFilterBuffer {
-        NeuralFilterBuffer {
-            w0: self.weights[0],
-            w1: self.weights[1],
-            w2: self.weights[2],
-            w3: self.weights[3],
-            w4: self.weights[4],
-            w5: self.weights[5],
-            w6: self.weights[6],
-            w7: self.weights[7],
-            w8: self.weights[8],
+    pub fn to_channels_buffer(&self, boundary: Boundary) -> NeuralChannelsBuffer {
+        NeuralChannelsBuffer {
+            channels: self.channels,
+            kernel_size: self.kernel_size,
+            residual: self.residual as u32,
+            wrap: self.wrap as u32,
+            time_step: self.time_step,
+            fire_rate: self.fire_rate,
+            boundary: boundary.code(),
+            _padding: 0,
+            bias: if self.use_bias {
+                self.bias
+            } else {
+                [0.0; MAX_CHANNELS]
+            },
+            activations: self.activations.map(|a| a.to_buffer()),
         }
     }
 
+    //Single channel 3x3 filter with the given weights.
     pub fn from_slice(s: &[f32; 9]) -> NeuralFilter {
-        NeuralFilter { weights: *s }
+        let mut filter = NeuralFilter {
+            channels: 1,
+            kernel_size: 3,
+            weights: vec![0.0; 9 * MAX_CHANNELS * MAX_CHANNELS],
+            bias: [0.0; MAX_CHANNELS],
+            use_bias: true,
+            residual: false,
+            time_step: default_time_step(),
+            wrap: false,
+            fire_rate: default_fire_rate(),
+            activations: [Activation::default(); MAX_CHANNELS],
+        };
+        filter.kernel_mut(0, 0).copy_from_slice(s);
+        filter
+    }
+
+    pub fn get_kernel_size(&self) -> u32 {
+        self.kernel_size
+    }
+
+    pub fn get_weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    fn kernel_index(&self, output: usize, input: usize) -> usize {
+        let taps = (self.kernel_size * self.kernel_size) as usize;
+        (output * MAX_CHANNELS + input) * taps
+    }
+
+    pub fn kernel(&self, output: usize, input: usize) -> &[f32] {
+        let start = self.kernel_index(output, input);
+        &self.weights[start..start + (self.kernel_size * self.kernel_size) as usize]
+    }
+
+    pub fn kernel_mut(&mut self, output: usize, input: usize) -> &mut [f32] {
+        let start = self.kernel_index(output, input);
+        let taps = (self.kernel_size * self.kernel_size) as usize;
+        &mut self.weights[start..start + taps]
+    }
+
+    //Changes the kernel size, keeping the weights around the centre. Even sizes are rounded up.
+    pub fn set_kernel_size(&mut self, size: u32) {
+        let size = (size | 1).min(MAX_KERNEL_SIZE);
+        let old = self.clone();
+        self.kernel_size = size;
+        self.weights = vec![0.0; (size * size) as usize * MAX_CHANNELS * MAX_CHANNELS];
+        let old_r = (old.kernel_size / 2) as i32;
+        let r = (size / 2) as i32;
+        for o in 0..MAX_CHANNELS {
+            for i in 0..MAX_CHANNELS {
+                let old_kernel = old.kernel(o, i);
+                let kernel = self.kernel_mut(o, i);
+                for dy in -r.min(old_r)..=r.min(old_r) {
+                    for dx in -r.min(old_r)..=r.min(old_r) {
+                        let old_tap = (dy + old_r) * old.kernel_size as i32 + dx + old_r;
+                        let tap = (dy + r) * size as i32 + dx + r;
+                        kernel[tap as usize] = old_kernel[old_tap as usize];
+                    }
+                }
+            }
+        }
+    }
+}
+
+//Symmetry a kernel can be locked to. Taps related by the symmetry always hold the same weight.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Symmetry {
+    None,
+    //Every rotation and reflection of the square.
+    Full,
+    //Mirrored left to right.
+    Horizontal,
+    //Mirrored top to bottom.
+    Vertical,
+}
+
+impl Symmetry {
+    //Offsets from the kernel centre that share a weight with (dx, dy), including itself.
+    fn partners(&self, dx: i32, dy: i32) -> Vec<(i32, i32)> {
+        let mut p = match self {
+            Symmetry::None => vec![(dx, dy)],
+            Symmetry::Horizontal => vec![(dx, dy), (-dx, dy)],
+            Symmetry::Vertical => vec![(dx, dy), (dx, -dy)],
+            Symmetry::Full => vec![
+                (dx, dy),
+                (-dx, dy),
+                (dx, -dy),
+                (-dx, -dy),
+                (dy, dx),
+                (-dy, dx),
+                (dy, -dx),
+                (-dy, -dx),
+            ],
+        };
+        p.sort_unstable();
+        p.dedup();
+        p
+    }
+}
+
+impl NeuralFilter {
+    fn partner_taps(&self, tap: usize, symmetry: Symmetry) -> Vec<usize> {
+        let k = self.kernel_size as i32;
+        let r = k / 2;
+        let (dx, dy) = (tap as i32 % k - r, tap as i32 / k - r);
+        symmetry
+            .partners(dx, dy)
+            .iter()
+            .map(|(x, y)| ((y + r) * k + x + r) as usize)
+            .collect()
+    }
+
+    //Sets a weight and every weight related to it by the symmetry.
+    pub fn set_weight(
+        &mut self,
+        output: usize,
+        input: usize,
+        tap: usize,
+        value: f32,
+        symmetry: Symmetry,
+    ) {
+        let taps = self.partner_taps(tap, symmetry);
+        let kernel = self.kernel_mut(output, input);
+        for t in taps {
+            kernel[t] = value;
+        }
+    }
+
+    //Makes every kernel in use symmetric by averaging related weights.
+    pub fn symmetrise(&mut self, symmetry: Symmetry) {
+        let channels = self.channels as usize;
+        let taps = (self.kernel_size * self.kernel_size) as usize;
+        for o in 0..channels {
+            for i in 0..channels {
+                for tap in 0..taps {
+                    let partners = self.partner_taps(tap, symmetry);
+                    let kernel = self.kernel_mut(o, i);
+                    let mean =
+                        partners.iter().map(|t| kernel[*t]).sum::<f32>() / partners.len() as f32;
+                    for t in partners {
+                        kernel[t] = mean;
+                    }
+                }
+            }
+        }
+    }
+
+    //Replaces every kernel in use with uniform random weights in [-1, 1].
+    pub fn randomise(&mut self, symmetry: Symmetry) {
+        let channels = self.channels as usize;
+        for o in 0..channels {
+            for i in 0..channels {
+                for w in self.kernel_mut(o, i) {
+                    *w = rand::random::<f32>() * 2.0 - 1.0;
+                }
+            }
+        }
+        self.symmetrise(symmetry);
+    }
+
+    //Adds gaussian noise with the given standard deviation to every weight in use.
+    pub fn mutate(&mut self, strength: f32, symmetry: Symmetry) {
+        let channels = self.channels as usize;
+        for o in 0..channels {
+            for i in 0..channels {
+                for w in self.kernel_mut(o, i) {
+                    //Box-Muller transform
+                    let u1 = 1.0 - rand::random::<f32>();
+                    let u2 = rand::random::<f32>();
+                    let noise = (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos();
+                    *w = (*w + noise * strength).clamp(-1.0, 1.0);
+                }
+            }
+        }
+        self.symmetrise(symmetry);
+    }
+}
+
+//Activation function applied to the result of the convolution.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Activation {
+    Identity,
+    Sigmoid,
+    Tanh,
+    Relu,
+    //sin(frequency * x)
+    Sin { frequency: f32 },
+    Abs,
+    //2^(-scale * x^2)
+    Gaussian { scale: f32 },
+    //1 - 2^(-scale * x^2)
+    InverseGaussian { scale: f32 },
+    //c0 + c1 * x + c2 * x^2 + c3 * x^3
+    Polynomial { coefficients: [f32; 4] },
+}
+
+impl Default for Activation {
+    fn default() -> Self {
+        Activation::InverseGaussian { scale: 0.6 }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub struct ActivationBuffer {
+    kind: u32,
+    _padding: [u32; 3],
+    params: [f32; 4],
+}
+
+impl Activation {
+    pub const ALL: [Activation; 9] = [
+        Activation::Identity,
+        Activation::Sigmoid,
+        Activation::Tanh,
+        Activation::Relu,
+        Activation::Sin { frequency: 1.0 },
+        Activation::Abs,
+        Activation::Gaussian { scale: 0.6 },
+        Activation::InverseGaussian { scale: 0.6 },
+        Activation::Polynomial {
+            coefficients: [0.0, 0.0, 1.0, 0.0],
+        },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activation::Identity => "Identity",
+            Activation::Sigmoid => "Sigmoid",
+            Activation::Tanh => "Tanh",
+            Activation::Relu => "ReLU",
+            Activation::Sin { .. } => "Sin",
+            Activation::Abs => "Abs",
+            Activation::Gaussian { .. } => "Gaussian",
+            Activation::InverseGaussian { .. } => "Inverse Gaussian",
+            Activation::Polynomial { .. } => "Polynomial",
+        }
+    }
+
+    //Must match the switch in activation() in neural.wgsl
+    fn kind(&self) -> u32 {
+        match self {
+            Activation::Identity => 0,
+            Activation::Sigmoid => 1,
+            Activation::Tanh => 2,
+            Activation::Relu => 3,
+            Activation::Sin { .. } => 4,
+            Activation::Abs => 5,
+            Activation::Gaussian { .. } => 6,
+            Activation::InverseGaussian { .. } => 7,
+            Activation::Polynomial { .. } => 8,
+        }
+    }
+
+    //Same as activation() in neural.wgsl.
+    pub fn apply(&self, x: f32) -> f32 {
+        let gaussian = |scale: f32| 1.0 / 2f32.powf(scale * x.powi(2));
+        match *self {
+            Activation::Identity => x,
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Tanh => x.tanh(),
+            Activation::Relu => x.max(0.0),
+            Activation::Sin { frequency } => (frequency * x).sin(),
+            Activation::Abs => x.abs(),
+            Activation::Gaussian { scale } => gaussian(scale),
+            Activation::InverseGaussian { scale } => 1.0 - gaussian(scale),
+            Activation::Polynomial { coefficients: c } => c[0] + x * (c[1] + x * (c[2] + x * c[3])),
+        }
+    }
+
+    pub fn to_buffer(self) -> ActivationBuffer {
+        let params = match self {
+            Activation::Sin { frequency } => [frequency, 0.0, 0.0, 0.0],
+            Activation::Gaussian { scale } | Activation::InverseGaussian { scale } => {
+                [scale, 0.0, 0.0, 0.0]
+            }
+            Activation::Polynomial { coefficients } => coefficients,
+            _ => [0.0; 4],
+        };
+        ActivationBuffer {
+            kind: self.kind(),
+            _padding: [0; 3],
+            params,
+        }
+    }
+}
+
+//Number of channels shown as colour. Channels the filter doesn't compute can be used as memory instead.
+pub const COLOUR_CHANNELS: usize = 3;
+
+//Keeps a fading record of when a source channel fires, e.g. the classic green trail.
+//Only used for channels the filter doesn't compute itself.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct MemoryChannel {
+    pub enabled: bool,
+    //Channel of the newly computed cell that triggers the memory.
+    pub source: u32,
+    //The memory is set to 1 when the source goes above this...
+    pub threshold: f32,
+    //...but only once it has decayed below this.
+    pub rearm: f32,
+    //Multiplied in every step the memory isn't triggered.
+    pub decay: f32,
+    //Below fade_below the memory decays by fade_decay as well, so trails end quickly.
+    pub fade_below: f32,
+    pub fade_decay: f32,
+}
+
+impl Default for MemoryChannel {
+    fn default() -> Self {
+        MemoryChannel {
+            enabled: false,
+            source: 0,
+            threshold: 0.8,
+            rearm: 0.5,
+            decay: 0.99,
+            fade_below: 0.2,
+            fade_decay: 0.5,
+        }
+    }
+}
+
+impl MemoryChannel {
+    //The green trail single channel simulations have always had.
+    pub fn trail() -> Self {
+        MemoryChannel {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    //A green trail of the red channel, and nothing else.
+    pub fn defaults() -> [MemoryChannel; COLOUR_CHANNELS] {
+        [
+            MemoryChannel::default(),
+            MemoryChannel::trail(),
+            MemoryChannel::default(),
+        ]
+    }
+
+    fn to_buffer(self) -> MemoryChannelBuffer {
+        MemoryChannelBuffer {
+            enabled: self.enabled as u32,
+            source: self.source,
+            threshold: self.threshold,
+            rearm: self.rearm,
+            decay: self.decay,
+            fade_below: self.fade_below,
+            fade_decay: self.fade_decay,
+            _padding: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub struct MemoryChannelBuffer {
+    enabled: u32,
+    source: u32,
+    threshold: f32,
+    rearm: f32,
+    decay: f32,
+    fade_below: f32,
+    fade_decay: f32,
+    _padding: f32,
+}
+
+//How the state channels map to display colour in neural_render.wgsl.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ChannelColours {
+    //Greyscale for one channel, straight RGB for more.
+    pub auto: bool,
+    //Display colour of each state channel, used when auto is off. The results are added together.
+    pub colours: [[f32; 3]; COLOUR_CHANNELS],
+}
+
+impl Default for ChannelColours {
+    fn default() -> Self {
+        ChannelColours {
+            auto: true,
+            colours: [[1.0, 1.0, 1.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        }
+    }
+}
+
+impl ChannelColours {
+    //Colours the renderer should use for a simulation with the given number of channels.
+    pub fn resolve(&self, channels: u32) -> [[f32; 4]; COLOUR_CHANNELS] {
+        let mut out = [[0.0; 4]; COLOUR_CHANNELS];
+        for (c, colour) in out.iter_mut().enumerate() {
+            let rgb = if !self.auto {
+                self.colours[c]
+            } else if channels == 1 {
+                [if c == 0 { 1.0 } else { 0.0 }; 3]
+            } else {
+                let mut unit = [0.0; 3];
+                if (c as u32) < channels {
+                    unit[c] = 1.0;
+                }
+                unit
+            };
+            colour[..3].copy_from_slice(&rgb);
+        }
+        out
     }
 }
 
@@ -63,34 +510,113 @@ pub struct NeuralCreationParameters {
 
 pub struct NeuralParameters {
     pub filter: NeuralFilter,
+    //Post step memory for the red, green and blue channels.
+    pub memory: [MemoryChannel; COLOUR_CHANNELS],
+    pub colours: ChannelColours,
+    pub boundary: Boundary,
+    filter_buffer: CachedBuffer,
+    channels_buffer: CachedBuffer,
+    memory_buffer: CachedBuffer,
+    bind_group: wgpu::BindGroup,
     bind_group_layout: Rc<wgpu::BindGroupLayout>,
 }
 
 impl NeuralParameters {
-    pub fn new(layout: Rc<wgpu::BindGroupLayout>) -> NeuralParameters {
+    pub fn new(layout: Rc<wgpu::BindGroupLayout>, gpu: &Gpu) -> NeuralParameters {
+        let filter = NeuralFilter::default();
+        let memory = MemoryChannel::defaults();
+        let boundary = Boundary::default();
+        let filter_buffer = CachedBuffer::new(
+            gpu,
+            "Neural Filter Buffer",
+            wgpu::BufferUsages::STORAGE,
+            bytemuck::cast_slice(filter.get_weights()),
+        );
+        let channels_buffer = CachedBuffer::new(
+            gpu,
+            "Neural Channels Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::bytes_of(&filter.to_channels_buffer(boundary)),
+        );
+        let memory_buffer = CachedBuffer::new(
+            gpu,
+            "Neural Memory Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::cast_slice(&memory.map(|m| m.to_buffer())),
+        );
+        let bind_group = NeuralParameters::create_bind_group(
+            gpu,
+            &layout,
+            &filter_buffer,
+            &channels_buffer,
+            &memory_buffer,
+        );
         NeuralParameters {
-            filter: NeuralFilter::default(),
-            bind_group_layout: layout.clone(),
+            filter,
+            memory,
+            colours: ChannelColours::default(),
+            boundary,
+            filter_buffer,
+            channels_buffer,
+            memory_buffer,
+            bind_group,
+            bind_group_layout: layout,
         }
     }
-}
 
-impl ToBindgroup for NeuralParameters {
-    fn to_bind_group(&self, gpu: &Gpu) -> wgpu::BindGroup {
-        let filter_buffer = gpu
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Neural Filter Buffer"),
-                contents: bytemuck::bytes_of(&self.filter.to_buffer()),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
+    //Uploads whatever changed since the last step. The weights grow and shrink with the kernel, which replaces their buffer
+    //and so the bind group. Call before using bind_group.
+    pub fn prepare(&mut self, gpu: &Gpu) {
+        let resized = self
+            .filter_buffer
+            .write(gpu, bytemuck::cast_slice(self.filter.get_weights()));
+        self.channels_buffer.write(
+            gpu,
+            bytemuck::bytes_of(&self.filter.to_channels_buffer(self.boundary)),
+        );
+        self.memory_buffer.write(
+            gpu,
+            bytemuck::cast_slice(&self.memory.map(|m| m.to_buffer())),
+        );
+        if resized {
+            self.bind_group = NeuralParameters::create_bind_group(
+                gpu,
+                &self.bind_group_layout,
+                &self.filter_buffer,
+                &self.channels_buffer,
+                &self.memory_buffer,
+            );
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    fn create_bind_group(
+        gpu: &Gpu,
+        layout: &wgpu::BindGroupLayout,
+        filter_buffer: &CachedBuffer,
+        channels_buffer: &CachedBuffer,
+        memory_buffer: &CachedBuffer,
+    ) -> wgpu::BindGroup {
         gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Neural Parameters bind group"),
-            layout: self.bind_group_layout.as_ref(),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: filter_buffer.as_entire_binding(),
-            }],
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: filter_buffer.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: channels_buffer.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: memory_buffer.buffer().as_entire_binding(),
+                },
+            ],
         })
     }
 }
@@ -0,0 +1,192 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    neural_parameters::{
+        Activation, ChannelColours, MemoryChannel, NeuralFilter, COLOUR_CHANNELS, MAX_CHANNELS,
+        MAX_KERNEL_SIZE,
+    },
+    DEFAULT_ACTIVATION_EXPR, DEFAULT_UPDATE_EXPR,
+};
+
+#[derive(Debug)]
+pub enum PresetError {
+    FileRead(String),
+    FileWrite(String),
+    Parse(String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for PresetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresetError::FileRead(e) => write!(f, "couldn't read presets file: {}", e),
+            PresetError::FileWrite(e) => write!(f, "couldn't write presets file: {}", e),
+            PresetError::Parse(e) => write!(f, "couldn't parse presets: {}", e),
+            PresetError::Invalid(e) => write!(f, "invalid preset: {}", e),
+        }
+    }
+}
+
+//A named filter together with the WGSL activation and update expressions it was made with.
+//The update expression is where a preset sets its colouring.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NeuralPreset {
+    pub name: String,
+    #[serde(default = "default_activation_expr")]
+    pub activation_expr: String,
+    #[serde(default = "default_update_expr")]
+    pub update_expr: String,
+    //Tables are kept last so TOML writes them after the plain values.
+    pub filter: NeuralFilter,
+    #[serde(default = "MemoryChannel::defaults")]
+    pub memory: [MemoryChannel; COLOUR_CHANNELS],
+    #[serde(default)]
+    pub colours: ChannelColours,
+}
+
+fn default_activation_expr() -> String {
+    DEFAULT_ACTIVATION_EXPR.to_owned()
+}
+
+fn default_update_expr() -> String {
+    DEFAULT_UPDATE_EXPR.to_owned()
+}
+
+//TOML needs a table at the top level, so the list is wrapped.
+#[derive(Serialize, Deserialize)]
+struct PresetFile {
+    presets: Vec<NeuralPreset>,
+}
+
+impl NeuralPreset {
+    fn new(name: &str, weights: &[f32; 9], activation: Activation, activation_expr: &str) -> Self {
+        let mut filter = NeuralFilter::from_slice(weights);
+        filter.activations = [activation; MAX_CHANNELS];
+        NeuralPreset {
+            name: name.to_owned(),
+            activation_expr: activation_expr.to_owned(),
+            update_expr: DEFAULT_UPDATE_EXPR.to_owned(),
+            filter,
+            memory: MemoryChannel::defaults(),
+            colours: ChannelColours::default(),
+        }
+    }
+
+    //Presets shipped with the app. Most come from the neuralpatterns.io examples.
+    pub fn builtin() -> Vec<NeuralPreset> {
+        let gaussian = Activation::InverseGaussian { scale: 0.6 };
+        vec![
+            NeuralPreset::new(
+                "Default",
+                &[-0.72, 0.90, -0.68, 0.92, 0.68, 0.91, -0.68, 0.9, -0.72],
+                gaussian,
+                DEFAULT_ACTIVATION_EXPR,
+            ),
+            NeuralPreset::new(
+                "Worms",
+                &[0.68, -0.9, 0.68, -0.9, -0.66, -0.9, 0.68, -0.9, 0.68],
+                gaussian,
+                DEFAULT_ACTIVATION_EXPR,
+            ),
+            NeuralPreset::new(
+                "Waves",
+                &[
+                    0.565, -0.716, 0.565, -0.716, 0.627, -0.716, 0.565, -0.716, 0.565,
+                ],
+                Activation::Abs,
+                "abs(1.2 * x)",
+            ),
+            NeuralPreset::new(
+                "Stars",
+                &[
+                    0.565, -0.716, 0.565, -0.759, 0.627, -0.759, 0.565, -0.716, 0.565,
+                ],
+                Activation::Abs,
+                DEFAULT_ACTIVATION_EXPR,
+            ),
+            NeuralPreset::new(
+                "Slime",
+                &[0.8, -0.85, 0.8, -0.85, -0.2, -0.85, 0.8, -0.85, 0.8],
+                gaussian,
+                "1.0 - 1.0 / (0.89 * x * x + 1.0)",
+            ),
+            NeuralPreset::new(
+                "Mitosis",
+                &[-0.939, 0.88, -0.939, 0.88, 0.4, 0.88, -0.939, 0.88, -0.939],
+                gaussian,
+                "1.0 - 1.0 / (0.9 * x * x + 1.0)",
+            ),
+            NeuralPreset::new(
+                "Pathways",
+                &[0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0],
+                Activation::Gaussian { scale: 1.0 },
+                "gaussian(x - 3.5, a.params.x)",
+            ),
+        ]
+    }
+
+    //Saved filters come from files people can edit by hand, so check the shapes before the weights reach the GPU.
+    fn validate(&self) -> Result<(), PresetError> {
+        let f = &self.filter;
+        let k = f.get_kernel_size();
+        if f.channels == 0 || f.channels as usize > MAX_CHANNELS {
+            return Err(PresetError::Invalid(format!(
+                "{}: channels must be between 1 and {}",
+                self.name, MAX_CHANNELS
+            )));
+        }
+        if k & 1 == 0 || k > MAX_KERNEL_SIZE {
+            return Err(PresetError::Invalid(format!(
+                "{}: kernel size must be odd and at most {}",
+                self.name, MAX_KERNEL_SIZE
+            )));
+        }
+        let expected = (k * k) as usize * MAX_CHANNELS * MAX_CHANNELS;
+        if f.get_weights().len() != expected {
+            return Err(PresetError::Invalid(format!(
+                "{}: expected {} weights for a {}x{} kernel, found {}",
+                self.name,
+                expected,
+                k,
+                k,
+                f.get_weights().len()
+            )));
+        }
+        Ok(())
+    }
+
+    //Reads a presets file. Files ending in .toml are TOML, anything else is JSON.
+    pub fn load_file(path: &str) -> Result<Vec<NeuralPreset>, PresetError> {
+        let src = fs::read_to_string(path).map_err(|e| PresetError::FileRead(e.to_string()))?;
+        let file: PresetFile = if is_toml(path) {
+            toml::from_str(&src).map_err(|e| PresetError::Parse(e.to_string()))?
+        } else {
+            serde_json::from_str(&src).map_err(|e| PresetError::Parse(e.to_string()))?
+        };
+        for p in &file.presets {
+            p.validate()?;
+        }
+        Ok(file.presets)
+    }
+
+    pub fn save_file(path: &str, presets: &[NeuralPreset]) -> Result<(), PresetError> {
+        let file = PresetFile {
+            presets: presets.to_vec(),
+        };
+        let src = if is_toml(path) {
+            toml::to_string(&file).map_err(|e| PresetError::FileWrite(e.to_string()))?
+        } else {
+            serde_json::to_string_pretty(&file)
+                .map_err(|e| PresetError::FileWrite(e.to_string()))?
+        };
+        fs::write(path, src).map_err(|e| PresetError::FileWrite(e.to_string()))
+    }
+}
+
+fn is_toml(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
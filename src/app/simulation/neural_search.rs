@@ -0,0 +1,216 @@
+use crate::app::{gpu::Gpu, image_util::ImageData};
+
+use super::{
+    neural_parameters::{MemoryChannel, NeuralFilter, Symmetry},
+    NeuralState,
+};
+
+//Candidates run on a small grid for a fixed number of steps. Snapshots are taken halfway and at the last two steps.
+pub const SEARCH_SIZE: u32 = 64;
+const SEARCH_STEPS: usize = 200;
+const SNAPSHOTS: [usize; 3] = [SEARCH_STEPS / 2, SEARCH_STEPS - 1, SEARCH_STEPS];
+
+//How a filter behaves in a headless run. Every metric is in [0, 1].
+#[derive(Clone, Copy, Default)]
+pub struct FilterMetrics {
+    //Mean cell value at the end of the run.
+    pub activity: f32,
+    //Entropy of the 2x2 patterns of live cells, over the 4 bits a pattern can hold.
+    pub entropy: f32,
+    //Mean change of a cell over the last step.
+    pub change: f32,
+    //Mean change of a cell over the second half of the run. Low for still lifes and short period oscillators.
+    pub non_stationarity: f32,
+}
+
+impl FilterMetrics {
+    fn from_frames(frames: &[ImageData], channels: u32) -> FilterMetrics {
+        let mid = cell_values(&frames[0], channels);
+        let before_last = cell_values(&frames[1], channels);
+        let last = cell_values(&frames[2], channels);
+        let n = last.len() as f32;
+        let mean_difference =
+            |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum::<f32>() / n;
+
+        let width = frames[2].size.x as usize;
+        let height = frames[2].size.y as usize;
+        let mut patterns = [0u32; 16];
+        for y in 0..height {
+            for x in 0..width {
+                let mut pattern = 0;
+                for (bit, (dx, dy)) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter().enumerate() {
+                    let i = (y + dy) % height * width + (x + dx) % width;
+                    if last[i] > 0.5 {
+                        pattern |= 1 << bit;
+                    }
+                }
+                patterns[pattern] += 1;
+            }
+        }
+        let entropy = patterns
+            .iter()
+            .filter(|c| **c > 0)
+            .map(|c| {
+                let p = *c as f32 / n;
+                -p * p.log2()
+            })
+            .sum::<f32>()
+            / 4.0;
+
+        FilterMetrics {
+            activity: last.iter().sum::<f32>() / n,
+            entropy,
+            change: mean_difference(&last, &before_last),
+            non_stationarity: mean_difference(&last, &mid),
+        }
+    }
+}
+
+//Cell values in [0, 1], averaged over the channels in use.
+fn cell_values(image: &ImageData, channels: u32) -> Vec<f32> {
+    let channels = channels.clamp(1, 3) as usize;
+    image
+        .data
+        .chunks_exact(4)
+        .map(|c| c[..channels].iter().map(|v| *v as f32).sum::<f32>() / (255.0 * channels as f32))
+        .collect()
+}
+
+//How much each metric counts towards a candidate's score.
+#[derive(Clone, Copy)]
+pub struct MetricWeights {
+    //Rewards activity near one half. Empty and saturated grids score zero.
+    pub activity: f32,
+    pub entropy: f32,
+    pub change: f32,
+    pub non_stationarity: f32,
+}
+
+impl Default for MetricWeights {
+    fn default() -> Self {
+        MetricWeights {
+            activity: 1.0,
+            entropy: 1.0,
+            change: 1.0,
+            non_stationarity: 1.0,
+        }
+    }
+}
+
+impl MetricWeights {
+    pub fn score(&self, m: &FilterMetrics) -> f32 {
+        self.activity * (1.0 - (2.0 * m.activity - 1.0).abs())
+            + self.entropy * m.entropy
+            + self.change * m.change
+            + self.non_stationarity * m.non_stationarity
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum SearchStrategy {
+    //Every candidate is a fresh random filter.
+    Random,
+    //Candidates are mutations of the current best filters.
+    Evolutionary { mutation_strength: f32 },
+}
+
+pub struct SearchResult {
+    //Number of the evaluation that produced this result. Unique within a search.
+    pub id: usize,
+    pub filter: NeuralFilter,
+    pub metrics: FilterMetrics,
+    pub score: f32,
+    //Cells at the end of the run, for the gallery.
+    pub image: ImageData,
+}
+
+pub struct NeuralSearch {
+    pipeline: wgpu::ComputePipeline,
+    //Channels, kernel size and activations are kept from this. Only the weights are searched.
+    base: NeuralFilter,
+    pub strategy: SearchStrategy,
+    pub symmetry: Symmetry,
+    pub weights: MetricWeights,
+    //Best results so far, highest score first.
+    pub results: Vec<SearchResult>,
+    pub keep: usize,
+    pub evaluated: usize,
+}
+
+impl NeuralSearch {
+    pub fn new(
+        gpu: &Gpu,
+        base: NeuralFilter,
+        activation_expr: &str,
+        update_expr: &str,
+    ) -> Result<NeuralSearch, String> {
+        Ok(NeuralSearch {
+            pipeline: NeuralState::create_pipeline(gpu, activation_expr, update_expr)?,
+            base,
+            strategy: SearchStrategy::Random,
+            symmetry: Symmetry::None,
+            weights: MetricWeights::default(),
+            results: Vec::new(),
+            keep: 12,
+            evaluated: 0,
+        })
+    }
+
+    fn next_candidate(&self) -> NeuralFilter {
+        let mut candidate = self.base.clone();
+        match self.strategy {
+            SearchStrategy::Evolutionary { mutation_strength } if !self.results.is_empty() => {
+                let parent = rand::random::<usize>() % self.results.len();
+                candidate = self.results[parent].filter.clone();
+                candidate.mutate(mutation_strength, self.symmetry);
+            }
+            _ => candidate.randomise(self.symmetry),
+        }
+        candidate
+    }
+
+    //Scores the kept results again after the metric weights change.
+    pub fn rescore(&mut self) {
+        for r in &mut self.results {
+            r.score = self.weights.score(&r.metrics);
+        }
+        self.results
+            .sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    }
+
+    //Runs and scores one candidate, keeping it if it's among the best.
+    pub fn step(&mut self, gpu: &Gpu) {
+        let filter = self.next_candidate();
+        let mut frames = NeuralState::run_headless(
+            gpu,
+            &self.pipeline,
+            &filter,
+            &MemoryChannel::defaults(),
+            SEARCH_SIZE,
+            &SNAPSHOTS,
+        );
+        let metrics = FilterMetrics::from_frames(&frames, filter.channels);
+        let score = self.weights.score(&metrics);
+        self.evaluated += 1;
+        if self.results.len() == self.keep && self.results.last().is_some_and(|r| r.score >= score)
+        {
+            return;
+        }
+        let index = self
+            .results
+            .iter()
+            .position(|r| r.score < score)
+            .unwrap_or(self.results.len());
+        self.results.insert(
+            index,
+            SearchResult {
+                id: self.evaluated,
+                filter,
+                metrics,
+                score,
+                image: frames.pop().unwrap(),
+            },
+        );
+        self.results.truncate(self.keep);
+    }
+}
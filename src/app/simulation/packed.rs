@@ -0,0 +1,245 @@
+/* Bit packed state for two state totalistic simulations.
+Cells are stored 32 to a u32 in storage buffers instead of one per Rgba8Unorm texel, so a grid takes a 32nd of the memory
+and packed.wgsl updates a whole word of cells with a handful of bitwise operations.
+*/
+
+use bytemuck::{bytes_of, Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use super::boundary::Boundary;
+use crate::app::{
+    gpu::{bindgroup::CachedBuffer, pipeline, Gpu},
+    image_util::InputImageType,
+    math::UVec2,
+    rule::Rule,
+};
+
+pub const CELLS_PER_WORD: u32 = 32;
+//Largest packed grid along each axis. A 32768^2 grid is 128MB per buffer, the default storage binding limit.
+pub const MAX_PACKED_SIZE: u32 = 32768;
+
+//Same layout as PackedParams in packed.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PackedParamsBuffer {
+    born: u32,
+    stay_alive: u32,
+    boundary: u32,
+    width: u32,
+    height: u32,
+    words_per_row: u32,
+    _padding: [u32; 2],
+}
+
+//Implemented by hand like PartialBuffer in statistics.rs.
+const _: () = assert!(std::mem::size_of::<PackedParamsBuffer>() == 32);
+unsafe impl Zeroable for PackedParamsBuffer {}
+unsafe impl Pod for PackedParamsBuffer {}
+
+//Rows are a whole number of words, so packed grids are rounded up to a multiple of 32 cells wide.
+pub fn packed_size(size: UVec2) -> UVec2 {
+    UVec2::new(
+        size.x
+            .clamp(1, MAX_PACKED_SIZE)
+            .next_multiple_of(CELLS_PER_WORD),
+        size.y.clamp(1, MAX_PACKED_SIZE),
+    )
+}
+
+//The two ping-pong buffers of a packed grid.
+pub struct PackedCells {
+    buffers: [wgpu::Buffer; 2],
+    //Index 0 reads the first buffer and writes the second, like ComputeTextures.
+    bind_groups: [wgpu::BindGroup; 2],
+    size: UVec2,
+}
+
+impl PackedCells {
+    fn new(gpu: &Gpu, layout: &wgpu::BindGroupLayout, size: UVec2, words: &[u32]) -> PackedCells {
+        let usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::COPY_DST;
+        let buffers = [
+            gpu.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Packed cells buffer"),
+                    contents: bytemuck::cast_slice(words),
+                    usage,
+                }),
+            gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Packed cells buffer"),
+                size: std::mem::size_of_val(words) as u64,
+                usage,
+                mapped_at_creation: false,
+            }),
+        ];
+        let bind_group = |read: usize| {
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Packed cells bind group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffers[read].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: buffers[1 - read].as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let bind_groups = [bind_group(0), bind_group(1)];
+        PackedCells {
+            buffers,
+            bind_groups,
+            size,
+        }
+    }
+
+    fn words_per_row(&self) -> u32 {
+        self.size.x / CELLS_PER_WORD
+    }
+
+    //Bind group that reads the cells of the given frame and writes the next one.
+    pub fn bind_group(&self, frame: usize) -> &wgpu::BindGroup {
+        &self.bind_groups[frame % 2]
+    }
+
+    //Buffer holding the cells of the given frame.
+    pub fn read_buffer(&self, frame: usize) -> &wgpu::Buffer {
+        &self.buffers[frame % 2]
+    }
+
+    //Copies the cells of a frame back and unpacks them into an image like the one ComputeTextures would hold. Waits on the GPU.
+    pub fn read_image(&self, gpu: &Gpu, frame: usize) -> InputImageType {
+        let size = self.size.x as u64 * self.size.y as u64 / 8;
+        let output_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(self.read_buffer(frame), 0, &output_buffer, 0, size);
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = output_buffer.slice(..);
+        buffer_slice.map_async(wgpu::MapMode::Read, |a| a.unwrap());
+        gpu.device.poll(wgpu::Maintain::Wait);
+        let data = buffer_slice.get_mapped_range();
+        let words_per_row = self.words_per_row() as usize;
+        InputImageType::from_fn(self.size.x, self.size.y, |x, y| {
+            let offset = (y as usize * words_per_row + (x / CELLS_PER_WORD) as usize) * 4;
+            let word: u32 = bytemuck::pod_read_unaligned(&data[offset..offset + 4]);
+            let v = if (word >> (x % CELLS_PER_WORD)) & 1 == 1 {
+                255
+            } else {
+                0
+            };
+            image::Rgba([v, v, v, 255])
+        })
+    }
+}
+
+//Pipeline and rule uniform for packed simulations. The rule and boundary come from TotalisticParameters.
+pub struct PackedState {
+    //Store two state simulations packed. Takes effect when the simulation is remade.
+    pub enabled: bool,
+    pipeline: wgpu::ComputePipeline,
+    cells_layout: wgpu::BindGroupLayout,
+    params_buffer: CachedBuffer,
+    params_bind_group: wgpu::BindGroup,
+}
+
+impl PackedState {
+    //update_layout is the layout of the totalistic UpdateParameters' bind groups, which packed steps share.
+    pub fn new(gpu: &Gpu, update_layout: &wgpu::BindGroupLayout) -> PackedState {
+        let cells_layout = pipeline::create_layout(
+            gpu,
+            "Packed cells layout",
+            &[
+                pipeline::buffer_entry(0, wgpu::BufferBindingType::Storage { read_only: true }),
+                pipeline::buffer_entry(1, wgpu::BufferBindingType::Storage { read_only: false }),
+            ],
+        );
+        let params_layout =
+            pipeline::create_layout(gpu, "Packed params layout", &[pipeline::uniform_entry(0)]);
+        let shader = pipeline::create_shader(gpu, "packed.wgsl", "Packed");
+        let pipeline = pipeline::create_compute_pipeline(
+            gpu,
+            &shader,
+            "Packed",
+            "main",
+            &[&cells_layout, &params_layout, update_layout],
+        );
+        let params_buffer = CachedBuffer::new(
+            gpu,
+            "Packed Params Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytes_of(&PackedParamsBuffer::zeroed()),
+        );
+        let params_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Packed Params BindGroup"),
+            layout: &params_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.buffer().as_entire_binding(),
+            }],
+        });
+        PackedState {
+            enabled: false,
+            pipeline,
+            cells_layout,
+            params_buffer,
+            params_bind_group,
+        }
+    }
+
+    pub fn pipeline(&self) -> &wgpu::ComputePipeline {
+        &self.pipeline
+    }
+
+    //A new grid of the given size, which has to come from packed_size, with each cell alive at random.
+    pub fn random_cells(&self, gpu: &Gpu, size: UVec2) -> PackedCells {
+        let words: Vec<u32> = (0..size.x / CELLS_PER_WORD * size.y)
+            .map(|_| rand::random())
+            .collect();
+        PackedCells::new(gpu, &self.cells_layout, size, &words)
+    }
+
+    //Uploads the rule if it changed since the last step. Call before using bind_group.
+    pub fn prepare(&mut self, gpu: &Gpu, rule: &Rule, boundary: Boundary, cells: &PackedCells) {
+        let bits = |counts: &[u32; 8]| {
+            counts
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| **c == 1)
+                .fold(0, |bits, (n, _)| bits | 1 << (n + 1))
+        };
+        let params = PackedParamsBuffer {
+            born: bits(&rule.born),
+            stay_alive: bits(&rule.stay_alive),
+            boundary: boundary.code(),
+            width: cells.size.x,
+            height: cells.size.y,
+            words_per_row: cells.words_per_row(),
+            _padding: [0; 2],
+        };
+        self.params_buffer.write(gpu, bytes_of(&params));
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.params_bind_group
+    }
+
+    //Workgroups covering every word of a grid.
+    pub fn work_group_count(&self, cells: &PackedCells) -> (u32, u32) {
+        (
+            cells.words_per_row().div_ceil(16),
+            cells.size.y.div_ceil(16),
+        )
+    }
+}
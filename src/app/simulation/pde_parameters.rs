@@ -0,0 +1,135 @@
+use std::rc::Rc;
+
+use bytemuck::{Pod, Zeroable};
+
+use super::boundary::Boundary;
+use crate::app::{
+    gpu::{bindgroup::UniformBatch, Gpu},
+    math::UVec2,
+};
+
+//Must match MAX_SOURCES in wave.wgsl and heat.wgsl
+pub const MAX_SOURCES: usize = 8;
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub struct PointSourceBuffer {
+    position: [f32; 2],
+    amplitude: f32,
+    frequency: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub struct PdeParamsBuffer {
+    wave_speed: f32,
+    damping: f32,
+    diffusivity: f32,
+    time: f32,
+    source_count: u32,
+    boundary: u32,
+    _padding: [u32; 2],
+    sources: [PointSourceBuffer; MAX_SOURCES],
+}
+
+//A point in the grid that is driven by a sine signal: value = amplitude * sin(2pi * frequency * time)
+#[derive(Clone, Copy)]
+pub struct PointSource {
+    pub position: UVec2,
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl PointSource {
+    pub fn to_buffer(self) -> PointSourceBuffer {
+        PointSourceBuffer {
+            position: [self.position.x as f32, self.position.y as f32],
+            amplitude: self.amplitude,
+            frequency: self.frequency,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PdeSettings {
+    //Courant number c*dt/dx. The explicit scheme is only stable up to 1/sqrt(2).
+    pub wave_speed: f32,
+    pub damping: f32,
+    //alpha*dt/dx^2. The explicit scheme is only stable up to 0.25.
+    pub diffusivity: f32,
+    //Simulated time that passes per generation. Drives the point sources.
+    pub time_step: f32,
+    pub sources: Vec<PointSource>,
+}
+
+impl Default for PdeSettings {
+    fn default() -> Self {
+        Self {
+            wave_speed: 0.5,
+            damping: 0.001,
+            diffusivity: 0.2,
+            time_step: 0.05,
+            sources: vec![PointSource {
+                position: UVec2::new(64, 256),
+                amplitude: 1.0,
+                frequency: 0.5,
+            }],
+        }
+    }
+}
+
+impl PdeSettings {
+    pub fn to_buffer(&self, time: f32, boundary: Boundary) -> PdeParamsBuffer {
+        let mut sources = [PointSourceBuffer::zeroed(); MAX_SOURCES];
+        for (buf, source) in sources.iter_mut().zip(self.sources.iter()) {
+            *buf = source.to_buffer();
+        }
+        PdeParamsBuffer {
+            wave_speed: self.wave_speed,
+            damping: self.damping,
+            diffusivity: self.diffusivity,
+            time,
+            source_count: self.sources.len().min(MAX_SOURCES) as u32,
+            boundary: boundary.code(),
+            _padding: [0; 2],
+            sources,
+        }
+    }
+}
+
+pub struct PdeParameters {
+    pub settings: PdeSettings,
+    pub time: f32,
+    pub boundary: Boundary,
+    batch: UniformBatch,
+    bind_group_layout: Rc<wgpu::BindGroupLayout>,
+}
+
+impl PdeParameters {
+    pub fn new(layout: Rc<wgpu::BindGroupLayout>) -> PdeParameters {
+        PdeParameters {
+            settings: PdeSettings::default(),
+            time: 0.0,
+            boundary: Boundary::default(),
+            batch: UniformBatch::new("PDE Params Batch Buffer"),
+            bind_group_layout: layout,
+        }
+    }
+}
+
+impl PdeParameters {
+    //The sources are driven by the time, which moves on every generation, so each generation in a batch gets its own parameters.
+    pub fn write_batch(&mut self, gpu: &Gpu, first_generation: usize, n: usize) {
+        let uniforms: Vec<PdeParamsBuffer> = (first_generation..first_generation + n)
+            .map(|generation| {
+                self.time = generation as f32 * self.settings.time_step;
+                self.settings.to_buffer(self.time, self.boundary)
+            })
+            .collect();
+        self.batch.write(gpu, &self.bind_group_layout, &uniforms);
+    }
+
+    pub fn batch_bind_groups(&self) -> &[wgpu::BindGroup] {
+        self.batch.bind_groups()
+    }
+}
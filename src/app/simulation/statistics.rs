@@ -0,0 +1,343 @@
+/* Live population statistics, computed on the GPU after a batch of steps and read back without waiting on it.
+Later frames poll for the results and report the newest one, so the numbers lag the simulation by a frame or two but never stall it.
+*/
+
+use bytemuck::{Pod, Zeroable};
+
+use super::compute_textures::ComputeTextures;
+use crate::app::gpu::{bindgroup::CachedBuffer, pipeline, readback::ReadbackRing, Gpu};
+
+//Staging buffers that can wait on the GPU at once. Batches that find them all busy skip their statistics.
+const READBACK_BUFFERS: usize = 3;
+//Cells summed by each workgroup of the first pass, along each axis.
+pub const TILE_SIZE: u32 = 16;
+
+//Same layout as Partial in statistics.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PartialBuffer {
+    sum: [f32; 4],
+    sum_sq: [f32; 4],
+    live: u32,
+    births: u32,
+    deaths: u32,
+    cells: u32,
+}
+
+//Pod and Zeroable are implemented by hand because the derive's padding check leaves dead code warnings behind.
+//Every field is 4 bytes, so checking the size is enough to rule out padding.
+const _: () = assert!(std::mem::size_of::<PartialBuffer>() == 48);
+unsafe impl Zeroable for PartialBuffer {}
+unsafe impl Pod for PartialBuffer {}
+
+//Shared by the statistics and hash reductions, see StatsParams in statistics.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct StatsParamsBuffer {
+    channels: u32,
+    partial_count: u32,
+    workgroups_x: u32,
+    _padding: u32,
+}
+
+const _: () = assert!(std::mem::size_of::<StatsParamsBuffer>() == 16);
+unsafe impl Zeroable for StatsParamsBuffer {}
+unsafe impl Pod for StatsParamsBuffer {}
+
+impl StatsParamsBuffer {
+    pub fn new(channels: u32, size: (u32, u32)) -> StatsParamsBuffer {
+        let workgroups = StatsParamsBuffer::workgroups(size);
+        StatsParamsBuffer {
+            channels,
+            partial_count: workgroups.0 * workgroups.1,
+            workgroups_x: workgroups.0,
+            _padding: 0,
+        }
+    }
+
+    //Workgroups of the first pass over a grid of the given size.
+    pub fn workgroups(size: (u32, u32)) -> (u32, u32) {
+        (size.0.div_ceil(TILE_SIZE), size.1.div_ceil(TILE_SIZE))
+    }
+
+    pub fn partial_count(&self) -> u32 {
+        self.partial_count
+    }
+}
+
+//A read_write storage buffer of statistics.wgsl.
+pub fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    pipeline::buffer_entry(
+        binding,
+        wgpu::BufferBindingType::Storage { read_only: false },
+    )
+}
+
+//The two passes of a reduction in statistics.wgsl, by entry point, with the group 0 bindings each one uses.
+//The layouts are explicit so the textures can be the unfilterable float32 state of PDE simulations too.
+pub fn create_reduction_pipelines(
+    gpu: &Gpu,
+    (cells_entry_point, cells_entries): (&str, &[wgpu::BindGroupLayoutEntry]),
+    (partials_entry_point, partials_entries): (&str, &[wgpu::BindGroupLayoutEntry]),
+) -> (wgpu::ComputePipeline, wgpu::ComputePipeline) {
+    let shader = pipeline::create_shader(gpu, "statistics.wgsl", "Statistics");
+    let create_pipeline = |entry_point: &str, entries: &[wgpu::BindGroupLayoutEntry]| {
+        let name = format!("Statistics {}", entry_point);
+        let layout = pipeline::create_layout(gpu, format!("{} layout", name).as_str(), entries);
+        pipeline::create_compute_pipeline(gpu, &shader, &name, entry_point, &[&layout])
+    };
+    (
+        create_pipeline(cells_entry_point, cells_entries),
+        create_pipeline(partials_entry_point, partials_entries),
+    )
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CellStatistics {
+    pub generation: usize,
+    //Cells with any of the counted channels above one half.
+    pub live: u32,
+    //Cells that became live or stopped being live since the generation before.
+    pub births: u32,
+    pub deaths: u32,
+    //Per channel over every cell.
+    pub mean: [f32; 4],
+    pub variance: [f32; 4],
+}
+
+impl CellStatistics {
+    fn from_partial(generation: usize, p: &PartialBuffer) -> CellStatistics {
+        let cells = p.cells.max(1) as f32;
+        let mean = p.sum.map(|s| s / cells);
+        let mut variance = [0.0; 4];
+        for ((v, sum_sq), m) in variance.iter_mut().zip(p.sum_sq).zip(mean) {
+            *v = (sum_sq / cells - m * m).max(0.0);
+        }
+        CellStatistics {
+            generation,
+            live: p.live,
+            births: p.births,
+            deaths: p.deaths,
+            mean,
+            variance,
+        }
+    }
+}
+
+pub struct Statistics {
+    pub enabled: bool,
+    cells_pipeline: wgpu::ComputePipeline,
+    partials_pipeline: wgpu::ComputePipeline,
+    params: CachedBuffer,
+    //Partial sums of every tile, and how many fit.
+    partials: Option<(wgpu::Buffer, u32)>,
+    result: wgpu::Buffer,
+    //Bind groups for both ping-pong directions, and the texture version and partial count they were made for.
+    cells_bind_groups: Option<(u64, u32, [wgpu::BindGroup; 2])>,
+    partials_bind_group: Option<wgpu::BindGroup>,
+    //Tagged with the generation and texture version, so results for remade textures can be dropped.
+    readbacks: ReadbackRing<(usize, u64)>,
+}
+
+impl Statistics {
+    pub fn new(gpu: &Gpu) -> Statistics {
+        let (cells_pipeline, partials_pipeline) = create_reduction_pipelines(
+            gpu,
+            (
+                "reduce_cells",
+                &[
+                    pipeline::unfilterable_texture_entry(0),
+                    pipeline::unfilterable_texture_entry(1),
+                    storage_entry(2),
+                    pipeline::uniform_entry(3),
+                ],
+            ),
+            (
+                "reduce_partials",
+                &[
+                    storage_entry(2),
+                    pipeline::uniform_entry(3),
+                    storage_entry(4),
+                ],
+            ),
+        );
+        let result_size = std::mem::size_of::<PartialBuffer>() as u64;
+        Statistics {
+            enabled: false,
+            cells_pipeline,
+            partials_pipeline,
+            params: CachedBuffer::new(
+                gpu,
+                "Statistics Params Buffer",
+                wgpu::BufferUsages::UNIFORM,
+                bytemuck::bytes_of(&StatsParamsBuffer::zeroed()),
+            ),
+            partials: None,
+            result: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Statistics Result Buffer"),
+                size: result_size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            cells_bind_groups: None,
+            partials_bind_group: None,
+            readbacks: ReadbackRing::new(
+                gpu,
+                "Statistics Readback Buffer",
+                result_size,
+                READBACK_BUFFERS,
+            ),
+        }
+    }
+
+    //Records the reduction of the given generation of 2D textures, compared to the generation before it.
+    //channels is how many channels can make a cell live. texture_version has to change whenever the textures are remade.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &mut self,
+        gpu: &Gpu,
+        encoder: &mut wgpu::CommandEncoder,
+        textures: &ComputeTextures,
+        texture_version: u64,
+        size: (u32, u32),
+        generation: usize,
+        channels: u32,
+    ) {
+        if self.readbacks.is_full() {
+            return;
+        }
+        let workgroups = StatsParamsBuffer::workgroups(size);
+        let params = StatsParamsBuffer::new(channels, size);
+        let partial_count = params.partial_count();
+        self.params.write(gpu, bytemuck::bytes_of(&params));
+        let stale = !matches!(
+            &self.cells_bind_groups,
+            Some((version, count, _)) if *version == texture_version && *count == partial_count
+        );
+        if stale {
+            self.create_bind_groups(gpu, textures, texture_version, partial_count);
+        }
+        let (cells_bind_groups, partials_bind_group) =
+            match (&self.cells_bind_groups, &self.partials_bind_group) {
+                (Some((_, _, cells)), Some(partials)) => (cells, partials),
+                _ => return,
+            };
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Statistics reduction"),
+            });
+            compute_pass.set_pipeline(&self.cells_pipeline);
+            compute_pass.set_bind_group(0, &cells_bind_groups[generation % 2], &[]);
+            compute_pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+            compute_pass.set_pipeline(&self.partials_pipeline);
+            compute_pass.set_bind_group(0, partials_bind_group, &[]);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        self.readbacks.record(
+            encoder,
+            &self.result,
+            std::mem::size_of::<PartialBuffer>() as u64,
+            (generation, texture_version),
+        );
+    }
+
+    //Starts reading back what the last record wrote. Call after submitting its encoder.
+    pub fn submitted(&mut self) {
+        self.readbacks.submitted();
+    }
+
+    //Newest statistics of the current textures the GPU has finished since the last poll, if any. Never waits.
+    pub fn poll(&mut self, gpu: &Gpu, texture_version: u64) -> Option<CellStatistics> {
+        self.readbacks
+            .poll(gpu)
+            .into_iter()
+            .filter(|((_, version), _)| *version == texture_version)
+            .max_by_key(|((generation, _), _)| *generation)
+            .map(|((generation, _), data)| {
+                CellStatistics::from_partial(generation, &bytemuck::pod_read_unaligned(&data))
+            })
+    }
+
+    fn create_bind_groups(
+        &mut self,
+        gpu: &Gpu,
+        textures: &ComputeTextures,
+        texture_version: u64,
+        partial_count: u32,
+    ) {
+        let partials_size = partial_count as u64 * std::mem::size_of::<PartialBuffer>() as u64;
+        if self
+            .partials
+            .as_ref()
+            .is_none_or(|(_, capacity)| *capacity < partial_count)
+        {
+            self.partials = Some((
+                gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Statistics Partials Buffer"),
+                    size: partials_size,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                }),
+                partial_count,
+            ));
+        }
+        let partials = &self.partials.as_ref().unwrap().0;
+        let cells_layout = self.cells_pipeline.get_bind_group_layout(0);
+        let cells_bind_group = |frame: usize| {
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Statistics cells bind group"),
+                layout: &cells_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(
+                            &textures
+                                .frame_texture(frame)
+                                .create_view(&wgpu::TextureViewDescriptor::default()),
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(
+                            &textures
+                                .frame_texture(frame + 1)
+                                .create_view(&wgpu::TextureViewDescriptor::default()),
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: partials.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: self.params.buffer().as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        self.cells_bind_groups = Some((
+            texture_version,
+            partial_count,
+            [cells_bind_group(0), cells_bind_group(1)],
+        ));
+        self.partials_bind_group = Some(gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Statistics partials bind group"),
+            layout: &self.partials_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: partials.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.params.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.result.as_entire_binding(),
+                },
+            ],
+        }));
+    }
+}
@@ -1,41 +1,271 @@
 use std::rc::Rc;
 
-use bytemuck::bytes_of;
-use wgpu::{
-    util::{BufferInitDescriptor, DeviceExt},
-    BufferUsages,
+use bytemuck::{bytes_of, Pod, Zeroable};
+use wgpu::BufferUsages;
+
+use super::boundary::Boundary;
+use crate::app::{
+    gpu::{bindgroup::CachedBuffer, Gpu},
+    math::UVec2,
+    rule::{Rule, MAX_SPECIES},
 };
 
-use crate::app::{gpu::bindgroup::ToBindgroup, rule::Rule};
+//Maximum number of rules that can be assigned to regions of the grid through the rule mask.
+pub const MAX_REGION_RULES: usize = 16;
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct TotalisticParamsBuffer {
+    rules: [Rule; MAX_SPECIES],
+    species: u32,
+    use_rule_mask: u32,
+    region_rule_count: u32,
+    boundary: u32,
+}
 
 pub struct TotalisticParameters {
-    pub rule: Rule,
+    //Rule used by each species. A classic single species simulation only uses the first.
+    pub rules: [Rule; MAX_SPECIES],
+    //Number of competing species. 1 is a classic two state simulation, 2 is Immigration style, 4 is QuadLife style.
+    pub species: u32,
+    //Rules picked per cell by the rule mask. When enabled, these replace the per species rules.
+    pub region_rules: Vec<Rule>,
+    pub use_rule_mask: bool,
+    pub boundary: Boundary,
+    //Index into region_rules for every cell. Kept on the cpu so it can be painted and resized.
+    rule_mask: image::GrayImage,
+    rule_mask_texture: wgpu::Texture,
+    rule_buffer: CachedBuffer,
+    region_rule_buffer: CachedBuffer,
+    bind_group: wgpu::BindGroup,
     bind_group_layout: Rc<wgpu::BindGroupLayout>,
 }
 
 impl TotalisticParameters {
-    pub fn new(layout: Rc<wgpu::BindGroupLayout>) -> TotalisticParameters {
-        TotalisticParameters {
-            rule: Rule::from_rule_str("B3/S23").unwrap(),
-            bind_group_layout: layout.clone(),
+    pub fn new(layout: Rc<wgpu::BindGroupLayout>, gpu: &Gpu) -> TotalisticParameters {
+        let rule_mask = image::GrayImage::new(1, 1);
+        let rule_mask_texture = TotalisticParameters::create_mask_texture(gpu, &rule_mask);
+        let rule_buffer = CachedBuffer::new(
+            gpu,
+            "Rule buffer",
+            BufferUsages::UNIFORM,
+            bytes_of(&TotalisticParamsBuffer::zeroed()),
+        );
+        let region_rule_buffer = CachedBuffer::new(
+            gpu,
+            "Region rule buffer",
+            BufferUsages::STORAGE,
+            bytemuck::cast_slice(&[Rule::zeroed(); MAX_REGION_RULES]),
+        );
+        let bind_group = TotalisticParameters::create_bind_group(
+            gpu,
+            &layout,
+            &rule_buffer,
+            &region_rule_buffer,
+            &rule_mask_texture,
+        );
+        let mut params = TotalisticParameters {
+            rules: [Rule::from_rule_str("B3/S23").unwrap(); MAX_SPECIES],
+            species: 1,
+            region_rules: vec![Rule::from_rule_str("B3/S23").unwrap()],
+            use_rule_mask: false,
+            boundary: Boundary::default(),
+            rule_mask,
+            rule_mask_texture,
+            rule_buffer,
+            region_rule_buffer,
+            bind_group,
+            bind_group_layout: layout,
+        };
+        params.prepare(gpu);
+        params
+    }
+
+    //Uploads the rules if they changed since the last step. Call before using bind_group.
+    pub fn prepare(&mut self, gpu: &Gpu) {
+        self.rule_buffer.write(gpu, bytes_of(&self.to_buffer()));
+        let mut region_rules = [Rule::zeroed(); MAX_REGION_RULES];
+        for (dst, src) in region_rules.iter_mut().zip(self.region_rules.iter()) {
+            *dst = *src;
         }
+        self.region_rule_buffer
+            .write(gpu, bytemuck::cast_slice(&region_rules));
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
     }
-}
 
-impl ToBindgroup for TotalisticParameters {
-    fn to_bind_group(&self, gpu: &crate::app::gpu::Gpu) -> wgpu::BindGroup {
-        let rule_buffer = gpu.device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Rule buffer"),
-            contents: bytes_of(&self.rule),
-            usage: BufferUsages::UNIFORM,
+    //The mask texture is only replaced when its size or whole contents change, so the bind group is rebuilt along with it.
+    fn replace_mask_texture(&mut self, gpu: &Gpu) {
+        self.rule_mask_texture = TotalisticParameters::create_mask_texture(gpu, &self.rule_mask);
+        self.bind_group = TotalisticParameters::create_bind_group(
+            gpu,
+            &self.bind_group_layout,
+            &self.rule_buffer,
+            &self.region_rule_buffer,
+            &self.rule_mask_texture,
+        );
+    }
+
+    //Uses the same rule for every species.
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rules = [rule; MAX_SPECIES];
+    }
+
+    pub fn to_buffer(&self) -> TotalisticParamsBuffer {
+        TotalisticParamsBuffer {
+            rules: self.rules,
+            species: self.species.clamp(1, MAX_SPECIES as u32),
+            use_rule_mask: (self.use_rule_mask && !self.region_rules.is_empty()) as u32,
+            region_rule_count: self.region_rules.len().min(MAX_REGION_RULES) as u32,
+            boundary: self.boundary.code(),
+        }
+    }
+
+    //Rule a cell of the given species follows. Same as cell_rules in totalistic.wgsl.
+    pub fn cell_rule(&self, x: u32, y: u32, species: usize) -> Rule {
+        if self.use_rule_mask && !self.region_rules.is_empty() {
+            let (w, h) = self.rule_mask.dimensions();
+            let index = if x < w && y < h {
+                self.rule_mask.get_pixel(x, y).0[0] as usize
+            } else {
+                0
+            };
+            let count = self.region_rules.len().min(MAX_REGION_RULES);
+            return self.region_rules[index.min(count - 1)];
+        }
+        self.rules[species]
+    }
+
+    //Replaces the rule mask with an image. Gray levels are split evenly between the region rules, so black is the first rule and white is the last.
+    pub fn load_rule_mask(&mut self, gpu: &Gpu, img: &image::GrayImage) {
+        let count = self.region_rules.len().clamp(1, MAX_REGION_RULES) as u32;
+        let (w, h) = self.rule_mask.dimensions();
+        let resized = image::imageops::resize(img, w, h, image::imageops::FilterType::Nearest);
+        self.rule_mask = image::GrayImage::from_fn(w, h, |x, y| {
+            let v = resized.get_pixel(x, y).0[0] as u32;
+            image::Luma([(v * count / 256) as u8])
+        });
+        self.replace_mask_texture(gpu);
+    }
+
+    //Matches the rule mask to the simulation size, keeping the painted regions.
+    pub fn resize_rule_mask(&mut self, gpu: &Gpu, size: UVec2) {
+        if self.rule_mask.dimensions() != (size.x, size.y) {
+            self.rule_mask = image::imageops::resize(
+                &self.rule_mask,
+                size.x,
+                size.y,
+                image::imageops::FilterType::Nearest,
+            );
+            self.replace_mask_texture(gpu);
+        }
+    }
+
+    pub fn clear_rule_mask(&mut self, gpu: &Gpu) {
+        for p in self.rule_mask.pixels_mut() {
+            *p = image::Luma([0]);
+        }
+        self.replace_mask_texture(gpu);
+    }
+
+    //Paints a filled circle of the given rule index into the mask and uploads the changed rows.
+    pub fn paint_rule_mask(&mut self, gpu: &Gpu, center: UVec2, radius: u32, index: u8) {
+        let (w, h) = self.rule_mask.dimensions();
+        let x0 = center.x.saturating_sub(radius);
+        let y0 = center.y.saturating_sub(radius);
+        let x1 = (center.x + radius + 1).min(w);
+        let y1 = (center.y + radius + 1).min(h);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+        let r2 = (radius * radius) as i64;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let dx = x as i64 - center.x as i64;
+                let dy = y as i64 - center.y as i64;
+                if dx * dx + dy * dy <= r2 {
+                    self.rule_mask.put_pixel(x, y, image::Luma([index]));
+                }
+            }
+        }
+        gpu.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.rule_mask_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: x0, y: y0, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            self.rule_mask.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: (y0 * w + x0) as u64,
+                bytes_per_row: std::num::NonZeroU32::new(w),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: x1 - x0,
+                height: y1 - y0,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    fn create_mask_texture(gpu: &Gpu, mask: &image::GrayImage) -> wgpu::Texture {
+        let (width, height) = mask.dimensions();
+        let texture_size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Rule mask texture"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Uint,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
         });
+        gpu.queue.write_texture(
+            texture.as_image_copy(),
+            mask.as_raw(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(width),
+                rows_per_image: None,
+            },
+            texture_size,
+        );
+        texture
+    }
+
+    fn create_bind_group(
+        gpu: &Gpu,
+        layout: &wgpu::BindGroupLayout,
+        rule_buffer: &CachedBuffer,
+        region_rule_buffer: &CachedBuffer,
+        rule_mask_texture: &wgpu::Texture,
+    ) -> wgpu::BindGroup {
         gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Totalistic Params BindGroup"),
-            layout: self.bind_group_layout.as_ref(),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: rule_buffer.as_entire_binding(),
-            }],
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: rule_buffer.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: region_rule_buffer.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &rule_mask_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
         })
     }
 }
@@ -0,0 +1,150 @@
+use std::rc::Rc;
+
+use bytemuck::{Pod, Zeroable};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::app::gpu::{bindgroup::UniformBatch, Gpu};
+
+//How cells are picked for updating each generation.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum UpdateScheme {
+    //Every cell updates every generation.
+    Synchronous,
+    //Each cell updates with probability alpha. Approximates random sequential updating.
+    RandomSequential { alpha: f32 },
+    //Cells alternate in a checkerboard pattern, half of the grid per generation.
+    Checkerboard,
+    //Cells are split into block_size^2 interleaved groups that update in turn.
+    BlockSequential { block_size: u32 },
+}
+
+impl UpdateScheme {
+    //Must match the UPDATE_* constants in update_scheme.wgsl
+    fn mode(&self) -> u32 {
+        match self {
+            UpdateScheme::Synchronous => 0,
+            UpdateScheme::RandomSequential { .. } => 1,
+            UpdateScheme::Checkerboard => 2,
+            UpdateScheme::BlockSequential { .. } => 3,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UpdateParamsBuffer {
+    mode: u32,
+    alpha: f32,
+    block_size: u32,
+    step_seed: u32,
+    generation: u32,
+    _padding: [u32; 3],
+}
+
+//Implemented by hand like PartialBuffer in statistics.rs.
+const _: () = assert!(std::mem::size_of::<UpdateParamsBuffer>() == 32);
+unsafe impl Zeroable for UpdateParamsBuffer {}
+unsafe impl Pod for UpdateParamsBuffer {}
+
+//Cpu copies of the hashes in update_scheme.wgsl, for the reference engine.
+pub fn pcg_hash(v: u32) -> u32 {
+    let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+pub fn random_unit(x: u32, y: u32, seed: u32) -> f32 {
+    let h = pcg_hash(x ^ pcg_hash(y ^ pcg_hash(seed)));
+    h as f32 / 4294967295.0
+}
+
+impl UpdateParamsBuffer {
+    //Same as should_update in update_scheme.wgsl.
+    pub fn should_update(&self, x: u32, y: u32) -> bool {
+        match self.mode {
+            1 => random_unit(x, y, self.step_seed) < self.alpha,
+            2 => (x + y) % 2 == self.generation % 2,
+            3 => {
+                let b = self.block_size.max(1);
+                x % b + (y % b) * b == self.generation % (b * b)
+            }
+            _ => true,
+        }
+    }
+
+    pub fn step_seed(&self) -> u32 {
+        self.step_seed
+    }
+}
+
+pub struct UpdateParameters {
+    pub scheme: UpdateScheme,
+    seed: u64,
+    rng: StdRng,
+    step_seed: u32,
+    generation: u32,
+    batch: UniformBatch,
+    bind_group_layout: Rc<wgpu::BindGroupLayout>,
+}
+
+impl UpdateParameters {
+    pub fn new(layout: Rc<wgpu::BindGroupLayout>) -> UpdateParameters {
+        UpdateParameters {
+            scheme: UpdateScheme::Synchronous,
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
+            step_seed: 0,
+            generation: 0,
+            batch: UniformBatch::new("Update Params Batch Buffer"),
+            bind_group_layout: layout,
+        }
+    }
+
+    //Restarts the random sequence. Runs with the same seed pick the same cells.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    //Called once before every generation.
+    pub fn next_step(&mut self, generation: usize) {
+        self.step_seed = self.rng.gen();
+        self.generation = generation as u32;
+    }
+
+    //Advances through n generations, writing the parameters of each so they can all be recorded in one submission.
+    //See batch_bind_groups.
+    pub fn write_batch(&mut self, gpu: &Gpu, first_generation: usize, n: usize) {
+        let uniforms: Vec<UpdateParamsBuffer> = (first_generation..first_generation + n)
+            .map(|generation| {
+                self.next_step(generation);
+                self.to_buffer()
+            })
+            .collect();
+        self.batch.write(gpu, &self.bind_group_layout, &uniforms);
+    }
+
+    pub fn batch_bind_groups(&self) -> &[wgpu::BindGroup] {
+        self.batch.bind_groups()
+    }
+
+    pub fn to_buffer(&self) -> UpdateParamsBuffer {
+        let (alpha, block_size) = match self.scheme {
+            UpdateScheme::RandomSequential { alpha } => (alpha, 1),
+            UpdateScheme::BlockSequential { block_size } => (1.0, block_size),
+            _ => (1.0, 1),
+        };
+        UpdateParamsBuffer {
+            mode: self.scheme.mode(),
+            alpha,
+            block_size,
+            step_seed: self.step_seed,
+            generation: self.generation,
+            _padding: [0; 3],
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::rc::Rc;
+
+use crate::app::{
+    gpu::{bindgroup::CachedBuffer, Gpu},
+    rule3d::Rule3D,
+};
+
+pub struct VolumeParameters {
+    pub rule: Rule3D,
+    //Edge length of the cubic volume in cells.
+    pub size: u32,
+    //Edge length of the cube the random soup is placed in, as a fraction of the volume size.
+    pub soup_size: f32,
+    pub soup_density: f32,
+    rule_buffer: CachedBuffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl VolumeParameters {
+    pub fn new(layout: Rc<wgpu::BindGroupLayout>, gpu: &Gpu) -> VolumeParameters {
+        let rule = Rule3D::from_rule_str("445").unwrap();
+        let rule_buffer = CachedBuffer::new(
+            gpu,
+            "Rule3D Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::bytes_of(&rule),
+        );
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Volume Parameters bind group"),
+            layout: layout.as_ref(),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: rule_buffer.buffer().as_entire_binding(),
+            }],
+        });
+        VolumeParameters {
+            rule,
+            size: 64,
+            soup_size: 0.5,
+            soup_density: 0.3,
+            rule_buffer,
+            bind_group,
+        }
+    }
+
+    //Uploads the rule if it changed since the last step. Call before using bind_group.
+    pub fn prepare(&mut self, gpu: &Gpu) {
+        self.rule_buffer.write(gpu, bytemuck::bytes_of(&self.rule));
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
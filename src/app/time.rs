@@ -1,5 +1,8 @@
 use std::time::{Duration, Instant};
 
+//Largest number of generations recorded into one submission.
+pub const MAX_BATCH_SIZE: u32 = 1024;
+
 pub struct Time {
     last_frame: Instant,
     last_update: Instant,
@@ -77,10 +80,22 @@ impl Time {
         is_time_left && !max_frames_reached && delayed_enough
     }
 
-    //returns true if the simulation should run another update tick. This checks to see if there's enough time, and that the max updates per frame havent happened.
-    pub fn update_tick(&mut self) {
-        self.updates_this_frame += 1;
-        self.updates_since_last_fps_check += 1;
+    //Number of updates the next batch can run in one submission. 0 if the simulation can't update yet.
+    //Delayed updates go one at a time so the delay is kept between every generation.
+    pub fn batch_size(&self) -> u32 {
+        if !self.can_update() {
+            0
+        } else if self.update_delay > Duration::ZERO {
+            1
+        } else {
+            (self.target_updates_per_frame - self.updates_this_frame).min(MAX_BATCH_SIZE)
+        }
+    }
+
+    //Records a batch of updates that just ran.
+    pub fn update_ticks(&mut self, updates: u32) {
+        self.updates_this_frame += updates;
+        self.updates_since_last_fps_check += updates;
         self.last_update = Instant::now();
     }
 
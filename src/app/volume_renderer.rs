@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use bytemuck::Zeroable;
+
+use crate::app::{
+    gpu::{bindgroup::CachedBuffer, Gpu},
+    math::{IVec2, Vertex},
+    orbit_camera::OrbitCameraBuffer,
+    wgsl_preproc::WgslPreProcessor,
+    App,
+};
+
+//Draws volumetric simulations by ray marching through the 3D state texture from the orbit camera.
+pub struct VolumeRenderer {
+    pipeline: wgpu::RenderPipeline,
+    texture_layout: wgpu::BindGroupLayout,
+    camera_buffer: CachedBuffer,
+    camera_bind_group: wgpu::BindGroup,
+    //Bind groups over the two ping-pong volumes, keyed by Simulation::get_current_texture_key like SimulationRenderer's.
+    texture_bind_groups: HashMap<(u64, usize), wgpu::BindGroup>,
+}
+
+impl VolumeRenderer {
+    pub fn new(gpu: &Gpu) -> VolumeRenderer {
+        let shader_src =
+            WgslPreProcessor::load_and_process("volume_render.wgsl", "./shaders").unwrap();
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Volume render shader"),
+                source: wgpu::ShaderSource::Wgsl(shader_src.into()),
+            });
+
+        let pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Volume Render Pipeline"),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: gpu.config.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+        let camera_buffer = CachedBuffer::new(
+            gpu,
+            "Orbit Camera Buffer",
+            wgpu::BufferUsages::UNIFORM,
+            bytemuck::bytes_of(&OrbitCameraBuffer::zeroed()),
+        );
+        let camera_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipeline.get_bind_group_layout(1),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.buffer().as_entire_binding(),
+            }],
+            label: Some("Orbit camera bind group"),
+        });
+        VolumeRenderer {
+            texture_layout: pipeline.get_bind_group_layout(0),
+            pipeline,
+            camera_buffer,
+            camera_bind_group,
+            texture_bind_groups: HashMap::new(),
+        }
+    }
+
+    //Draws the fullscreen quad given by the vertex and index buffers.
+    pub fn render(
+        &mut self,
+        gpu: &Gpu,
+        app: &App,
+        view: &wgpu::TextureView,
+        (vertex_buffer, index_buffer, num_indices): (&wgpu::Buffer, &wgpu::Buffer, u32),
+        window_size: IVec2,
+    ) -> wgpu::CommandBuffer {
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Volume Render Encoder"),
+            });
+
+        let key = app.simulation.get_current_texture_key();
+        if !self.texture_bind_groups.contains_key(&key) {
+            self.texture_bind_groups
+                .retain(|(version, _), _| *version == key.0);
+            let render_tex = app.simulation.get_current_texture();
+            let texture_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.texture_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &render_tex.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                }],
+                label: Some("Volume texture bind group"),
+            });
+            self.texture_bind_groups.insert(key, texture_bind_group);
+        }
+        let texture_bind_group = &self.texture_bind_groups[&key];
+
+        let aspect = window_size.x as f32 / window_size.y as f32;
+        let states = app.simulation.volume_state.params.rule.states;
+        //Only uploaded when the camera moves or the rule changes its state count.
+        self.camera_buffer.write(
+            gpu,
+            bytemuck::bytes_of(&app.orbit_camera.to_buffer(aspect, states)),
+        );
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Volume Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, texture_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..num_indices, 0, 0..1);
+        }
+        encoder.finish()
+    }
+}
@@ -6,6 +6,15 @@ pub struct WgslPreProcessor {}
 pub enum PreProcessError {
     FileNotFound(String),
     FileReadError(String),
+    MissingSnippet(String),
+}
+
+//Where a snippet was inserted into a processed shader, so errors can be reported relative to it.
+pub struct SnippetLocation {
+    pub name: String,
+    //1-based line of the processed shader the snippet starts on.
+    pub first_line: usize,
+    pub line_count: usize,
 }
 
 impl WgslPreProcessor {
@@ -14,7 +23,7 @@ impl WgslPreProcessor {
         let reg = r#"\#include\("(.+.wgsl)"\);"#;
         let re = Regex::new(reg).unwrap();
         let mut processed = src.to_owned();
-        for cap in re.captures(src) {
+        for cap in re.captures_iter(src) {
             let filename = cap[1].to_owned();
             let file_path = root.join(filename);
             let repl = cap[0].to_owned();
@@ -38,6 +47,84 @@ impl WgslPreProcessor {
         Ok(processed)
     }
 
+    //Replaces #snippet("name") markers with user code. Every marker needs a matching snippet.
+    pub fn insert_snippets(
+        src: &str,
+        snippets: &[(&str, &str)],
+    ) -> Result<(String, Vec<SnippetLocation>), PreProcessError> {
+        let re = Regex::new(r#"\#snippet\("(\w+)"\)"#).unwrap();
+        let mut processed = String::new();
+        let mut locations = Vec::new();
+        let mut last = 0;
+        for cap in re.captures_iter(src) {
+            let marker = cap.get(0).unwrap();
+            let name = &cap[1];
+            let code = match snippets.iter().find(|(n, _)| *n == name) {
+                Some((_, code)) => code.trim(),
+                None => {
+                    return Err(PreProcessError::MissingSnippet(format!(
+                        "No snippet named: {}",
+                        name
+                    )))
+                }
+            };
+            processed.push_str(&src[last..marker.start()]);
+            locations.push(SnippetLocation {
+                name: name.to_owned(),
+                first_line: processed.matches('\n').count() + 1,
+                line_count: code.lines().count().max(1),
+            });
+            processed.push_str(code);
+            last = marker.end();
+        }
+        processed.push_str(&src[last..]);
+        Ok((processed, locations))
+    }
+
+    //Parses and validates a shader with naga before it reaches wgpu, which panics on invalid shaders.
+    //Errors inside a snippet are reported with line numbers relative to that snippet.
+    pub fn validate(src: &str, snippets: &[SnippetLocation]) -> Result<(), String> {
+        let (message, location) = match naga::front::wgsl::parse_str(src) {
+            Ok(module) => {
+                let mut validator = naga::valid::Validator::new(
+                    naga::valid::ValidationFlags::all(),
+                    naga::valid::Capabilities::empty(),
+                );
+                match validator.validate(&module) {
+                    Ok(_) => return Ok(()),
+                    Err(e) => {
+                        //Naga nests the actual cause inside function and expression errors.
+                        let mut causes = Vec::new();
+                        let mut cause: Option<&dyn std::error::Error> = Some(e.as_inner());
+                        while let Some(c) = cause {
+                            causes.push(c.to_string());
+                            cause = c.source();
+                        }
+                        //The last span is the most specific one.
+                        let location = e.spans().last().map(|(span, _)| span.location(src));
+                        (causes.join(": "), location)
+                    }
+                }
+            }
+            Err(e) => (e.to_string(), e.location(src)),
+        };
+        let line = match location {
+            Some(l) => l.line_number as usize,
+            None => return Err(message),
+        };
+        for s in snippets {
+            if line >= s.first_line && line < s.first_line + s.line_count {
+                return Err(format!(
+                    "{} snippet, line {}: {}",
+                    s.name,
+                    line - s.first_line + 1,
+                    message
+                ));
+            }
+        }
+        Err(format!("Shader line {}: {}", line, message))
+    }
+
     pub fn load_and_process(file: &str, shader_root: &str) -> Result<String, PreProcessError> {
         let root = PathBuf::from_str(shader_root).unwrap();
         match fs::read_to_string(root.join(file)) {
@@ -2,9 +2,11 @@ mod app;
 mod renderer;
 use app::{
     gpu::Gpu,
+    image_util::ImageUtil,
     math::{IVec2, UVec2},
     sim_renderer::RendererType,
-    time::Time,
+    simulation::{cpu_engine, Simulation, SimulationType},
+    time::{Time, MAX_BATCH_SIZE},
 };
 use renderer::Renderer;
 use winit::{
@@ -19,9 +21,80 @@ use std::time::Duration;
 use crate::app::App;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--headless") {
+        run_headless(&args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("--verify") {
+        run_verify(&args[2..]);
+        return;
+    }
     pollster::block_on(run());
 }
 
+//Runs the default simulation without a window and saves the last generation.
+//Usage: wgpu_automata --headless [generations] [output.png]
+fn run_headless(args: &[String]) {
+    let generations: usize = args.first().and_then(|g| g.parse().ok()).unwrap_or(100);
+    let output = args.get(1).map_or("headless.png", String::as_str);
+    let image = match Gpu::new_headless() {
+        Ok(gpu) => {
+            let mut simulation = Simulation::new(&gpu, UVec2::new(512, 512));
+            //Remaking takes the size from gol1.png, so the whole image is stepped like the cpu fallback does.
+            let size = simulation.size;
+            simulation.remake(&gpu, size, SimulationType::Totalistic);
+            simulation.get_simulation_state_mut().paused = false;
+            let mut remaining = generations;
+            while remaining > 0 {
+                let batch = remaining.min(MAX_BATCH_SIZE as usize);
+                simulation.step_n(&gpu, batch);
+                remaining -= batch;
+            }
+            let size = simulation.size;
+            ImageUtil::tex_to_buffer(simulation.get_current_texture(), &gpu, size.x, size.y)
+                .into_image()
+        }
+        Err(e) => {
+            eprintln!("Couldn't create a headless device: {}", e);
+            println!("Running on the cpu instead");
+            cpu_engine::run_default(generations)
+        }
+    };
+    match image.save(output) {
+        Ok(()) => println!("Saved generation {} to {}", generations, output),
+        Err(e) => eprintln!("Couldn't save {}: {}", output, e),
+    }
+}
+
+//Runs the default totalistic and neural simulations on the GPU and the cpu side by side, and reports any difference.
+//Usage: wgpu_automata --verify [generations]
+fn run_verify(args: &[String]) {
+    let generations: usize = args.first().and_then(|g| g.parse().ok()).unwrap_or(100);
+    let gpu = match Gpu::new_headless() {
+        Ok(gpu) => gpu,
+        Err(e) => {
+            eprintln!("Couldn't create a headless device: {}", e);
+            return;
+        }
+    };
+    let size = UVec2::new(512, 512);
+    let mut simulation = Simulation::new(&gpu, size);
+    for (name, s_type, packed) in [
+        ("Totalistic", SimulationType::Totalistic, false),
+        ("Packed totalistic", SimulationType::Totalistic, true),
+        ("Neural", SimulationType::Neural, false),
+    ] {
+        simulation.totalistic_state.packed.enabled = packed;
+        simulation.remake(&gpu, size, s_type);
+        println!(
+            "{}: {}",
+            name,
+            simulation.verify_against_cpu(&gpu, generations)
+        );
+    }
+}
+
 async fn run() {
     let renderer_size = IVec2::new(1024, 1024);
     let sim_size = UVec2::new(2048, 2048);
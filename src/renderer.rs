@@ -44,7 +44,9 @@ impl Renderer {
             gpu.size = new_size;
             gpu.config.width = new_size.width;
             gpu.config.height = new_size.height;
-            gpu.surface.configure(&gpu.device, &gpu.config);
+            if let Some(surface) = &gpu.surface {
+                surface.configure(&gpu.device, &gpu.config);
+            }
             let s = IVec2::new(gpu.size.width as i32, gpu.size.height as i32);
             self.sim_renderer.resize(s);
         }
@@ -57,7 +59,12 @@ impl Renderer {
         window: &Window,
     ) -> Result<(), wgpu::SurfaceError> {
         // submit will accept anything that implements IntoIter
-        let output = gpu.surface.get_current_texture().unwrap();
+        let output = gpu
+            .surface
+            .as_ref()
+            .expect("Rendering needs a window surface")
+            .get_current_texture()
+            .unwrap();
         let sim_render = self.sim_renderer.render(gpu, app, &output).unwrap();
         let gui_render = self
             .gui